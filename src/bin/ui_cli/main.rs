@@ -17,17 +17,68 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
-use clap::{crate_version, App, Arg};
-use phys_plotter::data::TwoVarDataSet;
+use clap::{crate_version, App, Arg, ArgGroup, ArgMatches};
+use phys_plotter::data::{
+    ColumnMapping, Expression, FitMethod, FitModel, FitOptions, FitResults, GradientLineMethod,
+    Notation, ParseError, Repeats, Table, TableColumn, TableError, Transform, Transforms,
+    TwoVarDataSet, Uncertainty,
+};
 use phys_plotter::default_values as defv;
-use phys_plotter::plot;
+use phys_plotter::plot::{
+    self, Backends, FittedSeries, ImageFormat, LegendPosition, PlotSpec, Series, SeriesStyle,
+    TickSpacing,
+};
 use phys_plotter::save_format::PhysPlotterFile;
-use plotters::prelude::*;
 use std::process::exit;
+use std::str::FromStr;
 
 /// Validator for uncertainties
 fn du_validator(num: &str) -> Result<(), String> {
+    match num.parse::<Uncertainty>() {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Validator for instrument resolutions
+fn resolution_validator(num: &str) -> Result<(), String> {
     match num.parse::<f64>() {
+        Ok(resolution) if resolution >= 0.0 && resolution.is_finite() => Ok(()),
+        Ok(_) => Err(String::from("resolution must be a non-negative number")),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Validator for outlier thresholds
+fn outlier_threshold_validator(num: &str) -> Result<(), String> {
+    match num.parse::<f64>() {
+        Ok(threshold) if threshold >= 0.0 && threshold.is_finite() => Ok(()),
+        Ok(_) => Err(String::from(
+            "outlier threshold must be a non-negative number",
+        )),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Validator for polynomial degree
+fn degree_validator(num: &str) -> Result<(), String> {
+    match num.parse::<usize>() {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Validator for model expressions
+fn expression_validator(expression: &str) -> Result<(), String> {
+    match expression.parse::<Expression>() {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Validator for series colours
+fn colour_validator(colour: &str) -> Result<(), String> {
+    match colour.parse::<plot::Colour>() {
         Ok(_) => Ok(()),
         Err(error) => Err(format!("{}", error)),
     }
@@ -41,143 +92,733 @@ fn size_validator(num: &str) -> Result<(), String> {
     }
 }
 
+/// Validator for axis ranges
+fn range_validator(range: &str) -> Result<(), String> {
+    match plot::parse_axis_range(range) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Validator for tick spacings
+fn ticks_validator(ticks: &str) -> Result<(), String> {
+    match ticks.parse::<TickSpacing>() {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("{}", error)),
+    }
+}
+
+/// Describe an error in the dataset from origin, quoting the offending line
+fn describe_parse_error(origin: &str, dataset: &str, error: &ParseError) -> String {
+    format!(
+        "{}:{}:{}: {}\n{}",
+        origin,
+        error.line,
+        error.columns.start + 1,
+        error.kind,
+        error.highlight(dataset)
+    )
+}
+
+/// Describe an error in the table from origin
+fn describe_table_error(origin: &str, table: &str, error: &TableError) -> String {
+    match error {
+        TableError::Parse(error) => describe_parse_error(origin, table, error),
+        TableError::UnknownColumn(_) => format!("{}: {}", origin, error),
+    }
+}
+
+/// Columns of the tables chosen by the user
+fn column_mapping(matches: &ArgMatches) -> ColumnMapping {
+    let column = |name| matches.value_of(name).map(TableColumn::from);
+    let default = ColumnMapping::default();
+    ColumnMapping {
+        x: column("x_column").unwrap_or(default.x),
+        x_uncertainty: column("ux_column"),
+        y: column("y_column").unwrap_or(default.y),
+        y_uncertainty: column("uy_column"),
+    }
+}
+
+/// Value of an option for the series at `index`, None for its default.
+/// A value given once applies to every series, and values given several
+/// times apply to the series in turn, leaving the defaults to the rest
+fn series_value<'a>(matches: &'a ArgMatches, name: &str, index: usize) -> Option<&'a str> {
+    let values: Vec<&str> = matches.values_of(name)?.collect();
+    match values[..] {
+        [value] => Some(value),
+        _ => values.get(index).copied(),
+    }
+}
+
+/// The saved file given with --psp-file, which must be the only DATASET_FILE
+fn psp_file_name(matches: &ArgMatches) -> Result<&str, String> {
+    let files: Vec<&str> = matches.values_of("DATASET_FILE").unwrap().collect();
+    match files[..] {
+        [file_name] => Ok(file_name),
+        _ => Err(format!(
+            "a saved file is plotted alone, but {} DATASET_FILEs are given",
+            files.len()
+        )),
+    }
+}
+
+/// Command line options
+fn app() -> App<'static> {
+    App::new("Physics Plotter")
+    .version(crate_version!())
+    .author("Zhang Maiyun <me@myzhangll.xyz>")
+    .about("Plot physics two-variable observation data with best-fit lines, max,min-gradient lines, and error bars.")
+    .after_help("SERIES OPTIONS:\n    --name, --colour, --marker, --default-ux, --default-uy, --decimal-separator,\n    --repeat-uncertainty and --resolution apply to every series if given once, and\n    otherwise to the series in the order of the files, the rest taking the defaults")
+    .arg(Arg::new("DATASET_FILE")
+        .help("Sets the data files to parse, each of which is plotted as a series")
+        .required(true)
+        .multiple_values(true)
+        .index(1))
+    .arg(Arg::new("psp_file")
+        .help("Indicates that a physics plotter saved file is used as the only DATASET_FILE")
+        .short('p')
+        .long("psp-file")
+        .conflicts_with_all(&["title", "x_label", "y_label", "dux", "duy", "backend", "fit_method", "gradient_lines", "residuals", "outlier_threshold", "degree", "expression", "initial_guesses", "x_transform", "y_transform", "scale", "name", "colour", "marker", "decimal_separator", "repeat_uncertainty", "resolution", "table"]))
+    .arg(Arg::new("title")
+        .short('t')
+        .long("title")
+        .value_name("TITLE")
+        .default_value(defv::TITLE)
+        .help("Sets the title of the plot"))
+    .arg(Arg::new("x_label")
+        .short('x')
+        .long("x-label")
+        .value_name("X_LABEL")
+        .default_value(defv::X_LABEL)
+        .help("Sets the x axis label"))
+    .arg(Arg::new("y_label")
+        .short('y')
+        .long("y-label")
+        .value_name("Y_LABEL")
+        .default_value(defv::Y_LABEL)
+        .help("Sets the y axis label"))
+    .arg(Arg::new("dux")
+        .short('X')
+        .long("default-ux")
+        .value_name("DEFAULT_X_UNCERTAINTY")
+        .default_value(defv::X_UNCERTAINTY)
+        .validator(du_validator)
+        .multiple_occurrences(true)
+        .help("Sets a default value for x uncertainty such as 0.01, 2% or 0.5% + 0.01, for each series"))
+    .arg(Arg::new("duy")
+        .short('Y')
+        .long("default-uy")
+        .value_name("DEFAULT_Y_UNCERTAINTY")
+        .default_value(defv::Y_UNCERTAINTY)
+        .validator(du_validator)
+        .multiple_occurrences(true)
+        .help("Sets a default value for y uncertainty such as 0.01, 2% or 0.5% + 0.01, for each series"))
+    .arg(Arg::new("name")
+        .short('n')
+        .long("name")
+        .value_name("NAME")
+        .multiple_occurrences(true)
+        .help("Sets the name of each series in the legend [default: the file name if there are several series]"))
+    .arg(Arg::new("colour")
+        .short('c')
+        .long("colour")
+        .value_name("COLOUR")
+        .validator(colour_validator)
+        .multiple_occurrences(true)
+        .help("Sets the colour of each series as #rrggbb"))
+    .arg(Arg::new("marker")
+        .short('m')
+        .long("marker")
+        .value_name("MARKER")
+        .possible_values(["plus", "cross", "circle", "square", "triangle"])
+        .multiple_occurrences(true)
+        .help("Sets the marker of the points of each series"))
+    .arg(Arg::new("decimal_separator")
+        .short('D')
+        .long("decimal-separator")
+        .value_name("SEPARATOR")
+        .possible_values(["point", "comma"])
+        .default_value(defv::DECIMAL_SEPARATOR)
+        .multiple_occurrences(true)
+        .help("Sets the decimal separator of the numbers in each dataset"))
+    .arg(Arg::new("repeat_uncertainty")
+        .short('R')
+        .long("repeat-uncertainty")
+        .value_name("METHOD")
+        .possible_values(["half-range", "standard-error"])
+        .default_value(defv::REPEAT_UNCERTAINTY)
+        .multiple_occurrences(true)
+        .help("Sets how repeated readings of y such as `x [y1 y2 y3]` give their uncertainty in each series"))
+    .arg(Arg::new("resolution")
+        .long("resolution")
+        .value_name("RESOLUTION")
+        .default_value(defv::RESOLUTION)
+        .validator(resolution_validator)
+        .multiple_occurrences(true)
+        .help("Sets the instrument resolution, the smallest step of the readings, whose standard uncertainty RESOLUTION/√12 is combined in quadrature with the spread of repeated readings, for each series"))
+    .arg(Arg::new("table")
+        .short('T')
+        .long("table")
+        .help("Reads the data files as CSV or TSV tables, whose headers are the default axis labels"))
+    .arg(Arg::new("delimiter")
+        .long("delimiter")
+        .value_name("DELIMITER")
+        .possible_values(["comma", "tab", "semicolon"])
+        .requires("table")
+        .help("Sets the delimiter of the tables instead of guessing it"))
+    .arg(Arg::new("x_column")
+        .long("x-column")
+        .value_name("COLUMN")
+        .requires("table")
+        .help("Sets the column of x values by header or position from 1 (default: 1)"))
+    .arg(Arg::new("ux_column")
+        .long("ux-column")
+        .value_name("COLUMN")
+        .requires("table")
+        .help("Sets the column of x uncertainties by header or position from 1"))
+    .arg(Arg::new("y_column")
+        .long("y-column")
+        .value_name("COLUMN")
+        .requires("table")
+        .help("Sets the column of y values by header or position from 1 (default: 2)"))
+    .arg(Arg::new("uy_column")
+        .long("uy-column")
+        .value_name("COLUMN")
+        .requires("table")
+        .help("Sets the column of y uncertainties by header or position from 1"))
+    .arg(Arg::new("backend")
+        .short('b')
+        .long("backend")
+        .value_name("BACKEND")
+        .possible_values(Backends::all().iter().map(|backend| backend.name()))
+        .default_value(defv::BACKEND)
+        .help("Sets the plotting backend"))
+    .arg(Arg::new("fit_method")
+        .short('f')
+        .long("fit")
+        .value_name("METHOD")
+        .possible_value("ordinary")
+        .possible_value("weighted")
+        .possible_value("york")
+        .default_value(defv::FIT_METHOD)
+        .help("Sets the method to compute the line of best fit"))
+    .arg(Arg::new("gradient_lines")
+        .short('g')
+        .long("gradient-lines")
+        .value_name("METHOD")
+        .possible_value("endpoints")
+        .possible_value("all-points")
+        .default_value(defv::GRADIENT_LINE_METHOD)
+        .help("Sets whether the max/min-gradient lines connect the first and last error boxes or pass through all of them"))
+    .arg(Arg::new("residuals")
+        .short('r')
+        .long("residuals")
+        .help("Shows the residuals from the best fit in a panel under the graph"))
+    .arg(Arg::new("outlier_threshold")
+        .long("outlier-threshold")
+        .value_name("THRESHOLD")
+        .default_value(defv::OUTLIER_THRESHOLD)
+        .validator(outlier_threshold_validator)
+        .help("Excludes points whose studentised residuals from the line fitted to the other points exceed THRESHOLD from the fits, 0 to keep all points"))
+    .arg(Arg::new("degree")
+        .short('d')
+        .long("degree")
+        .value_name("DEGREE")
+        .default_value(defv::POLYNOMIAL_DEGREE)
+        .validator(degree_validator)
+        .help("Fits a polynomial of DEGREE instead of a straight line with gradient lines unless it is 1"))
+    .arg(Arg::new("expression")
+        .short('e')
+        .long("expression")
+        .value_name("EXPRESSION")
+        .conflicts_with("degree")
+        .validator(expression_validator)
+        .help("Fits y = EXPRESSION by nonlinear least squares, e.g. \"a*exp(-x/tau)+c\". Functions: exp ln log sqrt abs sin cos tan asin acos atan sinh cosh tanh; log is base 10"))
+    .arg(Arg::new("initial_guesses")
+        .short('i')
+        .long("initial")
+        .value_name("GUESSES")
+        .requires("expression")
+        .help("Sets the initial guesses of the parameters in EXPRESSION, e.g. \"a=5, tau=2\"; 1 if not given"))
+    .arg(Arg::new("x_transform")
+        .long("x-transform")
+        .value_name("TRANSFORM")
+        .possible_values(["none", "ln", "log", "reciprocal", "reciprocal-square", "square", "sqrt"])
+        .default_value(defv::TRANSFORM)
+        .help("Transforms x values and uncertainties before plotting, e.g. to linearise the data"))
+    .arg(Arg::new("y_transform")
+        .long("y-transform")
+        .value_name("TRANSFORM")
+        .possible_values(["none", "ln", "log", "reciprocal", "reciprocal-square", "square", "sqrt"])
+        .default_value(defv::TRANSFORM)
+        .help("Transforms y values and uncertainties before plotting, e.g. to linearise the data"))
+    .arg(Arg::new("scale")
+        .short('l')
+        .long("scale")
+        .value_name("SCALE")
+        .possible_values(["linear", "log-x", "log-y", "log-log"])
+        .default_value(defv::AXIS_SCALE)
+        .help("Sets which axes are logarithmic"))
+    .arg(Arg::new("out_file")
+        .short('s')
+        .long("save-to")
+        .value_name("PATH")
+        .help("Saves the graph to PATH instead of showing it, as PNG, SVG, PDF or EPS by the extension unless --format is given"))
+    .arg(Arg::new("format")
+        .long("format")
+        .value_name("FORMAT")
+        .possible_values(["png", "svg", "pdf", "eps"])
+        .requires("out_file")
+        .help("Sets the format of the saved graph instead of guessing it from the extension"))
+    .arg(Arg::new("script")
+        .long("script")
+        .value_name("PATH")
+        .help("Writes a gnuplot script reproducing the graph to PATH and its data to PATH.dat, instead of showing the graph unless --save-to is given"))
+    .arg(Arg::new("width")
+        .short('w')
+        .long("width")
+        .value_name("WIDTH")
+        .requires("output")
+        .validator(size_validator)
+        .help("Sets the image width in pixels [default: 960 or that of the PSP file]"))
+    .arg(Arg::new("height")
+        .short('h')
+        .long("height")
+        .value_name("HEIGHT")
+        .requires("output")
+        .validator(size_validator)
+        .help("Sets the image height in pixels [default: 540 or that of the PSP file]"))
+    .arg(Arg::new("x_range")
+        .long("x-range")
+        .value_name("START:END")
+        .allow_hyphen_values(true)
+        .validator(range_validator)
+        .help("Sets the range of the x axis instead of covering the data"))
+    .arg(Arg::new("y_range")
+        .long("y-range")
+        .value_name("START:END")
+        .allow_hyphen_values(true)
+        .validator(range_validator)
+        .help("Sets the range of the y axis instead of covering the data"))
+    .arg(Arg::new("include_origin")
+        .long("include-origin")
+        .help("Extends the linear axes without a set range to the origin and the y intercepts of the fits"))
+    .arg(Arg::new("fit_x_range")
+        .long("fit-x-range")
+        .value_name("START:END")
+        .allow_hyphen_values(true)
+        .validator(range_validator)
+        .help("Draws the fits over this range of x, extrapolating them beyond the data"))
+    .arg(Arg::new("annotate_intercepts")
+        .long("annotate-intercepts")
+        .help("Marks where the lines of best fit cross the axes of a linear chart, with the uncertainties from the gradient lines"))
+    .arg(Arg::new("x_ticks")
+        .long("x-ticks")
+        .value_name("MAJOR[:MINOR]")
+        .validator(ticks_validator)
+        .help("Sets the spacing of the labelled ticks of a linear x axis, and optionally of the unlabelled ones"))
+    .arg(Arg::new("y_ticks")
+        .long("y-ticks")
+        .value_name("MAJOR[:MINOR]")
+        .validator(ticks_validator)
+        .help("Sets the spacing of the labelled ticks of a linear y axis, and optionally of the unlabelled ones"))
+    .arg(Arg::new("notation")
+        .long("notation")
+        .value_name("NOTATION")
+        .possible_values(["auto", "plain", "scientific", "si-prefix"])
+        .help("Sets how the powers of ten of the fitted gradients and intercepts are written [default: auto or that of the PSP file]"))
+    .arg(Arg::new("legend")
+        .long("legend")
+        .value_name("POSITION")
+        .possible_values(["upper-left", "upper-right", "lower-left", "lower-right"])
+        .help("Sets the corner of the chart holding the legend [default: upper-right or that of the PSP file]"))
+    // The size applies to saved graphs and to those drawn by scripts
+    .group(ArgGroup::new("output")
+        .args(&["out_file", "script"])
+        .multiple(true))
+}
+
 fn main() {
-    let matches = App::new("Physics Plotter")
-        .version(crate_version!())
-        .author("Zhang Maiyun <me@myzhangll.xyz>")
-        .about("Plot physics two-variable observation data with best-fit lines, max,min-gradient lines, and error bars.")
-        .arg(Arg::new("DATASET_FILE")
-            .help("Sets the data file to parse")
-            .required(true)
-            .index(1))
-        .arg(Arg::new("psp_file")
-            .help("Indicates that aphysics plotter saved file is used as DATASET_FILE")
-            .short('p')
-            .long("psp-file")
-            .conflicts_with_all(&["title", "x_label", "y_label", "dux", "duy", "backend"]))
-        .arg(Arg::new("title")
-            .short('t')
-            .long("title")
-            .value_name("TITLE")
-            .default_value(defv::TITLE)
-            .help("Sets the title of the plot"))
-        .arg(Arg::new("x_label")
-            .short('x')
-            .long("x-label")
-            .value_name("X_LABEL")
-            .default_value(defv::X_LABEL)
-            .help("Sets the x axis label"))
-        .arg(Arg::new("y_label")
-            .short('y')
-            .long("y-label")
-            .value_name("Y_LABEL")
-            .default_value(defv::Y_LABEL)
-            .help("Sets the y axis label"))
-        .arg(Arg::new("dux")
-            .short('X')
-            .long("default-ux")
-            .value_name("DEFAULT_X_UNCERTAINTY")
-            .default_value(defv::X_UNCERTAINTY)
-            .validator(du_validator)
-            .help("Sets a default value for x uncertainty"))
-        .arg(Arg::new("duy")
-            .short('Y')
-            .long("default-uy")
-            .value_name("DEFAULT_Y_UNCERTAINTY")
-            .default_value(defv::Y_UNCERTAINTY)
-            .validator(du_validator)
-            .help("Sets a default value for y uncertainty"))
-        .arg(Arg::new("backend")
-            .short('b')
-            .long("backend")
-            .value_name("BACKEND")
-            .possible_value("gnuplot")
-            .possible_value("plotters")
-            .default_value(defv::BACKEND)
-            .help("Sets the plotting backend"))
-        .arg(Arg::new("out_file")
-            .short('s')
-            .long("save-to")
-            .value_name("PATH")
-            .requires("width")
-            .requires("height")
-            .required_if_eq("backend", "plotters")
-            .help("Saves the graph to PATH instead of showing it"))
-        .arg(Arg::new("width")
-            .short('w')
-            .long("width")
-            .value_name("WIDTH")
-            .requires("out_file")
-            .validator(size_validator)
-            .help("Sets the image width in pixels (recommended: 960)"))
-        .arg(Arg::new("height")
-            .short('h')
-            .long("height")
-            .value_name("HEIGHT")
-            .requires("out_file")
-            .validator(size_validator)
-            .help("Sets the image height in pixels (recommended: 540)"))
-        .get_matches();
-
-    let (dataset, title, x_label, y_label) = if matches.is_present("psp_file") {
+    let matches = app().get_matches();
+
+    let (series, mut spec, transforms) = if matches.is_present("psp_file") {
         // Parse as PhysPlotterFile
-        let save_file =
-            PhysPlotterFile::from_file(&matches.value_of("DATASET_FILE").unwrap()).unwrap();
+        let file_name = match psp_file_name(&matches) {
+            Ok(file_name) => file_name,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                exit(2);
+            }
+        };
+        let save_file = match PhysPlotterFile::from_file(file_name) {
+            Ok(save_file) => save_file,
+            Err(error) => {
+                eprintln!("Error: {}: {}", file_name, error);
+                exit(2);
+            }
+        };
         (
-            TwoVarDataSet::from_string(
-                &save_file.dataset,
-                save_file.default_x_uncertainty,
-                save_file.default_y_uncertainty,
-            ),
-            save_file.title,
-            save_file.x_label,
-            save_file.y_label,
+            save_file
+                .series
+                .iter()
+                .enumerate()
+                .map(|(index, saved)| {
+                    saved.to_series().map_err(|error| {
+                        let origin = format!("{} (series {})", file_name, index + 1);
+                        describe_parse_error(&origin, &saved.dataset, &error)
+                    })
+                })
+                .collect::<Result<Vec<Series>, String>>(),
+            save_file.spec,
+            save_file.transforms,
         )
     } else {
-        // Parse as plain dataset
+        // Parse as plain datasets or tables
+        let files: Vec<&str> = matches.values_of("DATASET_FILE").unwrap().collect();
+        let mapping = column_mapping(&matches);
+        // Headers of the first table
+        let mut headers = (None, None);
         (
-            TwoVarDataSet::from_file(
-                &matches.value_of("DATASET_FILE").unwrap(),
-                matches.value_of("dux").unwrap().parse().unwrap(),
-                matches.value_of("dux").unwrap().parse().unwrap(),
-            ),
-            matches.value_of("title").unwrap().to_string(),
-            matches.value_of("x_label").unwrap().to_string(),
-            matches.value_of("y_label").unwrap().to_string(),
+            files
+                .iter()
+                .enumerate()
+                .map(|(index, file)| {
+                    // The clap rules will ensure that these are valid
+                    let default_style = SeriesStyle::nth(index);
+                    let dataset = std::fs::read_to_string(file)
+                        .map_err(|error| format!("{}: {}", file, error))?;
+                    let dux = series_value(&matches, "dux", index)
+                        .unwrap_or(defv::X_UNCERTAINTY)
+                        .parse()
+                        .unwrap();
+                    let duy = series_value(&matches, "duy", index)
+                        .unwrap_or(defv::Y_UNCERTAINTY)
+                        .parse()
+                        .unwrap();
+                    let separator = series_value(&matches, "decimal_separator", index)
+                        .unwrap_or(defv::DECIMAL_SEPARATOR)
+                        .parse()
+                        .unwrap();
+                    let repeats = Repeats {
+                        uncertainty: series_value(&matches, "repeat_uncertainty", index)
+                            .unwrap_or(defv::REPEAT_UNCERTAINTY)
+                            .parse()
+                            .unwrap(),
+                        resolution: series_value(&matches, "resolution", index)
+                            .unwrap_or(defv::RESOLUTION)
+                            .parse()
+                            .unwrap(),
+                    };
+                    let data = if matches.is_present("table") {
+                        let delimiter = matches
+                            .value_of("delimiter")
+                            .map(|delimiter| delimiter.parse().unwrap());
+                        let table = Table::parse(&dataset, delimiter, separator);
+                        if index == 0 {
+                            let header = |column| {
+                                table
+                                    .column_index(column)
+                                    .ok()
+                                    .and_then(|index| table.header(index))
+                                    .map(String::from)
+                            };
+                            headers = (header(&mapping.x), header(&mapping.y));
+                        }
+                        table
+                            .to_dataset(&mapping, dux, duy, separator)
+                            .map_err(|error| describe_table_error(file, &dataset, &error))?
+                    } else {
+                        TwoVarDataSet::from_string(&dataset, dux, duy, repeats, separator)
+                            .map_err(|error| describe_parse_error(file, &dataset, &error))?
+                    };
+                    Ok(Series {
+                        name: match series_value(&matches, "name", index) {
+                            Some(name) => name.to_string(),
+                            // Names only tell several series apart
+                            None if files.len() > 1 => std::path::Path::new(file)
+                                .file_stem()
+                                .map(|stem| stem.to_string_lossy().to_string())
+                                .unwrap_or_default(),
+                            None => String::new(),
+                        },
+                        data,
+                        style: SeriesStyle {
+                            colour: series_value(&matches, "colour", index)
+                                .map_or(default_style.colour, |colour| colour.parse().unwrap()),
+                            marker: series_value(&matches, "marker", index)
+                                .map_or(default_style.marker, |marker| marker.parse().unwrap()),
+                        },
+                    })
+                })
+                .collect::<Result<Vec<Series>, String>>(),
+            PlotSpec {
+                title: matches.value_of("title").unwrap().to_string(),
+                // Headers replace the default labels
+                x_label: match (matches.occurrences_of("x_label"), headers.0) {
+                    (0, Some(header)) => header,
+                    _ => matches.value_of("x_label").unwrap().to_string(),
+                },
+                y_label: match (matches.occurrences_of("y_label"), headers.1) {
+                    (0, Some(header)) => header,
+                    _ => matches.value_of("y_label").unwrap().to_string(),
+                },
+                // The clap rules will ensure that these are valid
+                fit: FitOptions {
+                    model: match matches.value_of("expression") {
+                        Some(expression) => FitModel::Expression {
+                            expression: expression.to_string(),
+                            initial_guesses: matches
+                                .value_of("initial_guesses")
+                                .unwrap_or_default()
+                                .to_string(),
+                        },
+                        None => match matches.value_of("degree").unwrap().parse().unwrap() {
+                            1 => FitModel::Line,
+                            degree => FitModel::Polynomial { degree },
+                        },
+                    },
+                    fit_method: FitMethod::from_str(matches.value_of("fit_method").unwrap())
+                        .unwrap(),
+                    gradient_line_method: GradientLineMethod::from_str(
+                        matches.value_of("gradient_lines").unwrap(),
+                    )
+                    .unwrap(),
+                    residuals: matches.is_present("residuals"),
+                    outlier_threshold: matches
+                        .value_of("outlier_threshold")
+                        .unwrap()
+                        .parse()
+                        .unwrap(),
+                },
+                axis_scale: plot::AxisScale::from_str(matches.value_of("scale").unwrap()).unwrap(),
+                ..PlotSpec::default()
+            },
+            Transforms {
+                x_transform: Transform::from_str(matches.value_of("x_transform").unwrap()).unwrap(),
+                y_transform: Transform::from_str(matches.value_of("y_transform").unwrap()).unwrap(),
+            },
         )
     };
-    if let Err(error) = dataset {
-        eprintln!("Error: {}", error);
+    // The clap rules will ensure that these are valid
+    if let Some(width) = matches.value_of("width") {
+        spec.size.width = width.parse().unwrap();
+    }
+    if let Some(height) = matches.value_of("height") {
+        spec.size.height = height.parse().unwrap();
+    }
+    if let Some(range) = matches.value_of("x_range") {
+        spec.x_range = Some(plot::parse_axis_range(range).unwrap());
+    }
+    if let Some(range) = matches.value_of("y_range") {
+        spec.y_range = Some(plot::parse_axis_range(range).unwrap());
+    }
+    if matches.is_present("include_origin") {
+        spec.include_origin = true;
+    }
+    if let Some(range) = matches.value_of("fit_x_range") {
+        spec.fit_x_range = Some(plot::parse_axis_range(range).unwrap());
+    }
+    if matches.is_present("annotate_intercepts") {
+        spec.annotate_intercepts = true;
+    }
+    if let Some(ticks) = matches.value_of("x_ticks") {
+        spec.x_ticks = ticks.parse().unwrap();
+    }
+    if let Some(ticks) = matches.value_of("y_ticks") {
+        spec.y_ticks = ticks.parse().unwrap();
+    }
+    if let Some(notation) = matches.value_of("notation") {
+        spec.notation = Notation::from_str(notation).unwrap();
+    }
+    if let Some(legend) = matches.value_of("legend") {
+        spec.legend = LegendPosition::from_str(legend).unwrap();
+    }
+    let spec = spec.transformed(transforms);
+    let fit = &spec.fit;
+    let mut series = match series {
+        Ok(series) => series,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            exit(2);
+        }
+    };
+    for one in &mut series {
+        one.data = match one.data.transform(transforms) {
+            Ok(dataset) => dataset,
+            Err(error) => {
+                eprintln!("Error: {}", one.label(&error.to_string()));
+                exit(2);
+            }
+        };
+        if fit.outlier_threshold > 0.0 {
+            let count = one
+                .data
+                .exclude_outliers(fit.fit_method, fit.outlier_threshold);
+            if count > 0 {
+                eprintln!(
+                    "Warning: {}",
+                    one.label(&format!("outliers excluded from the fits: {}", count))
+                );
+            }
+        }
+    }
+    // Fitted once for both the messages and the graph
+    let series = FittedSeries::fit_all(series, fit);
+    for one in &series {
+        if let FitResults::Line {
+            best_fit,
+            gradient_lines,
+        } = &one.fits
+        {
+            if let Err(error) = best_fit {
+                eprintln!(
+                    "Error: {}",
+                    one.label(&format!("cannot fit the line: {}", error))
+                );
+                exit(2);
+            }
+            if let (GradientLineMethod::AllPoints, Err(error)) =
+                (fit.gradient_line_method, gradient_lines)
+            {
+                eprintln!("Warning: {}", one.label(&error.to_string()));
+            }
+        }
+        if let (FitModel::Expression { expression, .. }, FitResults::Expression(expression_fit)) =
+            (&fit.model, &one.fits)
+        {
+            match expression_fit {
+                Ok(expression_fit) => {
+                    for parameter in &expression_fit.parameters {
+                        println!(
+                            "{}",
+                            one.label(&format!(
+                                "{} = {}",
+                                parameter.name,
+                                parameter.uncertain_value(spec.notation)
+                            ))
+                        );
+                    }
+                }
+                Err(error) => {
+                    eprintln!(
+                        "Error: {}",
+                        one.label(&format!("cannot fit {}: {}", expression, error))
+                    );
+                    exit(2);
+                }
+            }
+        }
+    }
+    let backend = Backends::from_str(matches.value_of("backend").unwrap()).unwrap();
+    if !backend.can_show() && !matches.is_present("out_file") && !matches.is_present("script") {
+        eprintln!(
+            "Error: the {} backend cannot show the graph, pass --save-to or --script",
+            backend
+        );
         exit(2);
     }
-    match matches.value_of("backend").unwrap() {
-        "plotters" => plot::plotters(
-            &title,
-            &x_label,
-            &y_label,
-            &dataset.unwrap(),
-            // The clap rule will ensure that this argument exists
-            BitMapBackend::new(
-                matches.value_of("out_file").unwrap(),
-                (
-                    matches.value_of("width").unwrap().parse().unwrap(),
-                    matches.value_of("height").unwrap().parse().unwrap(),
-                ),
-            ),
-        )
-        .unwrap(),
-        "gnuplot" => plot::gnuplot(
-            &title,
-            &x_label,
-            &y_label,
-            &dataset.unwrap(),
-            matches.value_of("out_file").map(|path| plot::SaveOptions {
-                path: std::path::Path::new(path),
-                width: matches.value_of("width").unwrap().parse().unwrap(),
-                height: matches.value_of("height").unwrap().parse().unwrap(),
-            }),
-        )
-        .unwrap(),
-        _ => (),
+    let save = matches.value_of("out_file").map(|path| {
+        let path = std::path::Path::new(path);
+        plot::SaveOptions {
+            path,
+            // The clap rule will ensure that the format is valid
+            format: match ImageFormat::for_saving(
+                path,
+                matches
+                    .value_of("format")
+                    .map(|format| ImageFormat::from_str(format).unwrap()),
+                backend,
+            ) {
+                Ok(format) => format,
+                Err(error) => {
+                    eprintln!("Error: {}", error);
+                    exit(2);
+                }
+            },
+        }
+    });
+    if let Some(path) = matches.value_of("script") {
+        let path = std::path::Path::new(path);
+        if let Err(error) = plot::gnuplot_script(&spec, &series, path) {
+            eprintln!("Error: cannot write {}: {}", path.display(), error);
+            exit(1);
+        }
+    }
+    let result = match save {
+        Some(save) => backend.save(&spec, &series, save),
+        // Only the script is written
+        None if matches.is_present("script") => Ok(()),
+        // Renderers which cannot show are rejected above
+        None => backend.show(&spec, &series),
+    };
+    if let Err(error) = result {
+        eprintln!("Error: {}", error);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values of an option for three series
+    fn values_for_three(args: &[&str], name: &str) -> Vec<Option<String>> {
+        let matches = app()
+            .try_get_matches_from(
+                ["phys_plotter", "a.txt", "b.txt", "c.txt"]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap();
+        (0..3)
+            .map(|index| series_value(&matches, name, index).map(String::from))
+            .collect()
+    }
+
+    fn some(values: &[&str]) -> Vec<Option<String>> {
+        values.iter().map(|value| Some(value.to_string())).collect()
+    }
+
+    #[test]
+    fn once_for_every_series() {
+        assert_eq!(values_for_three(&[], "dux"), some(&["0.01"; 3]));
+        assert_eq!(values_for_three(&["-X", "2%"], "dux"), some(&["2%"; 3]));
+        assert_eq!(values_for_three(&["-n", "Run"], "name"), some(&["Run"; 3]));
+        assert_eq!(
+            values_for_three(&["-c", "#ff0000"], "colour"),
+            some(&["#ff0000"; 3])
+        );
+        assert_eq!(values_for_three(&[], "name"), [None, None, None]);
+    }
+
+    #[test]
+    fn in_turn_with_defaults_for_the_rest() {
+        for (args, name) in [
+            (["-Y", "0.1", "-Y", "0.2"], "duy"),
+            (["-D", "comma", "-D", "point"], "decimal_separator"),
+            (["--resolution", "0.1", "--resolution", "0.2"], "resolution"),
+            (["-n", "First", "-n", "Second"], "name"),
+            (["-m", "circle", "-m", "square"], "marker"),
+        ] {
+            let values = values_for_three(&args, name);
+            assert_eq!(values[..2], some(&[args[1], args[3]])[..], "{}", name);
+            assert_eq!(values[2], None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn saved_file_is_plotted_alone() {
+        let matches = app()
+            .try_get_matches_from(["phys_plotter", "-p", "graph.psp"])
+            .unwrap();
+        assert_eq!(psp_file_name(&matches).unwrap(), "graph.psp");
+        let matches = app()
+            .try_get_matches_from(["phys_plotter", "-p", "graph.psp", "data.txt"])
+            .unwrap();
+        assert_eq!(
+            psp_file_name(&matches).unwrap_err(),
+            "a saved file is plotted alone, but 2 DATASET_FILEs are given"
+        );
     }
 }
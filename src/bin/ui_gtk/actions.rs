@@ -17,28 +17,31 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
-use crate::state::UiState;
+use crate::state::{decimal_separator_choices, selected_decimal_separator, UiState};
 use crate::ui::{create_error_popup, disp_not_saved_dialog, disp_save_dialog};
 use crate::{unwrap_option_or_error_return, unwrap_result_or_error_return};
 use clap::crate_version;
 use gio::prelude::*;
 use glib::clone;
+use gtk::cairo;
 use gtk::prelude::*;
 use gtk::License::Gpl30;
 use gtk::{
-    builders::{AboutDialogBuilder, DialogBuilder},
-    Button, DrawingArea, Orientation, RadioButton, ResponseType,
+    builders::{AboutDialogBuilder, DialogBuilder, GridBuilder},
+    Button, ComboBoxText, DrawingArea, Label, Orientation, RadioButton, ResponseType, SpinButton,
+};
+use phys_plotter::data::{
+    ColumnMapping, DecimalSeparator, Delimiter, FitMethod, FitModel, FitResults,
+    GradientLineMethod, Table, TableColumn,
 };
-use phys_plotter::data::TwoVarDataSet;
 use phys_plotter::default_values as defv;
-use phys_plotter::plot::{self, Backends};
+use phys_plotter::plot::{self, Backends, CairoRenderer, FittedSeries, ImageFormat, PlotSpec};
 use phys_plotter::save_format::PhysPlotterFile;
-use plotters::prelude::*;
-use plotters_cairo::CairoBackend;
 use std::cell::RefCell;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
 use std::rc::Rc;
 
 fn about_action(application: &gtk::Application, window: &gtk::ApplicationWindow) {
@@ -77,32 +80,129 @@ fn change_backend(
             .attached_to(&window)
             .transient_for(&window)
             .build();
+        // One button for each renderer, answering with its index
+        let backends = Backends::all();
+        let current = state.borrow().backend;
+        let mut group: Option<RadioButton> = None;
+        for (index, backend) in backends.iter().enumerate() {
+            let radiobutton = RadioButton::new();
+            radiobutton.set_label(backend.display_name());
+            radiobutton.join_group(group.as_ref());
+            radiobutton.set_active(*backend == current);
+            dialog.add_action_widget(&radiobutton, ResponseType::Other(index as u16));
+            group.get_or_insert(radiobutton);
+        }
+        dialog.connect_response(clone!(@strong state => move |_, resp_type| {
+            if let ResponseType::Other(index) = resp_type {
+                if let Some(&backend) = backends.get(usize::from(index)) {
+                    let mut borrowed = state.borrow_mut();
+                    borrowed.saved = false;
+                    borrowed.backend = backend;
+                }
+            }
+        }));
+        //radiobuttons.show_all();
+        dialog.show_all();
+        dialog.run();
+        unsafe { dialog.destroy(); }
+    }));
+    application.add_action(&dialog);
+}
+
+/// Change the fitted model, the state is possibly altered
+fn change_fit_model(
+    application: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    state: &Rc<RefCell<UiState>>,
+) {
+    // Action to change the degree of the fitted polynomial
+    let dialog = gio::SimpleAction::new("change_fit_model", None);
+    dialog.connect_activate(clone!(@weak window, @strong state => move |_, _| {
+        let dialog = DialogBuilder::new()
+            .title("Fit Model")
+            .attached_to(&window)
+            .transient_for(&window)
+            .build();
+        let label = Label::new(Some("Polynomial degree (1 for a straight line with gradient lines),\nused when no model expression is given"));
+        let degree_input = SpinButton::with_range(0.0, 10.0, 1.0);
+        degree_input.set_value(match state.borrow().spec.fit.model {
+            FitModel::Line | FitModel::Expression { .. } => 1.0,
+            FitModel::Polynomial { degree } => degree as f64,
+        });
+        dialog.content_area().add(&label);
+        dialog.content_area().add(&degree_input);
+        dialog.add_buttons(&[("OK", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+        dialog.connect_response(clone!(@strong state, @weak degree_input => move |_,resp_type| {
+            if resp_type == ResponseType::Ok {
+                let mut borrowed = state.borrow_mut();
+                borrowed.saved = false;
+                borrowed.spec.fit.model = match degree_input.value_as_int() {
+                    1 => FitModel::Line,
+                    degree => FitModel::Polynomial { degree: degree as usize },
+                };
+            }
+        }));
+        dialog.show_all();
+        dialog.run();
+        unsafe { dialog.destroy(); }
+    }));
+    application.add_action(&dialog);
+}
+
+/// Change the best-fit method, the state is possibly altered
+fn change_fit_method(
+    application: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    state: &Rc<RefCell<UiState>>,
+) {
+    // Action to change the selected fit method
+    let dialog = gio::SimpleAction::new("change_fit_method", None);
+    dialog.connect_activate(clone!(@weak window, @strong state => move |_, _| {
+        let dialog = DialogBuilder::new()
+            .title("Best-fit Method")
+            .attached_to(&window)
+            .transient_for(&window)
+            .build();
         let radiobutton_1 = RadioButton::new();
-        radiobutton_1.set_label("plotters");
+        radiobutton_1.set_label("ordinary");
         let radiobutton_2 = RadioButton::new();
-        radiobutton_2.set_label("gnuplot");
-        match state.borrow().backend {
-            Backends::Plotters => {radiobutton_2.join_group(Some(&radiobutton_1));},
-            Backends::Gnuplot => {
-            radiobutton_1.join_group(Some(&radiobutton_2));
-            }
+        radiobutton_2.set_label("weighted");
+        let radiobutton_3 = RadioButton::new();
+        radiobutton_3.set_label("york");
+        // The button that the others join stays active
+        match state.borrow().spec.fit.fit_method {
+            FitMethod::Ordinary => {
+                radiobutton_2.join_group(Some(&radiobutton_1));
+                radiobutton_3.join_group(Some(&radiobutton_1));
+            },
+            FitMethod::Weighted => {
+                radiobutton_1.join_group(Some(&radiobutton_2));
+                radiobutton_3.join_group(Some(&radiobutton_2));
+            },
+            FitMethod::York => {
+                radiobutton_1.join_group(Some(&radiobutton_3));
+                radiobutton_2.join_group(Some(&radiobutton_3));
+            },
         }
         dialog.add_action_widget(&radiobutton_1, ResponseType::Other(1));
         dialog.add_action_widget(&radiobutton_2, ResponseType::Other(2));
+        dialog.add_action_widget(&radiobutton_3, ResponseType::Other(3));
         dialog.connect_response(clone!(@strong state => move |_,resp_type| {
             let mut borrowed = state.borrow_mut();
             borrowed.saved = false;
             match resp_type {
                 ResponseType::Other(1) => {
-                    borrowed.backend = Backends::Plotters
+                    borrowed.spec.fit.fit_method = FitMethod::Ordinary
                 },
                 ResponseType::Other(2) => {
-                    borrowed.backend =Backends::Gnuplot
+                    borrowed.spec.fit.fit_method = FitMethod::Weighted
+                },
+                ResponseType::Other(3) => {
+                    borrowed.spec.fit.fit_method = FitMethod::York
                 },
                 _ => ()
             }
         }));
-        //radiobuttons.show_all();
         dialog.show_all();
         dialog.run();
         unsafe { dialog.destroy(); }
@@ -110,83 +210,109 @@ fn change_backend(
     application.add_action(&dialog);
 }
 
-macro_rules! parse_state_float_or_return {
-    ($var: expr) => {
-        $var.text().parse()?
-    };
-}
-
-/// Error type while plotting
-enum PlotError {
-    EmptyData,
-}
-
-macro_rules! impl_fmt_plot_error {
-    ($item: ident) => {
-        impl std::fmt::$item for PlotError {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                match self {
-                    PlotError::EmptyData => write!(f, "Dataset cannot be empty"),
-                }
-            }
+/// Change the gradient line method, the state is possibly altered
+fn change_gradient_lines(
+    application: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    state: &Rc<RefCell<UiState>>,
+) {
+    // Action to change the selected gradient line method
+    let dialog = gio::SimpleAction::new("change_gradient_lines", None);
+    dialog.connect_activate(clone!(@weak window, @strong state => move |_, _| {
+        let dialog = DialogBuilder::new()
+            .title("Gradient Lines")
+            .attached_to(&window)
+            .transient_for(&window)
+            .build();
+        let radiobutton_1 = RadioButton::new();
+        radiobutton_1.set_label("through end points");
+        let radiobutton_2 = RadioButton::new();
+        radiobutton_2.set_label("through all points");
+        match state.borrow().spec.fit.gradient_line_method {
+            GradientLineMethod::Endpoints => {radiobutton_2.join_group(Some(&radiobutton_1));},
+            GradientLineMethod::AllPoints => {radiobutton_1.join_group(Some(&radiobutton_2));},
         }
-    };
+        dialog.add_action_widget(&radiobutton_1, ResponseType::Other(1));
+        dialog.add_action_widget(&radiobutton_2, ResponseType::Other(2));
+        dialog.connect_response(clone!(@strong state => move |_,resp_type| {
+            let mut borrowed = state.borrow_mut();
+            borrowed.saved = false;
+            match resp_type {
+                ResponseType::Other(1) => {
+                    borrowed.spec.fit.gradient_line_method = GradientLineMethod::Endpoints
+                },
+                ResponseType::Other(2) => {
+                    borrowed.spec.fit.gradient_line_method = GradientLineMethod::AllPoints
+                },
+                _ => ()
+            }
+        }));
+        dialog.show_all();
+        dialog.run();
+        unsafe { dialog.destroy(); }
+    }));
+    application.add_action(&dialog);
 }
 
-impl_fmt_plot_error!(Debug);
-impl_fmt_plot_error!(Display);
-
-impl std::error::Error for PlotError {}
-
-/// Image formats that can be exported
-#[derive(Debug, Copy, Clone)]
-enum ImageFormat {
-    Svg,
-    Png,
+/// Create a cairo surface writing PDF or EPS to the file
+fn vector_surface(
+    format: ImageFormat,
+    filename: &Path,
+    (width, height): (u32, u32),
+) -> Result<cairo::Surface, cairo::Error> {
+    let (width, height) = (f64::from(width), f64::from(height));
+    Ok(if format == ImageFormat::Eps {
+        let surface = cairo::PsSurface::new(width, height, filename)?;
+        surface.set_eps(true);
+        (*surface).clone()
+    } else {
+        (*cairo::PdfSurface::new(width, height, filename)?).clone()
+    })
 }
 
 fn save_image(
     window: &gtk::ApplicationWindow,
+    backend: &'static dyn CairoRenderer,
     format: ImageFormat,
-    title: &str,
-    x_label: &str,
-    y_label: &str,
-    dataset: &TwoVarDataSet,
+    spec: &PlotSpec,
+    series: &[FittedSeries],
 ) {
     // These variables must be owned in order for clone to succeed
-    let title = title.to_string();
-    let x_label = x_label.to_string();
-    let y_label = y_label.to_string();
+    let spec = spec.clone();
+    let series = series.to_vec();
     disp_save_dialog(
         window,
         "Save Image to",
-        clone!(@weak window, @strong title, @strong x_label, @strong y_label, @strong dataset => move |filename| {
-            match format {
-                ImageFormat::Svg => unwrap_result_or_error_return!(
-                    plot::plotters(
-                        &title,
-                        &x_label,
-                        &y_label,
-                        &dataset,
-                        SVGBackend::new(filename, (960, 540))
-                    ),
-                    &window,
-                    "Failed to open plot",
-                    {}
-                ),
-                ImageFormat::Png => unwrap_result_or_error_return!(
-                    plot::plotters(
-                        &title,
-                        &x_label,
-                        &y_label,
-                        &dataset,
-                        BitMapBackend::new(filename, (960, 540))
-                    ),
+        clone!(@weak window, @strong spec, @strong series => move |filename| {
+            if backend.can_save(format) {
+                unwrap_result_or_error_return!(
+                    backend.save(&spec, &series, plot::SaveOptions { path: filename, format }),
                     &window,
                     "Failed to open plot",
                     {}
-                ),
-            };
+                );
+                return;
+            }
+            // Otherwise draw on a cairo surface writing PDF or EPS
+            let surface = unwrap_result_or_error_return!(
+                vector_surface(format, filename, spec.size.into()),
+                &window,
+                "Failed to create file",
+                {}
+            );
+            let context = unwrap_result_or_error_return!(
+                cairo::Context::new(&surface),
+                &window,
+                "Failed to create file",
+                {}
+            );
+            unwrap_result_or_error_return!(
+                backend.draw_cairo(&spec, &series, &context),
+                &window,
+                "Failed to open plot",
+                {}
+            );
+            surface.finish();
         }),
     );
 }
@@ -196,84 +322,83 @@ fn do_generate_plot(
     window: &gtk::ApplicationWindow,
     state: &Rc<RefCell<UiState>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Get the range of the dataset text
     let state_local = state.borrow();
-    let range = state_local.dataset.bounds();
-    let dataset_text = state_local
-        .dataset
-        .text(&range.0, &range.1, true)
-        .unwrap_or_else(|| glib::GString::from(""));
-    // Construct dataset from the input
-    let dataset = TwoVarDataSet::from_string(
-        dataset_text.as_str(),
-        parse_state_float_or_return!(state_local.default_x_uncertainty),
-        parse_state_float_or_return!(state_local.default_y_uncertainty),
-    )?;
-    // Empty values can crash some backends
-    if dataset.is_empty() {
-        return Err(Box::new(PlotError::EmptyData));
-    }
+    // Construct the series from the input
+    let series = state_local.parse_series()?;
     // Extract information here first
-    let title = state_local.title.text();
-    let x_label = state_local.x_label.text();
-    let y_label = state_local.y_label.text();
-    // Call plotting backend
-    match state_local.backend {
-        Backends::Gnuplot => plot::gnuplot(&title, &x_label, &y_label, &dataset, None)?,
-        Backends::Plotters => {
-            // Create a new window for drawing
-            let plot_window = gtk::Window::new(gtk::WindowType::Toplevel);
-            application.add_window(&plot_window);
-            plot_window.set_title("Plotters Canva");
-            plot_window.set_default_size(960, 584);
-
-            let container = gtk::Box::new(Orientation::Vertical, 5);
-            // Create cairo drawing area
-            let drawing_area = DrawingArea::new();
-            // The drawing area has to expand or button will take all the space
-            drawing_area.set_vexpand(true);
-            container.add(&drawing_area);
-            drawing_area.connect_draw(clone!(@weak window, @weak plot_window, @strong title, @strong x_label, @strong y_label, @strong dataset => @default-return Inhibit(false), move |_, ctx| {
-                let backend = CairoBackend::new(ctx, (960, 540)).unwrap();
-                unwrap_result_or_error_return!(
-                    plot::plotters(
-                        &title,
-                        &x_label,
-                        &y_label,
-                        &dataset,
-                        backend
-                    ),
-                    &window,
-                    "Failed to open plot",
-                    {
-                        plot_window.close();
-                        Inhibit(false)
-                    }
-                );
-                Inhibit(false)
-            }));
-            // Save options
-            let button_area = gtk::Box::new(Orientation::Horizontal, 5);
-            let button_png = Button::with_label("Save to PNG");
-            button_png.connect_clicked(clone!(@weak window, @strong title, @strong x_label, @strong y_label, @strong dataset => move |_| {
-                save_image(&window, ImageFormat::Png, &title, &x_label, &y_label, &dataset);
-            }));
-            let button_svg = Button::with_label("Save to SVG");
-            button_svg.connect_clicked(clone!(@weak window, @strong title, @strong x_label, @strong y_label, @strong dataset => move |_| {
-                save_image(&window, ImageFormat::Svg, &title, &x_label, &y_label, &dataset);
-            }));
-            let button_close = Button::with_label("Close");
-            button_close.connect_clicked(clone!(@weak plot_window => move |_| {
-                plot_window.close();
-            }));
-            button_area.add(&button_png);
-            button_area.add(&button_svg);
-            button_area.add(&button_close);
-            container.add(&button_area);
-            plot_window.add(&container);
-            plot_window.show_all();
+    let spec = state_local
+        .plot_spec()?
+        .transformed(state_local.transforms());
+    let series = FittedSeries::fit_all(series, &spec.fit);
+    // Report problems with the fits here, the backends silently skip them
+    for one in &series {
+        match &one.fits {
+            FitResults::Line { best_fit, .. } => {
+                best_fit.map_err(|error| one.label(&error.to_string()))?;
+            }
+            FitResults::Expression(expression_fit) => {
+                expression_fit
+                    .as_ref()
+                    .map_err(|error| one.label(&error.to_string()))?;
+            }
+            FitResults::Polynomial(_) => (),
+        }
+    }
+    // Call plotting backend, drawing in a window of ours if it can
+    let backend = match state_local.backend.cairo() {
+        Some(backend) => backend,
+        None => {
+            state_local.backend.show(&spec, &series)?;
+            return Ok(());
         }
     };
+    // Create a new window for drawing
+    let plot_window = gtk::Window::new(gtk::WindowType::Toplevel);
+    application.add_window(&plot_window);
+    plot_window.set_title(backend.display_name());
+    // Leave room for the buttons under the graph
+    plot_window.set_default_size(spec.size.width as i32, spec.size.height as i32 + 44);
+
+    let container = gtk::Box::new(Orientation::Vertical, 5);
+    // Create cairo drawing area
+    let drawing_area = DrawingArea::new();
+    // The drawing area has to expand or button will take all the space
+    drawing_area.set_vexpand(true);
+    container.add(&drawing_area);
+    drawing_area.connect_draw(clone!(@weak window, @weak plot_window, @strong spec, @strong series => @default-return Inhibit(false), move |_, ctx| {
+        unwrap_result_or_error_return!(
+            backend.draw_cairo(&spec, &series, ctx),
+            &window,
+            "Failed to open plot",
+            {
+                plot_window.close();
+                Inhibit(false)
+            }
+        );
+        Inhibit(false)
+    }));
+    // Save options
+    let button_area = gtk::Box::new(Orientation::Horizontal, 5);
+    // PDF and EPS can always be drawn on cairo surfaces
+    for format in ImageFormat::ALL.into_iter().filter(|&format| {
+        backend.can_save(format) || matches!(format, ImageFormat::Pdf | ImageFormat::Eps)
+    }) {
+        let button = Button::with_label(&format!("Save to {}", format.extension().to_uppercase()));
+        button.connect_clicked(
+            clone!(@weak window, @strong spec, @strong series => move |_| {
+                save_image(&window, backend, format, &spec, &series);
+            }),
+        );
+        button_area.add(&button);
+    }
+    let button_close = Button::with_label("Close");
+    button_close.connect_clicked(clone!(@weak plot_window => move |_| {
+        plot_window.close();
+    }));
+    button_area.add(&button_close);
+    container.add(&button_area);
+    plot_window.add(&container);
+    plot_window.show_all();
     Ok(())
 }
 
@@ -297,6 +422,49 @@ fn generate_plot(
     application.add_action(&dialog);
 }
 
+fn do_export_script(
+    window: &gtk::ApplicationWindow,
+    state: &Rc<RefCell<UiState>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state_local = state.borrow();
+    let series = state_local.parse_series()?;
+    let spec = state_local
+        .plot_spec()?
+        .transformed(state_local.transforms());
+    let series = FittedSeries::fit_all(series, &spec.fit);
+    disp_save_dialog(
+        window,
+        "Export Gnuplot Script to",
+        clone!(@weak window, @strong spec, @strong series => move |filename| {
+            unwrap_result_or_error_return!(
+                plot::gnuplot_script(&spec, &series, filename),
+                &window,
+                "Failed to export script",
+                {}
+            );
+        }),
+    );
+    Ok(())
+}
+
+/// Export a gnuplot script and its data, reading the state
+fn export_script(
+    application: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    state: &Rc<RefCell<UiState>>,
+) {
+    let dialog = gio::SimpleAction::new("export_script", None);
+    dialog.connect_activate(clone!(@weak window, @strong state => move |_, _| {
+        unwrap_result_or_error_return!(
+            do_export_script(&window, &state),
+            &window,
+            "Failed to export script",
+            {}
+        );
+    }));
+    application.add_action(&dialog);
+}
+
 /// Immediately save two files without any check
 fn save_imm(window: &gtk::ApplicationWindow, state: &Rc<RefCell<UiState>>) {
     unwrap_result_or_error_return!(state.borrow().save(), window, "Cannot save file", {});
@@ -424,6 +592,199 @@ fn open_file(
     application.add_action(&open_file);
 }
 
+/// Fill choices with the columns of table, with "None" first if optional
+fn fill_column_choices(choices: &ComboBoxText, table: &Table, optional: bool, active: &str) {
+    choices.remove_all();
+    if optional {
+        choices.append(Some("none"), "None");
+    }
+    for index in 0..table.column_count() {
+        choices.append(Some(&index.to_string()), &table.column_title(index));
+    }
+    if !choices.set_active_id(Some(active)) {
+        choices.set_active(Some(0));
+    }
+}
+
+/// Fill the choices of x, x uncertainty, y and y uncertainty
+fn fill_mapping_choices(choices: &[ComboBoxText; 4], table: &Table) {
+    fill_column_choices(&choices[0], table, false, "0");
+    fill_column_choices(&choices[1], table, true, "none");
+    fill_column_choices(&choices[2], table, false, "1");
+    fill_column_choices(&choices[3], table, true, "none");
+}
+
+/// Get the column chosen in choices, None for "None"
+fn selected_column(choices: &ComboBoxText) -> Option<usize> {
+    choices.active_id().and_then(|id| id.parse().ok())
+}
+
+/// Parse text with the delimiter and the decimal separator chosen
+fn parse_table(
+    text: &str,
+    delimiter_choices: &ComboBoxText,
+    separator_choices: &ComboBoxText,
+) -> Table {
+    let delimiter = delimiter_choices.active_id().and_then(|id| id.parse().ok());
+    Table::parse(
+        text,
+        delimiter,
+        selected_decimal_separator(separator_choices),
+    )
+}
+
+/// Ask for the columns of a table and put them into the current series
+fn show_import_dialog(window: &gtk::ApplicationWindow, state: &Rc<RefCell<UiState>>, text: String) {
+    let dialog = DialogBuilder::new()
+        .title("Import Table")
+        .attached_to(window)
+        .transient_for(window)
+        .build();
+    let delimiter_choices = ComboBoxText::new();
+    for delimiter in Delimiter::ALL {
+        delimiter_choices.append(Some(&delimiter.to_string()), &delimiter.to_string());
+    }
+    delimiter_choices.set_active_id(Some(&Delimiter::detect(&text).to_string()));
+    let separator_choices = decimal_separator_choices();
+    separator_choices.set_active_id(Some(defv::DECIMAL_SEPARATOR));
+    // x, x uncertainty, y, y uncertainty
+    let column_choices = [
+        ComboBoxText::new(),
+        ComboBoxText::new(),
+        ComboBoxText::new(),
+        ComboBoxText::new(),
+    ];
+    fill_mapping_choices(
+        &column_choices,
+        &parse_table(&text, &delimiter_choices, &separator_choices),
+    );
+    // Cells are split differently after these change
+    for choices in [&delimiter_choices, &separator_choices] {
+        choices.connect_changed(clone!(
+            @strong text,
+            @strong column_choices,
+            @weak delimiter_choices,
+            @weak separator_choices => move |_| {
+                let table = parse_table(&text, &delimiter_choices, &separator_choices);
+                fill_mapping_choices(&column_choices, &table);
+            }
+        ));
+    }
+    let grid = GridBuilder::new()
+        .row_spacing(5)
+        .column_spacing(5)
+        .margin(5)
+        .build();
+    let labels = [
+        "Delimiter",
+        "Decimal separator",
+        "x",
+        "x uncertainty",
+        "y",
+        "y uncertainty",
+    ];
+    let choices = [&delimiter_choices, &separator_choices]
+        .into_iter()
+        .chain(column_choices.iter());
+    for (row, (label, widget)) in labels.into_iter().zip(choices).enumerate() {
+        grid.attach(&Label::new(Some(label)), 0, row as i32, 1, 1);
+        grid.attach(widget, 1, row as i32, 1, 1);
+    }
+    dialog.content_area().add(&grid);
+    dialog.add_buttons(&[
+        ("Import", ResponseType::Ok),
+        ("Cancel", ResponseType::Cancel),
+    ]);
+    dialog.connect_response(clone!(@weak window, @strong state => move |_, resp_type| {
+        if resp_type != ResponseType::Ok {
+            return;
+        }
+        let table = parse_table(&text, &delimiter_choices, &separator_choices);
+        let x = selected_column(&column_choices[0]).unwrap_or(0);
+        let x_uncertainty = selected_column(&column_choices[1]);
+        let y = selected_column(&column_choices[2]).unwrap_or(1);
+        let y_uncertainty = selected_column(&column_choices[3]);
+        let mapping = ColumnMapping {
+            x: TableColumn::Index(x),
+            x_uncertainty: x_uncertainty.map(TableColumn::Index),
+            y: TableColumn::Index(y),
+            y_uncertainty: y_uncertainty.map(TableColumn::Index),
+        };
+        let separator = selected_decimal_separator(&separator_choices);
+        let dataset = unwrap_result_or_error_return!(
+            table.to_dataset_text(&mapping, separator),
+            &window,
+            "Couldn't import table",
+            {}
+        );
+        {
+            let borrowed = state.borrow();
+            // The text is written with decimal points
+            borrowed
+                .decimal_separator
+                .set_active_id(Some(&DecimalSeparator::Point.to_string()));
+            borrowed.dataset.set_text(&dataset);
+            // Headers are the natural axis labels
+            if let Some(header) = table.header(x) {
+                borrowed.x_label.set_text(header);
+            }
+            if let Some(header) = table.header(y) {
+                borrowed.y_label.set_text(header);
+            }
+        }
+        state.borrow_mut().saved = false;
+    }));
+    dialog.show_all();
+    dialog.run();
+    unsafe {
+        dialog.destroy();
+    }
+}
+
+/// Import a CSV or TSV table into the current series, possibly altering state
+fn import_table(
+    application: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    state: &Rc<RefCell<UiState>>,
+) {
+    let import_table = gio::SimpleAction::new("import_table", None);
+    import_table.connect_activate(clone!(@weak window, @strong state => move |_, _| {
+        let file_chooser = gtk::FileChooserDialog::new(
+            Some("Import Table"),
+            Some(&window),
+            gtk::FileChooserAction::Open,
+        );
+        file_chooser.add_buttons(&[
+            ("Open", gtk::ResponseType::Ok),
+            ("Cancel", gtk::ResponseType::Cancel),
+        ]);
+        file_chooser.connect_response(
+            clone!(@weak window, @strong state => move |file_chooser, response| {
+                if response != gtk::ResponseType::Ok {
+                    file_chooser.close();
+                    return;
+                }
+                let filename = unwrap_option_or_error_return!(
+                    file_chooser.filename(),
+                    &window,
+                    "Couldn't get filename",
+                    {file_chooser.close()}
+                );
+                file_chooser.close();
+                let text = unwrap_result_or_error_return!(
+                    std::fs::read_to_string(&filename),
+                    &window,
+                    "Couldn't open file",
+                    {}
+                );
+                show_import_dialog(&window, &state, text);
+            }),
+        );
+        file_chooser.show_all();
+    }));
+    application.add_action(&import_table);
+}
+
 /// Create a new plot, possibly altering state
 fn new_plot(
     application: &gtk::Application,
@@ -459,14 +820,23 @@ pub fn register_actions(
     application.add_action(&quit);
     about_action(application, window);
     change_backend(application, window, state);
+    change_fit_model(application, window, state);
+    change_fit_method(application, window, state);
+    change_gradient_lines(application, window, state);
     generate_plot(application, window, state);
+    export_script(application, window, state);
     save(application, window, state);
     save_as(application, window, state);
     open_file(application, window, state);
+    import_table(application, window, state);
     new_plot(application, window, state);
     application.set_accels_for_action("app.quit", &["<Primary>Q"]);
     application.set_accels_for_action("app.change_backend", &["<Primary>B"]);
+    application.set_accels_for_action("app.change_fit_model", &["<Primary>M"]);
+    application.set_accels_for_action("app.change_fit_method", &["<Primary>F"]);
+    application.set_accels_for_action("app.change_gradient_lines", &["<Primary>L"]);
     application.set_accels_for_action("app.plot", &["<Primary>G"]);
+    application.set_accels_for_action("app.export_script", &["<Primary>E"]);
     application.set_accels_for_action("app.save", &["<Primary>S"]);
     application.set_accels_for_action("app.save_as", &["<Primary><Shift>S"]);
     application.set_accels_for_action("app.open", &["<Primary>O"]);
@@ -32,11 +32,13 @@ pub fn build_menu(application: &gtk::Application) {
     // First menu: files
     // - new
     // - open
+    // - import table
     // - save
     // - save as
     let files_menu = gio::Menu::new();
     files_menu.append(Some("_New"), Some("app.new"));
     files_menu.append(Some("_Open"), Some("app.open"));
+    files_menu.append(Some("_Import Table"), Some("app.import_table"));
     files_menu.append(Some("_Save"), Some("app.save"));
     files_menu.append(Some("Save _As"), Some("app.save_as"));
     menu_bar.append_submenu(Some("_Files"), &files_menu);
@@ -44,6 +46,12 @@ pub fn build_menu(application: &gtk::Application) {
     // Second menu: plot
     let plot_menu = gio::Menu::new();
     plot_menu.append(Some("Change _Backend"), Some("app.change_backend"));
+    plot_menu.append(Some("Change Fit _Model"), Some("app.change_fit_model"));
+    plot_menu.append(Some("Change _Fit Method"), Some("app.change_fit_method"));
+    plot_menu.append(
+        Some("Change Gradient _Lines"),
+        Some("app.change_gradient_lines"),
+    );
     plot_menu.append(Some("_Generate Plot"), Some("app.plot"));
     menu_bar.append_submenu(Some("_Plot"), &plot_menu);
 
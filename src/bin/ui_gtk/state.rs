@@ -18,11 +18,22 @@
 //
 
 use clap::crate_version;
+use gtk::gdk::RGBA;
 use gtk::prelude::*;
-use gtk::{builders::TextBufferBuilder, EntryBuffer, TextBuffer};
+use gtk::{
+    builders::TextBufferBuilder, CheckButton, ColorButton, ComboBoxText, EntryBuffer, SpinButton,
+    TextBuffer,
+};
+use phys_plotter::data::{
+    DecimalSeparator, FitModel, FitOptions, Notation, RepeatUncertainty, Repeats, Transform,
+    Transforms, UncertaintyFromStrError,
+};
 use phys_plotter::default_values as defv;
-use phys_plotter::plot::{Backends, BackendsFromStrError};
-use phys_plotter::save_format::PhysPlotterFile;
+use phys_plotter::plot::{
+    self, AxisScale, Backends, BackendsFromStrError, Colour, Marker, PlotSpec, Series, SeriesStyle,
+    TickSpacing,
+};
+use phys_plotter::save_format::{PhysPlotterFile, SavedSeries};
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 
@@ -32,14 +43,125 @@ pub struct UiState {
     pub saved: bool,
     pub file_path: String,
     pub backend: Backends,
+    /// Options of the graph without views. The title, the labels and the axes
+    /// are kept in the views below, and so are the expression fitted,
+    /// whether to show the residuals and the outlier threshold, so
+    /// `spec.fit.model` is never an expression. Use `plot_spec` for the
+    /// whole specification
+    pub spec: PlotSpec,
+    /// Fit this expression instead of `fit.model` if not empty
+    pub model_expression: EntryBuffer,
+    pub initial_guesses: EntryBuffer,
+    pub residuals: CheckButton,
+    pub outlier_threshold: SpinButton,
+    /// Choices of `Transform`, identified by their names
+    pub x_transform: ComboBoxText,
+    pub y_transform: ComboBoxText,
+    /// Choices of `AxisScale`, identified by their names
+    pub axis_scale: ComboBoxText,
+    /// Ranges such as 0:10, covering the data if empty
+    pub x_range: EntryBuffer,
+    pub y_range: EntryBuffer,
+    pub include_origin: CheckButton,
+    /// Range such as 0:10 over which the fits are drawn, that of the data if empty
+    pub fit_x_range: EntryBuffer,
+    pub annotate_intercepts: CheckButton,
+    /// Choices of `Notation`, identified by their names
+    pub notation: ComboBoxText,
+    /// Tick spacings such as 5 or 5:1, automatic if empty
+    pub x_ticks: EntryBuffer,
+    pub y_ticks: EntryBuffer,
     pub title: EntryBuffer,
-    pub dataset: TextBuffer,
     pub x_label: EntryBuffer,
     pub y_label: EntryBuffer,
+    /// All series, where the one being edited is outdated until `store_series`
+    pub series: Vec<SeriesInput>,
+    pub current_series: usize,
+    /// Choices of the series to edit, identified by their indices
+    pub series_choices: ComboBoxText,
+    /// Views of the series being edited
+    pub series_name: EntryBuffer,
+    pub series_colour: ColorButton,
+    /// Choices of `Marker`, identified by their names
+    pub series_marker: ComboBoxText,
+    /// Choices of `DecimalSeparator`, identified by their names
+    pub decimal_separator: ComboBoxText,
+    /// Choices of `RepeatUncertainty`, identified by their names
+    pub repeat_uncertainty: ComboBoxText,
+    pub resolution: SpinButton,
+    pub dataset: TextBuffer,
     pub default_x_uncertainty: EntryBuffer,
     pub default_y_uncertainty: EntryBuffer,
 }
 
+/// A series as being edited
+#[derive(Debug, Clone)]
+pub struct SeriesInput {
+    pub name: String,
+    pub dataset: String,
+    pub default_x_uncertainty: String,
+    pub default_y_uncertainty: String,
+    pub decimal_separator: DecimalSeparator,
+    pub repeats: Repeats,
+    pub style: SeriesStyle,
+}
+
+impl SeriesInput {
+    /// Create the nth series with default values
+    pub fn nth(index: usize) -> Self {
+        Self {
+            name: String::default(),
+            dataset: String::default(),
+            default_x_uncertainty: String::from(defv::X_UNCERTAINTY),
+            default_y_uncertainty: String::from(defv::Y_UNCERTAINTY),
+            decimal_separator: DecimalSeparator::from_str(defv::DECIMAL_SEPARATOR).unwrap(),
+            repeats: Repeats {
+                uncertainty: RepeatUncertainty::from_str(defv::REPEAT_UNCERTAINTY).unwrap(),
+                resolution: defv::RESOLUTION.parse().unwrap(),
+            },
+            style: SeriesStyle::nth(index),
+        }
+    }
+
+    /// Name shown in the series selection
+    pub fn title(&self, index: usize) -> String {
+        if self.name.is_empty() {
+            format!("Series {}", index + 1)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+impl TryInto<SavedSeries> for SeriesInput {
+    type Error = UncertaintyFromStrError;
+    fn try_into(self) -> Result<SavedSeries, Self::Error> {
+        Ok(SavedSeries {
+            name: self.name,
+            default_x_uncertainty: self.default_x_uncertainty.parse()?,
+            default_y_uncertainty: self.default_y_uncertainty.parse()?,
+            dataset: self.dataset,
+            decimal_separator: self.decimal_separator,
+            repeats: self.repeats,
+            style: self.style,
+        })
+    }
+}
+
+impl From<SavedSeries> for SeriesInput {
+    fn from(that: SavedSeries) -> Self {
+        Self {
+            name: that.name,
+            dataset: that.dataset,
+            default_x_uncertainty: that.default_x_uncertainty.to_string(),
+            default_y_uncertainty: that.default_y_uncertainty.to_string(),
+            decimal_separator: that.decimal_separator,
+            repeats: that.repeats,
+            style: that.style,
+        }
+    }
+}
+
 impl UiState {
     /// Create a new state
     pub fn new() -> Self {
@@ -47,13 +169,205 @@ impl UiState {
             saved: true,
             file_path: Default::default(),
             title: EntryBuffer::new(Some(defv::TITLE)),
-            dataset: TextBufferBuilder::new().build(),
             backend: Backends::from_str(defv::BACKEND).unwrap(),
+            spec: PlotSpec::default(),
+            model_expression: EntryBuffer::new(None),
+            initial_guesses: EntryBuffer::new(None),
+            residuals: residuals_choice(false),
+            outlier_threshold: outlier_threshold_input(defv::OUTLIER_THRESHOLD.parse().unwrap()),
+            x_transform: transform_choices("x", Transform::from_str(defv::TRANSFORM).unwrap()),
+            y_transform: transform_choices("y", Transform::from_str(defv::TRANSFORM).unwrap()),
+            axis_scale: axis_scale_choices(AxisScale::from_str(defv::AXIS_SCALE).unwrap()),
+            x_range: EntryBuffer::new(None),
+            y_range: EntryBuffer::new(None),
+            include_origin: include_origin_choice(false),
+            fit_x_range: EntryBuffer::new(None),
+            annotate_intercepts: annotate_intercepts_choice(false),
+            notation: notation_choices(Notation::from_str(defv::NOTATION).unwrap()),
+            x_ticks: EntryBuffer::new(None),
+            y_ticks: EntryBuffer::new(None),
             x_label: EntryBuffer::new(Some(defv::X_LABEL)),
             y_label: EntryBuffer::new(Some(defv::Y_LABEL)),
-            default_x_uncertainty: EntryBuffer::new(Some(defv::X_UNCERTAINTY)),
-            default_y_uncertainty: EntryBuffer::new(Some(defv::X_UNCERTAINTY)),
+            series: Vec::new(),
+            current_series: 0,
+            series_choices: ComboBoxText::new(),
+            series_name: EntryBuffer::new(None),
+            series_colour: ColorButton::new(),
+            series_marker: marker_choices(),
+            decimal_separator: decimal_separator_choices(),
+            repeat_uncertainty: repeat_uncertainty_choices(),
+            resolution: resolution_input(),
+            dataset: TextBufferBuilder::new().build(),
+            default_x_uncertainty: EntryBuffer::new(None),
+            default_y_uncertainty: EntryBuffer::new(None),
+        }
+        .with_series(vec![SeriesInput::nth(0)])
+    }
+
+    /// Replace the series and show the first one
+    fn with_series(mut self, series: Vec<SeriesInput>) -> Self {
+        self.series = series;
+        // There is always a series to edit
+        if self.series.is_empty() {
+            self.series.push(SeriesInput::nth(0));
         }
+        self.load_series(0);
+        self
+    }
+
+    /// Get the series being edited from the views
+    fn current_input(&self) -> SeriesInput {
+        let colour = self.series_colour.rgba();
+        let component = |value: f64| (value * 255.0).round() as u8;
+        let marker = self
+            .series_marker
+            .active_id()
+            .and_then(|id| Marker::from_str(&id).ok())
+            .unwrap_or_default();
+        SeriesInput {
+            name: self.series_name.text(),
+            dataset: self.dataset_str(),
+            default_x_uncertainty: self.default_x_uncertainty.text(),
+            default_y_uncertainty: self.default_y_uncertainty.text(),
+            decimal_separator: self.decimal_separator(),
+            repeats: Repeats {
+                uncertainty: self
+                    .repeat_uncertainty
+                    .active_id()
+                    .and_then(|id| RepeatUncertainty::from_str(&id).ok())
+                    .unwrap_or_default(),
+                resolution: self.resolution.value(),
+            },
+            style: SeriesStyle {
+                colour: Colour(
+                    component(colour.red()),
+                    component(colour.green()),
+                    component(colour.blue()),
+                ),
+                marker,
+            },
+        }
+    }
+
+    /// Write the views back to the series being edited
+    pub fn store_series(&mut self) {
+        self.series[self.current_series] = self.current_input();
+    }
+
+    /// Show the series at index in the views
+    pub fn load_series(&mut self, index: usize) {
+        self.current_series = index;
+        let series = &self.series[index];
+        let colour = series.style.colour;
+        self.series_name.set_text(&series.name);
+        self.series_colour.set_rgba(&RGBA::new(
+            f64::from(colour.0) / 255.0,
+            f64::from(colour.1) / 255.0,
+            f64::from(colour.2) / 255.0,
+            1.0,
+        ));
+        self.series_marker
+            .set_active_id(Some(&series.style.marker.to_string()));
+        // Set before the dataset so that it is checked with the right separator
+        self.decimal_separator
+            .set_active_id(Some(&series.decimal_separator.to_string()));
+        self.repeat_uncertainty
+            .set_active_id(Some(&series.repeats.uncertainty.to_string()));
+        self.resolution.set_value(series.repeats.resolution);
+        self.dataset.set_text(&series.dataset);
+        self.default_x_uncertainty
+            .set_text(&series.default_x_uncertainty);
+        self.default_y_uncertainty
+            .set_text(&series.default_y_uncertainty);
+        self.refresh_series_choices();
+    }
+
+    /// Update the series selection after series are added, removed or renamed
+    pub fn refresh_series_choices(&self) {
+        self.series_choices.remove_all();
+        for (index, series) in self.series.iter().enumerate() {
+            let title = if index == self.current_series {
+                // The name might not be stored yet
+                self.current_input().title(index)
+            } else {
+                series.title(index)
+            };
+            self.series_choices.append(Some(&index.to_string()), &title);
+        }
+        self.series_choices
+            .set_active_id(Some(&self.current_series.to_string()));
+    }
+
+    /// Edit another series
+    pub fn select_series(&mut self, index: usize) {
+        if index != self.current_series && index < self.series.len() {
+            self.store_series();
+            self.load_series(index);
+        }
+    }
+
+    /// Add a series and edit it
+    pub fn add_series(&mut self) {
+        self.store_series();
+        self.series.push(SeriesInput::nth(self.series.len()));
+        self.load_series(self.series.len() - 1);
+    }
+
+    /// Remove the series being edited, unless it is the only one
+    pub fn remove_series(&mut self) {
+        if self.series.len() > 1 {
+            self.series.remove(self.current_series);
+            self.load_series(self.current_series.min(self.series.len() - 1));
+        }
+    }
+
+    /// Get all series, including the changes to the one being edited
+    pub fn all_series(&self) -> Vec<SeriesInput> {
+        let mut series = self.series.clone();
+        series[self.current_series] = self.current_input();
+        series
+    }
+
+    /// Parse all series to be plotted, with the transforms applied and the
+    /// outliers excluded
+    pub fn parse_series(&self) -> Result<Vec<Series>, String> {
+        let transforms = self.transforms();
+        let fit = self.fit_options();
+        self.all_series()
+            .into_iter()
+            .enumerate()
+            .map(|(index, series)| {
+                let title = series.title(index);
+                let invalid = |what: &str, error: &dyn std::fmt::Display| {
+                    format!("{}: Invalid {}: {}", title, what, error)
+                };
+                let saved: SavedSeries = series
+                    .try_into()
+                    .map_err(|error| invalid("uncertainty", &error))?;
+                let mut parsed = saved
+                    .to_series()
+                    .map_err(|error| invalid("dataset", &error))?;
+                // Empty values can crash some backends
+                if parsed.data.is_empty() {
+                    return Err(format!("{}: Empty dataset", title));
+                }
+                parsed.data = parsed
+                    .data
+                    .transform(transforms)
+                    .map_err(|error| invalid("transform", &error))?;
+                if fit.outlier_threshold > 0.0 {
+                    parsed
+                        .data
+                        .exclude_outliers(fit.fit_method, fit.outlier_threshold);
+                }
+                Ok(parsed)
+            })
+            .collect()
+    }
+
+    /// Get the chosen decimal separator of the series being edited
+    pub fn decimal_separator(&self) -> DecimalSeparator {
+        selected_decimal_separator(&self.decimal_separator)
     }
 
     /// Get the value of the dataset
@@ -65,16 +379,91 @@ impl UiState {
             .to_string()
     }
 
+    /// Get the fit options, with the model expression if there is one
+    pub fn fit_options(&self) -> FitOptions {
+        let mut fit = self.spec.fit.clone();
+        fit.residuals = self.residuals.is_active();
+        fit.outlier_threshold = self.outlier_threshold.value();
+        let expression = self.model_expression.text();
+        if !expression.trim().is_empty() {
+            fit.model = FitModel::Expression {
+                expression,
+                initial_guesses: self.initial_guesses.text(),
+            };
+        }
+        fit
+    }
+
+    /// Get the selected transforms
+    pub fn transforms(&self) -> Transforms {
+        let selected = |choices: &ComboBoxText| {
+            choices
+                .active_id()
+                .and_then(|id| Transform::from_str(&id).ok())
+                .unwrap_or_default()
+        };
+        Transforms {
+            x_transform: selected(&self.x_transform),
+            y_transform: selected(&self.y_transform),
+        }
+    }
+
+    /// Get the selected axis scale
+    pub fn axis_scale(&self) -> AxisScale {
+        self.axis_scale
+            .active_id()
+            .and_then(|id| AxisScale::from_str(&id).ok())
+            .unwrap_or_default()
+    }
+
+    /// Get the selected notation of the fitted parameters
+    pub fn notation(&self) -> Notation {
+        self.notation
+            .active_id()
+            .and_then(|id| Notation::from_str(&id).ok())
+            .unwrap_or_default()
+    }
+
+    /// Get the specification of the graph, with the values of the views
+    pub fn plot_spec(&self) -> Result<PlotSpec, String> {
+        let range = |axis: &str, buffer: &EntryBuffer| {
+            let text = buffer.text();
+            if text.trim().is_empty() {
+                Ok(None)
+            } else {
+                plot::parse_axis_range(&text)
+                    .map(Some)
+                    .map_err(|error| format!("Invalid {} range: {}", axis, error))
+            }
+        };
+        let ticks = |axis: &str, buffer: &EntryBuffer| {
+            TickSpacing::from_str(&buffer.text())
+                .map_err(|error| format!("Invalid {} ticks: {}", axis, error))
+        };
+        Ok(PlotSpec {
+            title: self.title.text(),
+            x_label: self.x_label.text(),
+            y_label: self.y_label.text(),
+            fit: self.fit_options(),
+            axis_scale: self.axis_scale(),
+            x_range: range("x", &self.x_range)?,
+            y_range: range("y", &self.y_range)?,
+            include_origin: self.include_origin.is_active(),
+            fit_x_range: range("fit x", &self.fit_x_range)?,
+            annotate_intercepts: self.annotate_intercepts.is_active(),
+            notation: self.notation(),
+            x_ticks: ticks("x", &self.x_ticks)?,
+            y_ticks: ticks("y", &self.y_ticks)?,
+            ..self.spec.clone()
+        })
+    }
+
     /// Save to PhysPlotterFile
     pub fn save(&self) -> std::io::Result<()> {
         let try_save_file: Result<PhysPlotterFile, _> = self.clone().try_into();
-        if let Ok(save_file) = try_save_file {
-            save_file.save_to(&self.file_path)
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Cannot parse float",
-            ))
+        match try_save_file {
+            Ok(save_file) => save_file.save_to(&self.file_path),
+            Err(error) => Err(std::io::Error::new(std::io::ErrorKind::Other, error)),
         }
     }
 
@@ -82,32 +471,170 @@ impl UiState {
     pub fn replace(&mut self, other: UiState) {
         self.saved = other.saved;
         self.title.set_text(&other.title.text());
-        self.dataset.set_text(&other.dataset_str());
+        self.series = other.all_series();
+        self.load_series(other.current_series);
         self.file_path = other.file_path;
         self.backend = other.backend;
+        self.spec = other.spec;
+        self.model_expression
+            .set_text(&other.model_expression.text());
+        self.initial_guesses.set_text(&other.initial_guesses.text());
+        self.residuals.set_active(other.residuals.is_active());
+        self.outlier_threshold
+            .set_value(other.outlier_threshold.value());
+        self.x_transform
+            .set_active_id(other.x_transform.active_id().as_deref());
+        self.y_transform
+            .set_active_id(other.y_transform.active_id().as_deref());
+        self.axis_scale
+            .set_active_id(other.axis_scale.active_id().as_deref());
+        self.x_range.set_text(&other.x_range.text());
+        self.y_range.set_text(&other.y_range.text());
+        self.include_origin
+            .set_active(other.include_origin.is_active());
+        self.fit_x_range.set_text(&other.fit_x_range.text());
+        self.annotate_intercepts
+            .set_active(other.annotate_intercepts.is_active());
+        self.notation
+            .set_active_id(other.notation.active_id().as_deref());
+        self.x_ticks.set_text(&other.x_ticks.text());
+        self.y_ticks.set_text(&other.y_ticks.text());
         self.x_label.set_text(&other.x_label.text());
         self.y_label.set_text(&other.y_label.text());
-        self.default_x_uncertainty
-            .set_text(&other.default_x_uncertainty.text());
-        self.default_y_uncertainty
-            .set_text(&other.default_y_uncertainty.text());
     }
 }
 
+/// Create a selection of transforms shown applied to the variable name
+fn transform_choices(variable: &str, active: Transform) -> ComboBoxText {
+    let choices = ComboBoxText::new();
+    for transform in Transform::ALL {
+        choices.append(Some(&transform.to_string()), &transform.label(variable));
+    }
+    choices.set_active_id(Some(&active.to_string()));
+    choices
+}
+
+/// Create a check box of whether to show the residuals
+fn residuals_choice(active: bool) -> CheckButton {
+    let choice = CheckButton::with_label("Show residuals");
+    choice.set_active(active);
+    choice
+}
+
+/// Create a check box of whether to extend the axes to the origin
+fn include_origin_choice(active: bool) -> CheckButton {
+    let choice = CheckButton::with_label("Include origin");
+    choice.set_active(active);
+    choice
+}
+
+/// Create a check box of whether to mark the intercepts of the fits
+fn annotate_intercepts_choice(active: bool) -> CheckButton {
+    let choice = CheckButton::with_label("Annotate intercepts");
+    choice.set_active(active);
+    choice
+}
+
+/// Create an input of the standardised residual beyond which points are
+/// excluded from the fits
+fn outlier_threshold_input(value: f64) -> SpinButton {
+    let input = SpinButton::with_range(0.0, f64::MAX, 0.1);
+    input.set_digits(1);
+    input.set_value(value);
+    input
+}
+
+/// Create a selection of axis scales
+fn axis_scale_choices(active: AxisScale) -> ComboBoxText {
+    let choices = ComboBoxText::new();
+    for (scale, text) in [
+        (AxisScale::Linear, "Linear"),
+        (AxisScale::LogX, "Log x"),
+        (AxisScale::LogY, "Log y"),
+        (AxisScale::LogLog, "Log-log"),
+    ] {
+        choices.append(Some(&scale.to_string()), text);
+    }
+    choices.set_active_id(Some(&active.to_string()));
+    choices
+}
+
+/// Create a selection of notations of the fitted parameters
+fn notation_choices(active: Notation) -> ComboBoxText {
+    let choices = ComboBoxText::new();
+    for (notation, text) in [
+        (Notation::Auto, "Automatic"),
+        (Notation::Plain, "Plain"),
+        (Notation::Scientific, "Scientific"),
+        (Notation::SiPrefix, "SI prefixes"),
+    ] {
+        choices.append(Some(&notation.to_string()), text);
+    }
+    choices.set_active_id(Some(&active.to_string()));
+    choices
+}
+
+/// Create a selection of markers
+fn marker_choices() -> ComboBoxText {
+    let choices = ComboBoxText::new();
+    for marker in Marker::ALL {
+        choices.append(Some(&marker.to_string()), &marker.to_string());
+    }
+    choices
+}
+
+/// Create a selection of decimal separators
+pub fn decimal_separator_choices() -> ComboBoxText {
+    let choices = ComboBoxText::new();
+    for separator in DecimalSeparator::ALL {
+        choices.append(Some(&separator.to_string()), &separator.to_string());
+    }
+    choices
+}
+
+/// Create a selection of methods to find the uncertainty of repeated readings
+fn repeat_uncertainty_choices() -> ComboBoxText {
+    let choices = ComboBoxText::new();
+    for (method, text) in [
+        (RepeatUncertainty::HalfRange, "Half range"),
+        (RepeatUncertainty::StandardError, "Standard error"),
+    ] {
+        choices.append(Some(&method.to_string()), text);
+    }
+    choices
+}
+
+/// Create an input of the instrument resolution
+fn resolution_input() -> SpinButton {
+    let input = SpinButton::with_range(0.0, f64::MAX, 0.001);
+    input.set_digits(4);
+    input
+}
+
+/// Get the decimal separator chosen in choices
+pub fn selected_decimal_separator(choices: &ComboBoxText) -> DecimalSeparator {
+    choices
+        .active_id()
+        .and_then(|id| DecimalSeparator::from_str(&id).ok())
+        .unwrap_or_default()
+}
+
 /// Create save file from the state
 impl TryInto<PhysPlotterFile> for UiState {
-    type Error = <f64 as FromStr>::Err;
+    type Error = String;
     fn try_into(self) -> Result<PhysPlotterFile, Self::Error> {
         Ok(PhysPlotterFile {
             creator: defv::APP_ID.to_string(),
             version: crate_version!().to_string(),
-            title: self.title.text(),
             backend_name: format!("{}", self.backend),
-            x_label: self.x_label.text(),
-            y_label: self.y_label.text(),
-            default_x_uncertainty: self.default_x_uncertainty.text().parse()?,
-            default_y_uncertainty: self.default_x_uncertainty.text().parse()?,
-            dataset: self.dataset_str(),
+            series: self
+                .all_series()
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, UncertaintyFromStrError>>()
+                .map_err(|error| format!("Invalid uncertainty: {}", error))?,
+            spec: self.plot_spec()?,
+            transforms: self.transforms(),
         })
     }
 }
@@ -116,16 +643,69 @@ impl TryInto<PhysPlotterFile> for UiState {
 impl TryFrom<PhysPlotterFile> for UiState {
     type Error = BackendsFromStrError;
     fn try_from(that: PhysPlotterFile) -> Result<Self, Self::Error> {
+        let mut spec = that.spec;
+        let (expression, initial_guesses) = match std::mem::take(&mut spec.fit.model) {
+            FitModel::Expression {
+                expression,
+                initial_guesses,
+            } => (expression, initial_guesses),
+            model => {
+                spec.fit.model = model;
+                (String::default(), String::default())
+            }
+        };
+        let residuals = residuals_choice(spec.fit.residuals);
+        let outlier_threshold = outlier_threshold_input(spec.fit.outlier_threshold);
         Ok(Self {
             saved: true,
             file_path: Default::default(),
-            title: EntryBuffer::new(Some(&that.title)),
-            dataset: TextBufferBuilder::new().text(&that.dataset).build(),
+            title: EntryBuffer::new(Some(&spec.title)),
             backend: Backends::from_str(&that.backend_name)?,
-            x_label: EntryBuffer::new(Some(&that.x_label)),
-            y_label: EntryBuffer::new(Some(&that.y_label)),
-            default_x_uncertainty: EntryBuffer::new(Some(&that.default_x_uncertainty.to_string())),
-            default_y_uncertainty: EntryBuffer::new(Some(&that.default_y_uncertainty.to_string())),
-        })
+            model_expression: EntryBuffer::new(Some(&expression)),
+            initial_guesses: EntryBuffer::new(Some(&initial_guesses)),
+            residuals,
+            outlier_threshold,
+            x_transform: transform_choices("x", that.transforms.x_transform),
+            y_transform: transform_choices("y", that.transforms.y_transform),
+            axis_scale: axis_scale_choices(spec.axis_scale),
+            x_range: EntryBuffer::new(
+                spec.x_range
+                    .as_ref()
+                    .map(plot::format_axis_range)
+                    .as_deref(),
+            ),
+            y_range: EntryBuffer::new(
+                spec.y_range
+                    .as_ref()
+                    .map(plot::format_axis_range)
+                    .as_deref(),
+            ),
+            include_origin: include_origin_choice(spec.include_origin),
+            fit_x_range: EntryBuffer::new(
+                spec.fit_x_range
+                    .as_ref()
+                    .map(plot::format_axis_range)
+                    .as_deref(),
+            ),
+            annotate_intercepts: annotate_intercepts_choice(spec.annotate_intercepts),
+            notation: notation_choices(spec.notation),
+            x_ticks: EntryBuffer::new(Some(&spec.x_ticks.to_string())),
+            y_ticks: EntryBuffer::new(Some(&spec.y_ticks.to_string())),
+            x_label: EntryBuffer::new(Some(&spec.x_label)),
+            y_label: EntryBuffer::new(Some(&spec.y_label)),
+            series: Vec::new(),
+            current_series: 0,
+            series_choices: ComboBoxText::new(),
+            series_name: EntryBuffer::new(None),
+            series_colour: ColorButton::new(),
+            series_marker: marker_choices(),
+            decimal_separator: decimal_separator_choices(),
+            repeat_uncertainty: repeat_uncertainty_choices(),
+            resolution: resolution_input(),
+            dataset: TextBufferBuilder::new().build(),
+            default_x_uncertainty: EntryBuffer::new(None),
+            default_y_uncertainty: EntryBuffer::new(None),
+        }
+        .with_series(that.series.into_iter().map(SeriesInput::from).collect()))
     }
 }
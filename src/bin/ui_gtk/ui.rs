@@ -19,7 +19,7 @@
 
 use crate::actions::register_actions;
 use crate::menu::build_menu;
-use crate::state::UiState;
+use crate::state::{selected_decimal_separator, UiState};
 use gio::prelude::*;
 use glib::clone;
 use gtk::prelude::*;
@@ -29,9 +29,10 @@ use gtk::{
         EntryBuilder, HeaderBarBuilder, MessageDialogBuilder, ScrolledWindowBuilder,
         TextViewBuilder, ToolButtonBuilder,
     },
-    Box, ButtonsType, IconSize, Image, Label, MessageDialog, Paned, ResponseType, Separator,
-    ToolItem, Toolbar,
+    Box, Button, ButtonsType, ComboBoxText, IconSize, Image, Label, MessageDialog, Paned,
+    ResponseType, Separator, TextBuffer, TextView, ToolItem, Toolbar,
 };
+use phys_plotter::data::{Repeats, TwoVarDataSet, Uncertainty};
 use phys_plotter::default_values as defv;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -100,6 +101,16 @@ fn draw_toolbar() -> Toolbar {
         .action_name("app.plot")
         .build();
     toolbar.add(&button_generate);
+    let button_export_script = ToolButtonBuilder::new()
+        .label("_Export Script")
+        .label_widget(&Image::from_icon_name(
+            Some("text-x-script"),
+            IconSize::Menu,
+        ))
+        .tooltip_text("Export a gnuplot script reproducing the plot")
+        .action_name("app.export_script")
+        .build();
+    toolbar.add(&button_export_script);
     toolbar
 }
 
@@ -150,6 +161,111 @@ fn draw_properties_area(state: &Rc<RefCell<UiState>>) -> Box {
     let uy_label = Label::new(Some("Default y uncertainty"));
     let uy_input = text_input!(&state_borrowed.default_y_uncertainty, defv::Y_UNCERTAINTY);
     uy_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let expression_label = Label::new(Some("Model expression y ="));
+    let expression_input = text_input!(&state_borrowed.model_expression, "a*exp(-x/tau)+c");
+    expression_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let guesses_label = Label::new(Some("Initial guesses"));
+    let guesses_input = text_input!(&state_borrowed.initial_guesses, "a=1, tau=2");
+    guesses_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    state_borrowed
+        .residuals
+        .connect_toggled(clone!(@strong state => move |_| unsave!(state)));
+    let outlier_threshold_label = Label::new(Some("Exclude outliers beyond (σ, 0 keeps all)"));
+    state_borrowed
+        .outlier_threshold
+        .connect_value_changed(clone!(@strong state => move |_| unsave!(state)));
+    let x_transform_label = Label::new(Some("Transform x"));
+    state_borrowed
+        .x_transform
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let y_transform_label = Label::new(Some("Transform y"));
+    state_borrowed
+        .y_transform
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let axis_scale_label = Label::new(Some("Axis scale"));
+    state_borrowed
+        .axis_scale
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let x_range_label = Label::new(Some("X range (empty to cover the data)"));
+    let x_range_input = text_input!(&state_borrowed.x_range, "0:10");
+    x_range_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let y_range_label = Label::new(Some("Y range (empty to cover the data)"));
+    let y_range_input = text_input!(&state_borrowed.y_range, "0:10");
+    y_range_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    state_borrowed
+        .include_origin
+        .connect_toggled(clone!(@strong state => move |_| unsave!(state)));
+    let fit_x_range_label = Label::new(Some("Extrapolate fits over x (empty to cover the data)"));
+    let fit_x_range_input = text_input!(&state_borrowed.fit_x_range, "0:10");
+    fit_x_range_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    state_borrowed
+        .annotate_intercepts
+        .connect_toggled(clone!(@strong state => move |_| unsave!(state)));
+    let notation_label = Label::new(Some("Notation of the fitted parameters"));
+    state_borrowed
+        .notation
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let x_ticks_label = Label::new(Some("X tick spacing (major:minor, empty for automatic)"));
+    let x_ticks_input = text_input!(&state_borrowed.x_ticks, "5:1");
+    x_ticks_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let y_ticks_label = Label::new(Some("Y tick spacing (major:minor, empty for automatic)"));
+    let y_ticks_input = text_input!(&state_borrowed.y_ticks, "5:1");
+    y_ticks_input.connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let series_title = HeaderBarBuilder::new().title("Series").build();
+    state_borrowed
+        .series_choices
+        .connect_changed(clone!(@strong state => move |choices| {
+            // Skipped when the state rebuilds the choices itself
+            if let Ok(mut borrowed) = state.try_borrow_mut() {
+                if let Some(index) = choices.active_id().and_then(|id| id.parse().ok()) {
+                    borrowed.select_series(index);
+                }
+            }
+        }));
+    let add_series_button = Button::with_label("Add");
+    add_series_button.connect_clicked(clone!(@strong state => move |_| {
+        let mut borrowed = state.borrow_mut();
+        borrowed.add_series();
+        borrowed.saved = false;
+    }));
+    let remove_series_button = Button::with_label("Remove");
+    remove_series_button.connect_clicked(clone!(@strong state => move |_| {
+        let mut borrowed = state.borrow_mut();
+        borrowed.remove_series();
+        borrowed.saved = false;
+    }));
+    let series_selection = Box::new(Horizontal, 5);
+    series_selection.pack_start(&state_borrowed.series_choices, true, true, 0);
+    series_selection.add(&add_series_button);
+    series_selection.add(&remove_series_button);
+    let name_label = Label::new(Some("Name"));
+    let name_input = text_input!(&state_borrowed.series_name, "Shown in the legend");
+    name_input.connect_changed(clone!(@strong state => move |_| {
+        if let Ok(mut borrowed) = state.try_borrow_mut() {
+            borrowed.saved = false;
+            borrowed.refresh_series_choices();
+        }
+    }));
+    let colour_label = Label::new(Some("Colour"));
+    state_borrowed
+        .series_colour
+        .connect_color_set(clone!(@strong state => move |_| unsave!(state)));
+    let marker_label = Label::new(Some("Marker"));
+    state_borrowed
+        .series_marker
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let separator_label = Label::new(Some("Decimal separator"));
+    state_borrowed
+        .decimal_separator
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let repeat_uncertainty_label = Label::new(Some("Uncertainty of repeated readings"));
+    state_borrowed
+        .repeat_uncertainty
+        .connect_changed(clone!(@strong state => move |_| unsave!(state)));
+    let resolution_label = Label::new(Some("Instrument resolution (uncertainty resolution/√12)"));
+    state_borrowed
+        .resolution
+        .connect_value_changed(clone!(@strong state => move |_| unsave!(state)));
     properties_area.add(&properties_area_title);
     properties_area.add(&title_label);
     properties_area.add(&title_input);
@@ -157,13 +273,83 @@ fn draw_properties_area(state: &Rc<RefCell<UiState>>) -> Box {
     properties_area.add(&xlabel_input);
     properties_area.add(&ylabel_label);
     properties_area.add(&ylabel_input);
+    properties_area.add(&expression_label);
+    properties_area.add(&expression_input);
+    properties_area.add(&guesses_label);
+    properties_area.add(&guesses_input);
+    properties_area.add(&state_borrowed.residuals);
+    properties_area.add(&outlier_threshold_label);
+    properties_area.add(&state_borrowed.outlier_threshold);
+    properties_area.add(&x_transform_label);
+    properties_area.add(&state_borrowed.x_transform);
+    properties_area.add(&y_transform_label);
+    properties_area.add(&state_borrowed.y_transform);
+    properties_area.add(&axis_scale_label);
+    properties_area.add(&state_borrowed.axis_scale);
+    properties_area.add(&x_range_label);
+    properties_area.add(&x_range_input);
+    properties_area.add(&y_range_label);
+    properties_area.add(&y_range_input);
+    properties_area.add(&state_borrowed.include_origin);
+    properties_area.add(&fit_x_range_label);
+    properties_area.add(&fit_x_range_input);
+    properties_area.add(&state_borrowed.annotate_intercepts);
+    properties_area.add(&notation_label);
+    properties_area.add(&state_borrowed.notation);
+    properties_area.add(&x_ticks_label);
+    properties_area.add(&x_ticks_input);
+    properties_area.add(&y_ticks_label);
+    properties_area.add(&y_ticks_input);
+    properties_area.add(&series_title);
+    properties_area.add(&series_selection);
+    properties_area.add(&name_label);
+    properties_area.add(&name_input);
+    properties_area.add(&colour_label);
+    properties_area.add(&state_borrowed.series_colour);
+    properties_area.add(&marker_label);
+    properties_area.add(&state_borrowed.series_marker);
     properties_area.add(&ux_label);
     properties_area.add(&ux_input);
     properties_area.add(&uy_label);
     properties_area.add(&uy_input);
+    properties_area.add(&separator_label);
+    properties_area.add(&state_borrowed.decimal_separator);
+    properties_area.add(&repeat_uncertainty_label);
+    properties_area.add(&state_borrowed.repeat_uncertainty);
+    properties_area.add(&resolution_label);
+    properties_area.add(&state_borrowed.resolution);
     properties_area
 }
 
+/// Highlight the line of the dataset that cannot be parsed,
+/// and explain why in the tooltip
+fn mark_parse_error(buffer: &TextBuffer, view: &TextView, separator: &ComboBoxText) {
+    let (start, end) = buffer.bounds();
+    buffer.remove_tag_by_name("error", &start, &end);
+    let text = buffer
+        .text(&start, &end, true)
+        .map(|text| text.to_string())
+        .unwrap_or_default();
+    match TwoVarDataSet::from_string(
+        &text,
+        Uncertainty::default(),
+        Uncertainty::default(),
+        Repeats::default(),
+        selected_decimal_separator(separator),
+    ) {
+        Ok(_) => view.set_tooltip_text(None),
+        Err(error) => {
+            let line_start = buffer.iter_at_line(error.line as i32 - 1);
+            let mut line_end = line_start.clone();
+            if !line_end.ends_line() {
+                line_end.forward_to_line_end();
+            }
+            buffer.apply_tag_by_name("error", &line_start, &line_end);
+            view.set_tooltip_text(Some(&error.to_string()));
+        }
+    }
+}
+
 /// Draw the editing area
 fn draw_editing_area(state: &Rc<RefCell<UiState>>) -> Paned {
     let editing_area = Paned::new(Horizontal);
@@ -180,6 +366,19 @@ fn draw_editing_area(state: &Rc<RefCell<UiState>>) -> Paned {
     text_area_view.connect_delete_from_cursor(clone!(@strong state => move |_,_,_| unsave!(state)));
     text_area_view.connect_insert_at_cursor(clone!(@strong state => move |_,_| unsave!(state)));
     text_area_view.connect_paste_clipboard(clone!(@strong state => move |_| unsave!(state)));
+    let dataset = state.borrow().dataset.clone();
+    let separator = state.borrow().decimal_separator.clone();
+    dataset.create_tag(Some("error"), &[("background", &"#ffcccc")]);
+    dataset.connect_changed(
+        clone!(@weak text_area_view, @weak separator => move |buffer| {
+            mark_parse_error(buffer, &text_area_view, &separator);
+        }),
+    );
+    separator.connect_changed(
+        clone!(@weak text_area_view, @weak dataset => move |separator| {
+            mark_parse_error(&dataset, &text_area_view, separator);
+        }),
+    );
     let text_area_text = ScrolledWindowBuilder::new()
         .child(&text_area_view)
         // Have a border around
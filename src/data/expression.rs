@@ -0,0 +1,531 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Error while parsing an expression. Positions are 1-based character columns
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpressionError {
+    UnexpectedCharacter(char, usize),
+    UnexpectedToken(String, usize),
+    UnexpectedEnd,
+    UnknownFunction(String, usize),
+    Empty,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::UnexpectedCharacter(ch, pos) => {
+                write!(f, "Unexpected character '{}' at column {}", ch, pos)
+            }
+            ExpressionError::UnexpectedToken(token, pos) => {
+                write!(f, "Unexpected \"{}\" at column {}", token, pos)
+            }
+            ExpressionError::UnexpectedEnd => write!(f, "Unexpected end of expression"),
+            ExpressionError::UnknownFunction(name, pos) => {
+                write!(f, "Unknown function \"{}\" at column {}", name, pos)
+            }
+            ExpressionError::Empty => write!(f, "Empty expression"),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// Functions that can be called in an expression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Exp,
+    Ln,
+    Log10,
+    Sqrt,
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "exp" => Some(Function::Exp),
+            "ln" => Some(Function::Ln),
+            "log" | "log10" => Some(Function::Log10),
+            "sqrt" => Some(Function::Sqrt),
+            "abs" => Some(Function::Abs),
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "tan" => Some(Function::Tan),
+            "asin" => Some(Function::Asin),
+            "acos" => Some(Function::Acos),
+            "atan" => Some(Function::Atan),
+            "sinh" => Some(Function::Sinh),
+            "cosh" => Some(Function::Cosh),
+            "tanh" => Some(Function::Tanh),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            Function::Exp => value.exp(),
+            Function::Ln => value.ln(),
+            Function::Log10 => value.log10(),
+            Function::Sqrt => value.sqrt(),
+            Function::Abs => value.abs(),
+            Function::Sin => value.sin(),
+            Function::Cos => value.cos(),
+            Function::Tan => value.tan(),
+            Function::Asin => value.asin(),
+            Function::Acos => value.acos(),
+            Function::Atan => value.atan(),
+            Function::Sinh => value.sinh(),
+            Function::Cosh => value.cosh(),
+            Function::Tanh => value.tanh(),
+        }
+    }
+}
+
+/// Binary operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+/// Node of the syntax tree
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(f64),
+    /// The independent variable
+    X,
+    /// Index into the parameter list
+    Parameter(usize),
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Call(Function, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, x: f64, parameters: &[f64]) -> f64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::X => x,
+            Node::Parameter(index) => parameters[*index],
+            Node::Negate(operand) => -operand.eval(x, parameters),
+            Node::Binary(operator, lhs, rhs) => {
+                let lhs = lhs.eval(x, parameters);
+                let rhs = rhs.eval(x, parameters);
+                match operator {
+                    Operator::Add => lhs + rhs,
+                    Operator::Subtract => lhs - rhs,
+                    Operator::Multiply => lhs * rhs,
+                    Operator::Divide => lhs / rhs,
+                    Operator::Power => lhs.powf(rhs),
+                }
+            }
+            Node::Call(function, argument) => function.apply(argument.eval(x, parameters)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Operator(Operator),
+    LeftParen,
+    RightParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::Operator(Operator::Add) => write!(f, "+"),
+            Token::Operator(Operator::Subtract) => write!(f, "-"),
+            Token::Operator(Operator::Multiply) => write!(f, "*"),
+            Token::Operator(Operator::Divide) => write!(f, "/"),
+            Token::Operator(Operator::Power) => write!(f, "^"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+        }
+    }
+}
+
+/// Split the source into tokens, each with its 1-based column
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let ch = chars[idx];
+        let column = idx + 1;
+        if ch.is_whitespace() {
+            idx += 1;
+            continue;
+        }
+        if ch.is_ascii_digit() || ch == '.' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                idx += 1;
+            }
+            // Exponent, only if followed by digits, so that the e of "2e" is
+            // left as a name, which is then rejected after the number
+            if idx < chars.len() && (chars[idx] == 'e' || chars[idx] == 'E') {
+                let mut end = idx + 1;
+                if end < chars.len() && (chars[end] == '+' || chars[end] == '-') {
+                    end += 1;
+                }
+                if end < chars.len() && chars[end].is_ascii_digit() {
+                    idx = end;
+                    while idx < chars.len() && chars[idx].is_ascii_digit() {
+                        idx += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..idx].iter().collect();
+            match text.parse() {
+                Ok(value) => tokens.push((Token::Number(value), column)),
+                Err(_) => return Err(ExpressionError::UnexpectedToken(text, column)),
+            }
+            continue;
+        }
+        if ch.is_alphabetic() || ch == '_' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            let name: String = chars[start..idx].iter().collect();
+            tokens.push((Token::Identifier(name), column));
+            continue;
+        }
+        let token = match ch {
+            '+' => Token::Operator(Operator::Add),
+            '-' | '−' => Token::Operator(Operator::Subtract),
+            // Accept "**" as in many programming languages
+            '*' if chars.get(idx + 1) == Some(&'*') => {
+                idx += 1;
+                Token::Operator(Operator::Power)
+            }
+            '*' | '×' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '^' => Token::Operator(Operator::Power),
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            other => return Err(ExpressionError::UnexpectedCharacter(other, column)),
+        };
+        tokens.push((token, column));
+        idx += 1;
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    parameters: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ExpressionError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(ExpressionError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// expression := term (("+" | "-") term)*
+    fn expression(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.term()?;
+        while let Some(Token::Operator(operator @ (Operator::Add | Operator::Subtract))) =
+            self.peek()
+        {
+            let operator = *operator;
+            self.position += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+        Ok(node)
+    }
+
+    /// term := unary (("*" | "/") unary)*
+    fn term(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        while let Some(Token::Operator(operator @ (Operator::Multiply | Operator::Divide))) =
+            self.peek()
+        {
+            let operator = *operator;
+            self.position += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    /// unary := ("+" | "-") unary | power
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        match self.peek() {
+            Some(Token::Operator(Operator::Subtract)) => {
+                self.position += 1;
+                Ok(Node::Negate(Box::new(self.unary()?)))
+            }
+            Some(Token::Operator(Operator::Add)) => {
+                self.position += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// power := primary ("^" unary)?, so that it is right-associative
+    fn power(&mut self) -> Result<Node, ExpressionError> {
+        let base = self.primary()?;
+        if let Some(Token::Operator(Operator::Power)) = self.peek() {
+            self.position += 1;
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                Operator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    /// primary := number | name | name "(" expression ")" | "(" expression ")"
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        let (token, column) = self.next()?;
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::LeftParen => {
+                let node = self.expression()?;
+                self.expect_right_paren()?;
+                Ok(node)
+            }
+            Token::Identifier(name) => {
+                if self.peek() == Some(&Token::LeftParen) {
+                    let function = Function::from_name(&name)
+                        .ok_or(ExpressionError::UnknownFunction(name, column))?;
+                    self.position += 1;
+                    let argument = self.expression()?;
+                    self.expect_right_paren()?;
+                    return Ok(Node::Call(function, Box::new(argument)));
+                }
+                Ok(match name.as_str() {
+                    "x" => Node::X,
+                    "pi" => Node::Number(std::f64::consts::PI),
+                    "e" => Node::Number(std::f64::consts::E),
+                    _ => Node::Parameter(self.parameter_index(name)),
+                })
+            }
+            other => Err(ExpressionError::UnexpectedToken(other.to_string(), column)),
+        }
+    }
+
+    fn expect_right_paren(&mut self) -> Result<(), ExpressionError> {
+        match self.next()? {
+            (Token::RightParen, _) => Ok(()),
+            (other, column) => Err(ExpressionError::UnexpectedToken(other.to_string(), column)),
+        }
+    }
+
+    /// Index of the parameter, registering it if first seen
+    fn parameter_index(&mut self, name: String) -> usize {
+        match self.parameters.iter().position(|known| *known == name) {
+            Some(index) => index,
+            None => {
+                self.parameters.push(name);
+                self.parameters.len() - 1
+            }
+        }
+    }
+}
+
+/// Model expression of y in terms of x and free parameters.
+///
+/// Supports `+ - * / ^` (or `**`), parentheses, the constants `pi` and `e`,
+/// and the functions `exp ln log sqrt abs sin cos tan asin acos atan sinh
+/// cosh tanh`, where `log` is base 10. Any other name is a parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+    parameters: Vec<String>,
+}
+
+impl Expression {
+    /// Names of the parameters in order of first appearance
+    #[must_use]
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    /// Evaluate the expression, parameter values ordered as in `parameters()`
+    #[must_use]
+    pub fn eval(&self, x: f64, parameters: &[f64]) -> f64 {
+        self.root.eval(x, parameters)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Err(ExpressionError::Empty);
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            parameters: Vec::new(),
+        };
+        let root = parser.expression()?;
+        // Everything must have been consumed
+        if let Some((token, column)) = parser.tokens.get(parser.position) {
+            return Err(ExpressionError::UnexpectedToken(token.to_string(), *column));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            root,
+            parameters: parser.parameters,
+        })
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "y = {}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value of an expression without parameters
+    fn eval(source: &str, x: f64) -> f64 {
+        let expression: Expression = source.parse().unwrap();
+        assert!(expression.parameters().is_empty(), "{:?}", source);
+        expression.eval(x, &[])
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(eval("8 / 4 / 2", 0.0), 1.0);
+        assert_eq!(eval("10 - 4 - 3", 0.0), 3.0);
+        assert_eq!(eval("2 * x ^ 2", 3.0), 18.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0.0), 512.0);
+        assert_eq!(eval("2 ** 3", 0.0), 8.0);
+        assert_eq!(eval("1.5e2 + x", 1.0), 151.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-x ^ 2", 3.0), -9.0);
+        assert_eq!(eval("(-x) ^ 2", 3.0), 9.0);
+        assert_eq!(eval("2 ^ -1", 0.0), 0.5);
+        assert_eq!(eval("--x", 4.0), 4.0);
+        assert_eq!(eval("3 * -x", 2.0), -6.0);
+        assert_eq!(eval("\u{2212}x + +1", 2.0), -1.0);
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        assert_eq!(eval("exp(0) + ln(e)", 0.0), 2.0);
+        assert_eq!(eval("log(1000)", 0.0), 3.0);
+        assert_eq!(eval("sqrt(abs(x))", -16.0), 4.0);
+        assert_eq!(eval("cos(pi)", 0.0), -1.0);
+    }
+
+    #[test]
+    fn parameters_in_order_of_appearance() {
+        let expression: Expression = "a * exp(-x / tau) + a + c".parse().unwrap();
+        assert_eq!(expression.parameters(), ["a", "tau", "c"]);
+        assert_eq!(expression.eval(0.0, &[2.0, 5.0, 1.0]), 5.0);
+        assert_eq!(expression.to_string(), "y = a * exp(-x / tau) + a + c");
+    }
+
+    #[test]
+    fn unknown_function() {
+        assert_eq!(
+            "2 * foo(x)".parse::<Expression>(),
+            Err(ExpressionError::UnknownFunction("foo".to_string(), 5))
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!("".parse::<Expression>(), Err(ExpressionError::Empty));
+        assert_eq!("  ".parse::<Expression>(), Err(ExpressionError::Empty));
+        assert_eq!(
+            "1 +".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "(x + 1".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "x + 1)".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedToken(")".to_string(), 6))
+        );
+        assert_eq!(
+            "x # 2".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedCharacter('#', 3))
+        );
+        assert_eq!(
+            "1.2.3".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedToken("1.2.3".to_string(), 1))
+        );
+    }
+
+    #[test]
+    fn no_implicit_multiplication() {
+        assert_eq!(
+            "2x".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedToken("x".to_string(), 2))
+        );
+        assert_eq!(
+            "2e".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedToken("e".to_string(), 2))
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::{
+    FitMethod, FitModel, FitOptions, Line, LineFit, LineFitError, NonlinearFit, NonlinearFitError,
+    Polynomial, ThroughAllError, TwoVarDataSet,
+};
+
+/// The fits of a dataset with some fit options, made once and shared by
+/// everything drawn or written from them
+#[derive(Debug, Clone)]
+pub enum FitResults {
+    /// Straight line with its (minimum, maximum) gradient lines
+    Line {
+        best_fit: Result<LineFit, LineFitError>,
+        gradient_lines: Result<(Line, Line), ThroughAllError>,
+    },
+    /// None if the polynomial cannot be fitted
+    Polynomial(Option<Polynomial>),
+    Expression(Result<NonlinearFit, NonlinearFitError>),
+}
+
+impl FitResults {
+    /// Fit the model of the options to the dataset
+    #[must_use]
+    pub fn new(data: &TwoVarDataSet, fit: &FitOptions) -> Self {
+        match &fit.model {
+            FitModel::Line => FitResults::Line {
+                best_fit: data.line_fit(fit.fit_method),
+                gradient_lines: data.gradient_lines_by(fit.gradient_line_method),
+            },
+            FitModel::Polynomial { degree } => FitResults::Polynomial(
+                data.polynomial_best_fit(*degree, fit.fit_method == FitMethod::Weighted),
+            ),
+            FitModel::Expression {
+                expression,
+                initial_guesses,
+            } => FitResults::Expression(data.expression_fit(
+                expression,
+                initial_guesses,
+                fit.fit_method,
+            )),
+        }
+    }
+
+    /// y value of the best fit at x, None if the model cannot be fitted
+    #[must_use]
+    pub fn y(&self, x: f64) -> Option<f64> {
+        match self {
+            FitResults::Line { best_fit, .. } => {
+                best_fit.as_ref().ok().map(|line_fit| line_fit.line.y(x))
+            }
+            FitResults::Polynomial(polynomial) => polynomial.as_ref().map(|poly| poly.y(x)),
+            FitResults::Expression(expression_fit) => expression_fit
+                .as_ref()
+                .ok()
+                .map(|expression_fit| expression_fit.y(x)),
+        }
+    }
+
+    /// Gradient of the best fit at x, None if the model cannot be fitted
+    #[must_use]
+    pub fn gradient(&self, x: f64) -> Option<f64> {
+        match self {
+            FitResults::Line { best_fit, .. } => best_fit
+                .as_ref()
+                .ok()
+                .map(|line_fit| line_fit.line.gradient),
+            FitResults::Polynomial(polynomial) => polynomial.as_ref().map(|poly| poly.gradient(x)),
+            FitResults::Expression(expression_fit) => {
+                expression_fit.as_ref().ok().map(|expression_fit| {
+                    // Central difference, with the step balancing truncation
+                    // against rounding
+                    let step = f64::EPSILON.cbrt() * x.abs().max(1.0);
+                    (expression_fit.y(x + step) - expression_fit.y(x - step)) / (2.0 * step)
+                })
+            }
+        }
+    }
+
+    /// The line of best fit and its statistics, if the model is a straight
+    /// line and it can be fitted
+    #[must_use]
+    pub fn line_fit(&self) -> Option<LineFit> {
+        match self {
+            FitResults::Line { best_fit, .. } => best_fit.ok(),
+            _ => None,
+        }
+    }
+
+    /// The (minimum, maximum) gradient lines, if the model is a straight line
+    /// and they are found
+    #[must_use]
+    pub fn gradient_lines(&self) -> Option<(Line, Line)> {
+        match self {
+            FitResults::Line { gradient_lines, .. } => gradient_lines.ok(),
+            _ => None,
+        }
+    }
+}
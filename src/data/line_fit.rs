@@ -0,0 +1,268 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::{Line, Notation, UncertainValue};
+use std::str::FromStr;
+
+/// Methods to compute the line of best fit
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum FitMethod {
+    /// Ordinary least squares, ignoring the uncertainties
+    #[default]
+    Ordinary,
+    /// Least squares weighted by 1/σy²
+    Weighted,
+    /// York regression, accounting for both x and y uncertainties
+    York,
+}
+
+impl std::fmt::Display for FitMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitMethod::Ordinary => write!(f, "ordinary"),
+            FitMethod::Weighted => write!(f, "weighted"),
+            FitMethod::York => write!(f, "york"),
+        }
+    }
+}
+
+/// Error when converting from str to `FitMethod`
+#[derive(Clone, Debug)]
+pub enum FitMethodFromStrError {
+    UnknownMethod(String),
+}
+
+impl std::fmt::Display for FitMethodFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitMethodFromStrError::UnknownMethod(mstr) => {
+                write!(f, "Unknown fit method: {}", mstr)
+            }
+        }
+    }
+}
+
+impl FromStr for FitMethod {
+    type Err = FitMethodFromStrError;
+
+    /// Parse fit method description
+    fn from_str(mstr: &str) -> Result<Self, Self::Err> {
+        match mstr.to_lowercase().as_str() {
+            "ordinary" | "ols" => Ok(FitMethod::Ordinary),
+            "weighted" | "wls" => Ok(FitMethod::Weighted),
+            "york" => Ok(FitMethod::York),
+            other => Err(Self::Err::UnknownMethod(other.to_string())),
+        }
+    }
+}
+
+/// Methods to find the maximum and minimum gradient lines
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum GradientLineMethod {
+    /// Connect the corners of the error boxes of the first and the last points
+    #[default]
+    Endpoints,
+    /// Steepest and shallowest lines passing through every error box
+    AllPoints,
+}
+
+impl std::fmt::Display for GradientLineMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GradientLineMethod::Endpoints => write!(f, "endpoints"),
+            GradientLineMethod::AllPoints => write!(f, "all-points"),
+        }
+    }
+}
+
+/// Error when converting from str to `GradientLineMethod`
+#[derive(Clone, Debug)]
+pub enum GradientLineMethodFromStrError {
+    UnknownMethod(String),
+}
+
+impl std::fmt::Display for GradientLineMethodFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GradientLineMethodFromStrError::UnknownMethod(mstr) => {
+                write!(f, "Unknown gradient line method: {}", mstr)
+            }
+        }
+    }
+}
+
+impl FromStr for GradientLineMethod {
+    type Err = GradientLineMethodFromStrError;
+
+    /// Parse gradient line method description
+    fn from_str(mstr: &str) -> Result<Self, Self::Err> {
+        match mstr.to_lowercase().as_str() {
+            "endpoints" => Ok(GradientLineMethod::Endpoints),
+            "all-points" | "all" => Ok(GradientLineMethod::AllPoints),
+            other => Err(Self::Err::UnknownMethod(other.to_string())),
+        }
+    }
+}
+
+/// Reasons why there are no steepest and shallowest lines through every
+/// error box
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThroughAllError {
+    TooFewPoints,
+    /// No line passes through every error box
+    Infeasible,
+    /// A vertical line passes through every error box, as their x ranges
+    /// overlap, so the gradients have no bounds
+    Unbounded,
+}
+
+impl std::fmt::Display for ThroughAllError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThroughAllError::TooFewPoints => {
+                write!(f, "At least two points are required for gradient lines")
+            }
+            ThroughAllError::Infeasible => write!(f, "No line passes through all error bars"),
+            ThroughAllError::Unbounded => write!(
+                f,
+                "The x error bars overlap, so lines through all of them can be as steep as wanted"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ThroughAllError {}
+
+/// Models to be fitted to a dataset
+#[derive(PartialEq, Eq, Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub enum FitModel {
+    /// Straight line with maximum and minimum gradient lines
+    #[default]
+    Line,
+    /// Polynomial of the specified degree
+    Polynomial { degree: usize },
+    /// User-entered expression such as "a*exp(-x/tau)+c", with initial
+    /// guesses such as "a=1, tau=2"
+    Expression {
+        expression: String,
+        initial_guesses: String,
+    },
+}
+
+/// Options controlling how lines are fitted to a dataset
+#[derive(PartialEq, Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub struct FitOptions {
+    #[serde(default)]
+    pub model: FitModel,
+    #[serde(default)]
+    pub fit_method: FitMethod,
+    #[serde(default)]
+    pub gradient_line_method: GradientLineMethod,
+    /// Show the residuals from the best fit in a panel under the chart
+    #[serde(default)]
+    pub residuals: bool,
+    /// Points with studentised residuals larger than this are excluded
+    /// from the fits, 0 to keep all points
+    #[serde(default)]
+    pub outlier_threshold: f64,
+}
+
+/// Reasons why a line cannot be fitted with the chosen method
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineFitError {
+    TooFewPoints,
+    /// All points have the same x value
+    VerticalLine,
+    /// Weighted fitting with a y uncertainty that is zero or negative
+    NonPositiveUncertainty,
+    /// York fitting with a point that has neither x nor y uncertainty
+    MissingUncertainty,
+    NotConverged,
+}
+
+impl std::fmt::Display for LineFitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineFitError::TooFewPoints => write!(f, "At least two points are required"),
+            LineFitError::VerticalLine => write!(f, "All points have the same x value"),
+            LineFitError::NonPositiveUncertainty => {
+                write!(f, "Weighted fitting requires positive y uncertainties")
+            }
+            LineFitError::MissingUncertainty => write!(
+                f,
+                "York fitting requires an x or y uncertainty on every point"
+            ),
+            LineFitError::NotConverged => write!(f, "The fit did not converge"),
+        }
+    }
+}
+
+impl std::error::Error for LineFitError {}
+
+/// A fitted line together with the standard errors of its parameters
+/// and the goodness of fit
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LineFit {
+    pub line: Line,
+    /// None if it cannot be estimated, as from the residuals of two points
+    pub gradient_uncertainty: Option<f64>,
+    /// None if it cannot be estimated, as from the residuals of two points
+    pub y_intercept_uncertainty: Option<f64>,
+    /// Correlation coefficient between the gradient and the y-intercept
+    pub correlation: f64,
+    /// Coefficient of determination
+    pub r_squared: f64,
+    /// Standard deviation of the y residuals, None without degrees of freedom
+    pub residual_sd: Option<f64>,
+    pub degrees_of_freedom: usize,
+}
+
+impl LineFit {
+    /// Write a line, such as this one or a gradient line, to the places of the
+    /// uncertainties of this fit
+    #[must_use]
+    pub fn round_line(&self, line: &Line, notation: Notation) -> String {
+        // Unknown uncertainties are not usable, leaving six significant figures
+        line.rounded(
+            self.gradient_uncertainty.unwrap_or(0.0),
+            self.y_intercept_uncertainty.unwrap_or(0.0),
+            notation,
+        )
+    }
+
+    /// The gradient with its uncertainty
+    #[must_use]
+    pub fn gradient(&self, notation: Notation) -> UncertainValue {
+        UncertainValue::new(
+            self.line.gradient,
+            self.gradient_uncertainty.unwrap_or(0.0),
+            notation,
+        )
+    }
+
+    /// The y-intercept with its uncertainty
+    #[must_use]
+    pub fn y_intercept(&self, notation: Notation) -> UncertainValue {
+        UncertainValue::new(
+            self.line.y_intercept,
+            self.y_intercept_uncertainty.unwrap_or(0.0),
+            notation,
+        )
+    }
+}
@@ -17,6 +17,7 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
+use crate::data::{Notation, UncertainValue};
 use std::fmt;
 
 /// Struct representing a simple point
@@ -52,6 +53,35 @@ impl Line {
     pub fn y(&self, x: f64) -> f64 {
         self.gradient * x + self.y_intercept
     }
+
+    /// x value where y is zero, infinite or NaN if the line is horizontal
+    #[must_use]
+    pub fn x_intercept(&self) -> f64 {
+        -self.y_intercept / self.gradient
+    }
+
+    /// The line with the gradient and the y-intercept written to the places
+    /// of their uncertainties
+    #[must_use]
+    pub fn rounded(
+        &self,
+        gradient_uncertainty: f64,
+        y_intercept_uncertainty: f64,
+        notation: Notation,
+    ) -> String {
+        let gradient = UncertainValue::new(self.gradient, gradient_uncertainty, notation);
+        let y_intercept =
+            UncertainValue::new(self.y_intercept.abs(), y_intercept_uncertainty, notation);
+        // Omitted like in `Display` if it is shown as zero
+        let y_intercept = if y_intercept.rounded_value() == 0.0 {
+            String::new()
+        } else if self.y_intercept < 0.0 {
+            format!("-{}", y_intercept.value_term())
+        } else {
+            format!("+{}", y_intercept.value_term())
+        };
+        format!("y = {}x{}", gradient.value_term(), y_intercept)
+    }
 }
 
 impl fmt::Display for Line {
@@ -0,0 +1,125 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+/// Relative size of a diagonal element of R below which the matrix is
+/// considered rank-deficient
+const RANK_TOLERANCE: f64 = 1e-12;
+
+/// Solve the linear least-squares problem of minimizing |A x - b| by
+/// Householder QR decomposition, returning x.
+/// a: rows of A, each with the same number of columns
+/// b: right-hand side, one value per row of A
+/// None if there are fewer rows than columns or A is rank-deficient
+pub fn least_squares(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let rows = a.len();
+    let cols = a.first()?.len();
+    if rows < cols || b.len() != rows {
+        return None;
+    }
+    for k in 0..cols {
+        // Householder vector that zeroes a[k+1..][k]
+        let norm = (k..rows).map(|i| a[i][k].powi(2)).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return None;
+        }
+        let alpha = if a[k][k] > 0.0 { -norm } else { norm };
+        let mut v: Vec<f64> = (k..rows).map(|i| a[i][k]).collect();
+        v[0] -= alpha;
+        let v_norm2: f64 = v.iter().map(|vi| vi.powi(2)).sum();
+        if v_norm2 == 0.0 {
+            continue;
+        }
+        // Reflect the remaining columns and b
+        let factors: Vec<f64> = (k..cols)
+            .map(|j| (k..rows).map(|i| v[i - k] * a[i][j]).sum::<f64>() * 2.0 / v_norm2)
+            .collect();
+        for (row, vi) in a[k..].iter_mut().zip(v.iter()) {
+            for (element, factor) in row[k..].iter_mut().zip(factors.iter()) {
+                *element -= factor * vi;
+            }
+        }
+        let factor: f64 = (k..rows).map(|i| v[i - k] * b[i]).sum::<f64>() * 2.0 / v_norm2;
+        for (bi, vi) in b[k..].iter_mut().zip(v.iter()) {
+            *bi -= factor * vi;
+        }
+    }
+    // a[..cols] is now R, back-substitute
+    let max_diagonal = (0..cols).map(|k| a[k][k].abs()).fold(0.0, f64::max);
+    let mut x = vec![0.0; cols];
+    for k in (0..cols).rev() {
+        if a[k][k].abs() <= RANK_TOLERANCE * max_diagonal {
+            return None;
+        }
+        let sum: f64 = ((k + 1)..cols).map(|j| a[k][j] * x[j]).sum();
+        x[k] = (b[k] - sum) / a[k][k];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_solution(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-12, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn square_system() {
+        let a = vec![
+            vec![2.0, 1.0, -1.0],
+            vec![-3.0, -1.0, 2.0],
+            vec![-2.0, 1.0, 2.0],
+        ];
+        let b = vec![8.0, -11.0, -3.0];
+        assert_solution(&least_squares(a, b).unwrap(), &[2.0, 3.0, -1.0]);
+    }
+
+    #[test]
+    fn overdetermined_system() {
+        // Fitting y = c + m x to (0, 1), (1, 2), (2, 2), (3, 4)
+        let a = vec![
+            vec![1.0, 0.0],
+            vec![1.0, 1.0],
+            vec![1.0, 2.0],
+            vec![1.0, 3.0],
+        ];
+        let b = vec![1.0, 2.0, 2.0, 4.0];
+        assert_solution(&least_squares(a, b).unwrap(), &[0.9, 0.9]);
+    }
+
+    #[test]
+    fn rank_deficient() {
+        // The second column is twice the first
+        let a = vec![vec![1.0, 2.0], vec![2.0, 4.0], vec![3.0, 6.0]];
+        assert_eq!(least_squares(a, vec![1.0, 2.0, 3.0]), None);
+        let zero_column = vec![vec![1.0, 0.0], vec![2.0, 0.0]];
+        assert_eq!(least_squares(zero_column, vec![1.0, 2.0]), None);
+    }
+
+    #[test]
+    fn invalid_shapes() {
+        assert_eq!(least_squares(vec![vec![1.0, 2.0]], vec![3.0]), None);
+        assert_eq!(least_squares(vec![vec![1.0], vec![2.0]], vec![3.0]), None);
+        assert_eq!(least_squares(Vec::new(), Vec::new()), None);
+    }
+}
@@ -1,5 +1,37 @@
+mod expression;
+mod fit_results;
+mod line_fit;
 mod linear_data;
+mod matrix;
+mod nonlinear_fit;
+mod polynomial;
+mod repeats;
+mod table;
+mod tokenizer;
+mod transform;
 mod two_var_data;
+mod uncertain_value;
+mod uncertainty;
 
+pub use expression::{Expression, ExpressionError};
+pub use fit_results::FitResults;
+pub use line_fit::{
+    FitMethod, FitMethodFromStrError, FitModel, FitOptions, GradientLineMethod,
+    GradientLineMethodFromStrError, LineFit, LineFitError, ThroughAllError,
+};
 pub use linear_data::{Line, Point};
-pub use two_var_data::{ParseError, TwoVarDataPoint, TwoVarDataSet};
+pub use nonlinear_fit::{
+    parse_initial_guesses, NonlinearFit, NonlinearFitError, ParameterEstimate,
+};
+pub use polynomial::Polynomial;
+pub use repeats::{RepeatUncertainty, RepeatUncertaintyFromStrError, Repeats};
+pub use table::{
+    Cell, ColumnMapping, Delimiter, DelimiterFromStrError, Table, TableColumn, TableError,
+};
+pub use tokenizer::{DecimalSeparator, DecimalSeparatorFromStrError};
+pub use transform::{Transform, TransformError, TransformFromStrError, Transforms};
+pub use two_var_data::{
+    ParseError, ParseErrorKind, TwoVarDataPoint, TwoVarDataSet, EXCLUDED_MARKER,
+};
+pub use uncertain_value::{Notation, NotationFromStrError, UncertainValue};
+pub use uncertainty::{Uncertainty, UncertaintyFromStrError};
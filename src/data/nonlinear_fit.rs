@@ -0,0 +1,524 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::matrix::least_squares;
+use crate::data::tokenizer::tokenize;
+use crate::data::{
+    DecimalSeparator, Expression, ExpressionError, FitMethod, Notation, TwoVarDataSet,
+    UncertainValue,
+};
+use std::fmt;
+
+/// Maximum number of accepted Levenberg–Marquardt steps
+const MAX_ITERATIONS: usize = 200;
+/// Relative decrease of χ² below which the fit is considered converged
+const TOLERANCE: f64 = 1e-10;
+/// Damping factor above which no further improvement is possible
+const MAX_DAMPING: f64 = 1e12;
+
+/// Error while fitting an expression to a dataset
+#[derive(Clone, Debug)]
+pub enum NonlinearFitError {
+    Expression(ExpressionError),
+    /// Initial guesses not in the form of "name=value"
+    InvalidGuess(String),
+    /// Initial guess given for a name that is not in the expression
+    UnknownParameter(String),
+    /// Fewer points than parameters plus one
+    TooFewPoints,
+    NonPositiveUncertainty,
+    /// The model cannot be evaluated or the parameters are not determined
+    Singular,
+    NotConverged,
+}
+
+impl fmt::Display for NonlinearFitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NonlinearFitError::Expression(error) => write!(f, "Invalid expression: {}", error),
+            NonlinearFitError::InvalidGuess(guess) => {
+                write!(
+                    f,
+                    "Invalid initial guess \"{}\", expected name=value",
+                    guess
+                )
+            }
+            NonlinearFitError::UnknownParameter(name) => {
+                write!(f, "Initial guess for unknown parameter {}", name)
+            }
+            NonlinearFitError::TooFewPoints => {
+                write!(f, "More points than parameters are required")
+            }
+            NonlinearFitError::NonPositiveUncertainty => {
+                write!(f, "Weighted fitting requires positive uncertainties")
+            }
+            NonlinearFitError::Singular => write!(
+                f,
+                "The model cannot be evaluated or its parameters cannot be determined"
+            ),
+            NonlinearFitError::NotConverged => write!(f, "The fit did not converge"),
+        }
+    }
+}
+
+impl std::error::Error for NonlinearFitError {}
+
+impl From<ExpressionError> for NonlinearFitError {
+    fn from(error: ExpressionError) -> Self {
+        NonlinearFitError::Expression(error)
+    }
+}
+
+/// A fitted parameter of an expression
+#[derive(Clone, Debug)]
+pub struct ParameterEstimate {
+    pub name: String,
+    pub value: f64,
+    pub uncertainty: f64,
+}
+
+impl ParameterEstimate {
+    /// The value with its uncertainty
+    #[must_use]
+    pub fn uncertain_value(&self, notation: Notation) -> UncertainValue {
+        UncertainValue::new(self.value, self.uncertainty, notation)
+    }
+}
+
+/// An expression fitted to a dataset
+#[derive(Clone, Debug)]
+pub struct NonlinearFit {
+    pub expression: Expression,
+    /// In the order of `expression.parameters()`
+    pub parameters: Vec<ParameterEstimate>,
+    /// Coefficient of determination
+    pub r_squared: f64,
+    /// Standard deviation of the y residuals
+    pub residual_sd: f64,
+    pub degrees_of_freedom: usize,
+}
+
+impl NonlinearFit {
+    /// y value of the x
+    #[must_use]
+    pub fn y(&self, x: f64) -> f64 {
+        let values: Vec<f64> = self.parameters.iter().map(|p| p.value).collect();
+        self.expression.eval(x, &values)
+    }
+}
+
+/// Split initial guesses at `;`, and at `,` unless it is directly followed
+/// by a digit as the decimal comma of `a = 1,5`
+fn split_guesses(guesses: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = guesses.char_indices().peekable();
+    while let Some((idx, chr)) = chars.next() {
+        let decimal_comma =
+            chr == ',' && matches!(chars.peek(), Some((_, next)) if next.is_ascii_digit());
+        if chr == ';' || (chr == ',' && !decimal_comma) {
+            parts.push(&guesses[start..idx]);
+            start = idx + chr.len_utf8();
+        }
+    }
+    parts.push(&guesses[start..]);
+    parts
+}
+
+/// Parse a single number, with a decimal point or a decimal comma
+fn parse_guess_value(text: &str) -> Option<f64> {
+    let separator = if text.contains(',') {
+        DecimalSeparator::Comma
+    } else {
+        DecimalSeparator::Point
+    };
+    match tokenize(text, separator).ok()?.as_slice() {
+        [measurement]
+            if measurement.columns == (0..text.chars().count())
+                && measurement.uncertainty.is_none()
+                && !measurement.percentage =>
+        {
+            Some(measurement.value)
+        }
+        _ => None,
+    }
+}
+
+/// Parse initial guesses such as "a = 1, tau = 2,5" into values ordered as
+/// the parameters of the expression. Parameters without a guess start at 1.
+pub fn parse_initial_guesses(
+    expression: &Expression,
+    guesses: &str,
+) -> Result<Vec<f64>, NonlinearFitError> {
+    let mut values = vec![1.0; expression.parameters().len()];
+    for guess in split_guesses(guesses)
+        .into_iter()
+        .map(str::trim)
+        .filter(|guess| !guess.is_empty())
+    {
+        let invalid = || NonlinearFitError::InvalidGuess(guess.to_string());
+        let (name, value) = guess.split_once('=').ok_or_else(invalid)?;
+        let name = name.trim();
+        let value = parse_guess_value(value.trim()).ok_or_else(invalid)?;
+        let index = expression
+            .parameters()
+            .iter()
+            .position(|known| known == name)
+            .ok_or_else(|| NonlinearFitError::UnknownParameter(name.to_string()))?;
+        values[index] = value;
+    }
+    Ok(values)
+}
+
+/// Step for numerical differentiation around value
+fn step_size(value: f64) -> f64 {
+    f64::EPSILON.sqrt() * value.abs().max(1.0)
+}
+
+/// Weights of each point. York uses the effective variance σy² + (f'(x)σx)²
+fn weights(
+    data: &TwoVarDataSet,
+    expression: &Expression,
+    values: &[f64],
+    method: FitMethod,
+) -> Vec<f64> {
+    data.iter()
+        .map(|point| match method {
+            FitMethod::Ordinary => 1.0,
+            FitMethod::Weighted => point.y_uncertainty.powi(-2),
+            FitMethod::York => {
+                let h = step_size(point.x_value);
+                let slope = (expression.eval(point.x_value + h, values)
+                    - expression.eval(point.x_value - h, values))
+                    / (2.0 * h);
+                (point.y_uncertainty.powi(2) + (slope * point.x_uncertainty).powi(2)).recip()
+            }
+        })
+        .collect()
+}
+
+/// Weighted residuals and their Jacobian with respect to the parameters
+fn linearize(
+    data: &TwoVarDataSet,
+    expression: &Expression,
+    values: &[f64],
+    weights: &[f64],
+) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let mut residuals = Vec::with_capacity(data.len());
+    let mut jacobian = Vec::with_capacity(data.len());
+    for (point, weight) in data.iter().zip(weights.iter()) {
+        let scale = weight.sqrt();
+        residuals.push((point.y_value - expression.eval(point.x_value, values)) * scale);
+        let row = (0..values.len())
+            .map(|j| {
+                let h = step_size(values[j]);
+                let mut shifted = values.to_vec();
+                shifted[j] = values[j] + h;
+                let upper = expression.eval(point.x_value, &shifted);
+                shifted[j] = values[j] - h;
+                let lower = expression.eval(point.x_value, &shifted);
+                (upper - lower) / (2.0 * h) * scale
+            })
+            .collect();
+        jacobian.push(row);
+    }
+    (residuals, jacobian)
+}
+
+/// χ² of the parameter values under fixed weights
+fn chi_squared(
+    data: &TwoVarDataSet,
+    expression: &Expression,
+    values: &[f64],
+    weights: &[f64],
+) -> f64 {
+    data.iter()
+        .zip(weights.iter())
+        .map(|(point, weight)| {
+            (point.y_value - expression.eval(point.x_value, values)).powi(2) * weight
+        })
+        .sum()
+}
+
+/// Fit the expression by the Levenberg–Marquardt algorithm.
+/// Parameter uncertainties are taken from the covariance matrix, scaled by
+/// the residuals for ordinary least squares.
+pub fn levenberg_marquardt(
+    data: &TwoVarDataSet,
+    expression: &Expression,
+    initial: &[f64],
+    method: FitMethod,
+) -> Result<NonlinearFit, NonlinearFitError> {
+    let count = initial.len();
+    if data.len() <= count {
+        return Err(NonlinearFitError::TooFewPoints);
+    }
+    if method != FitMethod::Ordinary && data.iter().any(|point| point.y_uncertainty <= 0.0) {
+        return Err(NonlinearFitError::NonPositiveUncertainty);
+    }
+    let mut values = initial.to_vec();
+    let mut damping = 1e-3;
+    let mut converged = false;
+    for _ in 0..MAX_ITERATIONS {
+        let weights = weights(data, expression, &values, method);
+        let chi2 = chi_squared(data, expression, &values, &weights);
+        if !chi2.is_finite() {
+            return Err(NonlinearFitError::Singular);
+        }
+        let (residuals, jacobian) = linearize(data, expression, &values, &weights);
+        // Marquardt's scaling by the diagonal of JᵀJ
+        let scales: Vec<f64> = (0..count)
+            .map(|j| {
+                jacobian
+                    .iter()
+                    .map(|row| row[j].powi(2))
+                    .sum::<f64>()
+                    .sqrt()
+            })
+            .collect();
+        if scales
+            .iter()
+            .any(|scale| *scale == 0.0 || !scale.is_finite())
+        {
+            return Err(NonlinearFitError::Singular);
+        }
+        // Increase damping until the step reduces χ²
+        let mut improved = None;
+        while damping <= MAX_DAMPING {
+            // Minimize |J δ - r|² + λ|D δ|² as an augmented least-squares problem
+            let mut rows = jacobian.clone();
+            let mut rhs = residuals.clone();
+            for (j, scale) in scales.iter().enumerate() {
+                let mut row = vec![0.0; count];
+                row[j] = damping.sqrt() * scale;
+                rows.push(row);
+                rhs.push(0.0);
+            }
+            if let Some(step) = least_squares(rows, rhs) {
+                let trial: Vec<f64> = values.iter().zip(step.iter()).map(|(v, s)| v + s).collect();
+                let trial_chi2 = chi_squared(data, expression, &trial, &weights);
+                if trial_chi2.is_finite() && trial_chi2 <= chi2 {
+                    improved = Some((trial, trial_chi2));
+                    break;
+                }
+            }
+            damping *= 10.0;
+        }
+        match improved {
+            Some((trial, trial_chi2)) => {
+                values = trial;
+                damping = (damping / 10.0).max(1e-12);
+                if chi2 - trial_chi2 <= TOLERANCE * chi2 {
+                    converged = true;
+                    break;
+                }
+            }
+            // Already at the minimum within numerical precision
+            None => {
+                converged = true;
+                break;
+            }
+        }
+    }
+    if !converged {
+        return Err(NonlinearFitError::NotConverged);
+    }
+    let weights = weights(data, expression, &values, method);
+    let chi2 = chi_squared(data, expression, &values, &weights);
+    let (_, jacobian) = linearize(data, expression, &values, &weights);
+    // Covariance matrix (JᵀJ)⁻¹, one column at a time
+    let normal: Vec<Vec<f64>> = (0..count)
+        .map(|i| {
+            (0..count)
+                .map(|j| jacobian.iter().map(|row| row[i] * row[j]).sum())
+                .collect()
+        })
+        .collect();
+    let degrees_of_freedom = data.len() - count;
+    let variance_scale = match method {
+        FitMethod::Ordinary => chi2 / degrees_of_freedom as f64,
+        FitMethod::Weighted | FitMethod::York => 1.0,
+    };
+    let mut parameters = Vec::with_capacity(count);
+    for (k, name) in expression.parameters().iter().enumerate() {
+        let mut unit = vec![0.0; count];
+        unit[k] = 1.0;
+        let column = least_squares(normal.clone(), unit).ok_or(NonlinearFitError::Singular)?;
+        parameters.push(ParameterEstimate {
+            name: name.clone(),
+            value: values[k],
+            uncertainty: (column[k] * variance_scale).sqrt(),
+        });
+    }
+    // Unweighted goodness of fit, comparable with the straight lines
+    let mean_y = data.mean_y();
+    let ss_res: f64 = data
+        .iter()
+        .map(|point| (point.y_value - expression.eval(point.x_value, &values)).powi(2))
+        .sum();
+    let ss_tot: f64 = data
+        .iter()
+        .map(|point| (point.y_value - mean_y).powi(2))
+        .sum();
+    Ok(NonlinearFit {
+        expression: expression.clone(),
+        parameters,
+        r_squared: 1.0 - ss_res / ss_tot,
+        residual_sd: (ss_res / degrees_of_freedom as f64).sqrt(),
+        degrees_of_freedom,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TwoVarDataPoint;
+
+    /// Dataset of y values of the function at x = 0, 1, ..., with σy
+    fn sampled(function: impl Fn(f64) -> f64, count: usize, y_uncertainty: f64) -> TwoVarDataSet {
+        let mut data = TwoVarDataSet::default();
+        for idx in 0..count {
+            let x_value = idx as f64;
+            data.push(TwoVarDataPoint {
+                x_value,
+                y_value: function(x_value),
+                y_uncertainty,
+                ..TwoVarDataPoint::default()
+            });
+        }
+        data
+    }
+
+    fn expression(source: &str) -> Expression {
+        source.parse().unwrap()
+    }
+
+    #[test]
+    fn recovers_exponential_decay() {
+        let data = sampled(|x| 5.0 * (-x / 2.0).exp(), 10, 0.01);
+        let decay = expression("a*exp(-x/tau)");
+        for method in [FitMethod::Ordinary, FitMethod::Weighted, FitMethod::York] {
+            let fit = levenberg_marquardt(&data, &decay, &[1.0, 1.0], method).unwrap();
+            assert_eq!(fit.parameters[0].name, "a");
+            assert_eq!(fit.parameters[1].name, "tau");
+            assert!((fit.parameters[0].value - 5.0).abs() < 1e-6, "{:?}", fit);
+            assert!((fit.parameters[1].value - 2.0).abs() < 1e-6, "{:?}", fit);
+            assert_eq!(fit.degrees_of_freedom, 8);
+            assert!((fit.y(4.0) - 5.0 * (-2.0_f64).exp()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn weighted_uncertainties_from_covariance() {
+        // A straight line through exact points has the uncertainties of the
+        // weighted least-squares formulae
+        let data = sampled(|x| 2.0 * x + 1.0, 5, 0.5);
+        let line = expression("m*x + c");
+        let fit = levenberg_marquardt(&data, &line, &[1.0, 1.0], FitMethod::Weighted).unwrap();
+        // Σw = 20, Σwx = 40, Σwx² = 120, Δ = 800
+        assert!((fit.parameters[0].uncertainty - (20.0_f64 / 800.0).sqrt()).abs() < 1e-6);
+        assert!((fit.parameters[1].uncertainty - (120.0_f64 / 800.0).sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn too_few_points() {
+        let data = sampled(|x| x, 2, 0.1);
+        assert!(matches!(
+            levenberg_marquardt(
+                &data,
+                &expression("a*x + b"),
+                &[1.0, 1.0],
+                FitMethod::Ordinary
+            ),
+            Err(NonlinearFitError::TooFewPoints)
+        ));
+    }
+
+    #[test]
+    fn singular() {
+        let data = sampled(|x| x, 5, 0.1);
+        // The parameter does not change the model
+        assert!(matches!(
+            levenberg_marquardt(&data, &expression("x + 0*a"), &[1.0], FitMethod::Ordinary),
+            Err(NonlinearFitError::Singular)
+        ));
+        // The model cannot be evaluated at the points
+        assert!(matches!(
+            levenberg_marquardt(
+                &data,
+                &expression("a*ln(x - 10)"),
+                &[1.0],
+                FitMethod::Ordinary
+            ),
+            Err(NonlinearFitError::Singular)
+        ));
+    }
+
+    #[test]
+    fn non_positive_uncertainty() {
+        let data = sampled(|x| x, 5, 0.0);
+        let line = expression("a*x");
+        for method in [FitMethod::Weighted, FitMethod::York] {
+            assert!(matches!(
+                levenberg_marquardt(&data, &line, &[1.0], method),
+                Err(NonlinearFitError::NonPositiveUncertainty)
+            ));
+        }
+        assert!(levenberg_marquardt(&data, &line, &[1.0], FitMethod::Ordinary).is_ok());
+    }
+
+    #[test]
+    fn initial_guesses() {
+        let decay = expression("a*exp(-x/tau) + c");
+        assert_eq!(
+            parse_initial_guesses(&decay, "a = 5, tau = 2").unwrap(),
+            [5.0, 2.0, 1.0]
+        );
+        assert_eq!(
+            parse_initial_guesses(&decay, "tau=2.5;c=-1,a=3").unwrap(),
+            [3.0, 2.5, -1.0]
+        );
+        assert_eq!(
+            parse_initial_guesses(&decay, "a = 1,5; tau = \u{2212}2e3").unwrap(),
+            [1.5, -2e3, 1.0]
+        );
+        assert_eq!(parse_initial_guesses(&decay, " ").unwrap(), [1.0; 3]);
+    }
+
+    #[test]
+    fn invalid_initial_guesses() {
+        let decay = expression("a*exp(-x/tau)");
+        assert!(matches!(
+            parse_initial_guesses(&decay, "a = 5, tau"),
+            Err(NonlinearFitError::InvalidGuess(guess)) if guess == "tau"
+        ));
+        assert!(matches!(
+            parse_initial_guesses(&decay, "a = five"),
+            Err(NonlinearFitError::InvalidGuess(guess)) if guess == "a = five"
+        ));
+        assert!(matches!(
+            parse_initial_guesses(&decay, "a = 5 tau = 2"),
+            Err(NonlinearFitError::InvalidGuess(_))
+        ));
+        assert!(matches!(
+            parse_initial_guesses(&decay, "b = 5"),
+            Err(NonlinearFitError::UnknownParameter(name)) if name == "b"
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::{Notation, UncertainValue};
+use std::fmt;
+
+/// Struct representing a polynomial
+#[derive(Debug, Default, Clone)]
+pub struct Polynomial {
+    /// Coefficients from the constant term up
+    pub coefficients: Vec<f64>,
+}
+
+impl Polynomial {
+    /// Degree of the polynomial
+    #[must_use]
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// y value of the x
+    #[must_use]
+    pub fn y(&self, x: f64) -> f64 {
+        // Horner's method
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |result, coefficient| result * x + coefficient)
+    }
+
+    /// Gradient of the curve at the x
+    #[must_use]
+    pub fn gradient(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .enumerate()
+            .skip(1)
+            .rev()
+            .fold(0.0, |result, (power, coefficient)| {
+                result * x + power as f64 * coefficient
+            })
+    }
+
+    /// The equation with each coefficient to six significant figures, however
+    /// small it is
+    #[must_use]
+    pub fn equation(&self, notation: Notation) -> String {
+        let mut terms = String::new();
+        for (power, coefficient) in self.coefficients.iter().enumerate().rev() {
+            if *coefficient == 0.0 {
+                continue;
+            }
+            if *coefficient < 0.0 {
+                terms.push('-');
+            } else if !terms.is_empty() {
+                terms.push('+');
+            }
+            let magnitude =
+                UncertainValue::without_uncertainty(coefficient.abs(), notation).value_term();
+            match power {
+                0 => terms.push_str(&magnitude),
+                1 => terms.push_str(&format!("{}x", magnitude)),
+                _ => terms.push_str(&format!("{}x^{}", magnitude, power)),
+            }
+        }
+        if terms.is_empty() {
+            terms.push('0');
+        }
+        format!("y = {}", terms)
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.equation(Notation::Auto))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polynomial(coefficients: &[f64]) -> Polynomial {
+        Polynomial {
+            coefficients: coefficients.to_vec(),
+        }
+    }
+
+    #[test]
+    fn keeps_small_coefficients() {
+        assert_eq!(
+            polynomial(&[0.0, 3e-7, 4e-7]).to_string(),
+            "y = (4.00000 × 10⁻⁷)x^2+(3.00000 × 10⁻⁷)x"
+        );
+        assert_eq!(
+            polynomial(&[0.0, 3e-7, 4e-7]).equation(Notation::SiPrefix),
+            "y = (400.000 n)x^2+(300.000 n)x"
+        );
+    }
+
+    #[test]
+    fn signs_and_zeros() {
+        assert_eq!(
+            polynomial(&[-1.5, 0.0, 2.25]).to_string(),
+            "y = 2.25000x^2-1.50000"
+        );
+        assert_eq!(polynomial(&[0.0, 0.0]).to_string(), "y = 0");
+        assert_eq!(polynomial(&[]).to_string(), "y = 0");
+    }
+
+    #[test]
+    fn evaluates_by_horner() {
+        let cubic = polynomial(&[1.0, -2.0, 0.0, 3.0]);
+        assert_eq!(cubic.degree(), 3);
+        assert_eq!(cubic.y(2.0), 21.0);
+        assert_eq!(cubic.gradient(2.0), 34.0);
+        assert_eq!(polynomial(&[4.0]).gradient(2.0), 0.0);
+        assert_eq!(polynomial(&[]).y(5.0), 0.0);
+    }
+}
@@ -0,0 +1,195 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::str::FromStr;
+
+/// How the spread of repeated readings becomes their uncertainty
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum RepeatUncertainty {
+    /// Half of the difference between the largest and the smallest readings
+    #[default]
+    HalfRange,
+    /// Sample standard deviation divided by the square root of the count
+    StandardError,
+}
+
+impl RepeatUncertainty {
+    /// All methods, in the order shown to the user
+    pub const ALL: [RepeatUncertainty; 2] = [
+        RepeatUncertainty::HalfRange,
+        RepeatUncertainty::StandardError,
+    ];
+}
+
+impl std::fmt::Display for RepeatUncertainty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepeatUncertainty::HalfRange => write!(f, "half-range"),
+            RepeatUncertainty::StandardError => write!(f, "standard-error"),
+        }
+    }
+}
+
+/// Error when converting from str to `RepeatUncertainty`
+#[derive(Clone, Debug)]
+pub enum RepeatUncertaintyFromStrError {
+    UnknownMethod(String),
+}
+
+impl std::fmt::Display for RepeatUncertaintyFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepeatUncertaintyFromStrError::UnknownMethod(rstr) => {
+                write!(f, "Unknown repeat uncertainty method: {}", rstr)
+            }
+        }
+    }
+}
+
+impl FromStr for RepeatUncertainty {
+    type Err = RepeatUncertaintyFromStrError;
+
+    /// Parse repeat uncertainty method description
+    fn from_str(rstr: &str) -> Result<Self, Self::Err> {
+        match rstr.to_lowercase().as_str() {
+            "half-range" | "range" => Ok(RepeatUncertainty::HalfRange),
+            "standard-error" | "se" => Ok(RepeatUncertainty::StandardError),
+            other => Err(Self::Err::UnknownMethod(other.to_string())),
+        }
+    }
+}
+
+/// How repeated y readings such as `x [y1 y2 y3]` are combined into one point
+#[derive(PartialEq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Repeats {
+    pub uncertainty: RepeatUncertainty,
+    /// Instrument resolution, the smallest step of the readings. Its standard
+    /// uncertainty, resolution/√12 as for a rectangular distribution, is
+    /// combined in quadrature with the spread. Zero to use the spread alone
+    pub resolution: f64,
+}
+
+impl Repeats {
+    /// Mean of the readings and its uncertainty. A single reading has no
+    /// spread, leaving the uncertainty from the resolution
+    #[must_use]
+    pub fn combine(self, readings: &[f64]) -> (f64, f64) {
+        let count = readings.len() as f64;
+        let mean = readings.iter().sum::<f64>() / count;
+        let spread = match self.uncertainty {
+            _ if readings.len() < 2 => 0.0,
+            RepeatUncertainty::HalfRange => {
+                let max = readings.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let min = readings.iter().copied().fold(f64::INFINITY, f64::min);
+                (max - min) / 2.0
+            }
+            RepeatUncertainty::StandardError => {
+                let squares: f64 = readings.iter().map(|y| (y - mean).powi(2)).sum();
+                (squares / (count - 1.0) / count).sqrt()
+            }
+        };
+        (mean, spread.hypot(self.resolution / 12f64.sqrt()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{DecimalSeparator, ParseErrorKind, TwoVarDataSet, Uncertainty};
+
+    fn assert_combined(repeats: Repeats, readings: &[f64], expected: (f64, f64)) {
+        let (mean, uncertainty) = repeats.combine(readings);
+        assert!(
+            (mean - expected.0).abs() < 1e-12 && (uncertainty - expected.1).abs() < 1e-12,
+            "{:?} of {:?} gave {} ± {}, expected {} ± {}",
+            repeats.uncertainty,
+            readings,
+            mean,
+            uncertainty,
+            expected.0,
+            expected.1
+        );
+    }
+
+    fn repeats(uncertainty: RepeatUncertainty, resolution: f64) -> Repeats {
+        Repeats {
+            uncertainty,
+            resolution,
+        }
+    }
+
+    #[test]
+    fn half_range() {
+        let half_range = repeats(RepeatUncertainty::HalfRange, 0.0);
+        assert_combined(half_range, &[2.0, 2.4, 2.2], (2.2, 0.2));
+        assert_combined(half_range, &[5.0, 3.0, 3.0, 3.0], (3.5, 1.0));
+        assert_combined(half_range, &[1.5, 1.5], (1.5, 0.0));
+    }
+
+    #[test]
+    fn standard_error() {
+        let standard_error = repeats(RepeatUncertainty::StandardError, 0.0);
+        // Sample standard deviation 0.2, over the square root of 3
+        assert_combined(standard_error, &[2.0, 2.4, 2.2], (2.2, 0.2 / 3f64.sqrt()));
+        // Sample standard deviation 1, over 2
+        assert_combined(standard_error, &[5.0, 3.0, 3.0, 3.0], (3.5, 0.5));
+    }
+
+    #[test]
+    fn resolution_in_quadrature() {
+        assert_combined(
+            repeats(RepeatUncertainty::HalfRange, 0.4 * 12f64.sqrt()),
+            &[1.7, 2.3],
+            (2.0, 0.5),
+        );
+        assert_combined(
+            repeats(RepeatUncertainty::StandardError, 1.2 * 12f64.sqrt()),
+            &[5.0, 3.0, 3.0, 3.0],
+            (3.5, 1.3),
+        );
+        // Identical readings are as uncertain as the instrument
+        assert_combined(
+            repeats(RepeatUncertainty::StandardError, 0.1),
+            &[4.0, 4.0, 4.0],
+            (4.0, 0.1 / 12f64.sqrt()),
+        );
+    }
+
+    #[test]
+    fn single_reading() {
+        for uncertainty in RepeatUncertainty::ALL {
+            assert_combined(repeats(uncertainty, 0.0), &[3.0], (3.0, 0.0));
+            assert_combined(
+                repeats(uncertainty, 0.05),
+                &[3.0],
+                (3.0, 0.05 / 12f64.sqrt()),
+            );
+        }
+        // Datasets need at least two readings in the brackets
+        let error = TwoVarDataSet::from_string(
+            "1 [3]",
+            Uncertainty::default(),
+            Uncertainty::default(),
+            Repeats::default(),
+            DecimalSeparator::Point,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::TooFewReadings);
+    }
+}
@@ -0,0 +1,633 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::tokenizer::{tokenize, Measurement};
+use crate::data::{
+    DecimalSeparator, ParseError, ParseErrorKind, TwoVarDataPoint, TwoVarDataSet, Uncertainty,
+};
+use std::fmt::Write;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Character separating the cells of a table
+#[derive(PartialEq, Eq, Debug, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+    Semicolon,
+}
+
+impl Delimiter {
+    /// All delimiters, in the order shown to the user
+    pub const ALL: [Delimiter; 3] = [Delimiter::Comma, Delimiter::Tab, Delimiter::Semicolon];
+
+    /// The delimiter character
+    #[must_use]
+    pub fn as_char(self) -> char {
+        match self {
+            Delimiter::Comma => ',',
+            Delimiter::Tab => '\t',
+            Delimiter::Semicolon => ';',
+        }
+    }
+
+    /// Guess the delimiter from the first line of a table.
+    /// Semicolons win over commas as they are used with decimal commas
+    #[must_use]
+    pub fn detect(text: &str) -> Self {
+        let first_line = text
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or_default();
+        if first_line.contains('\t') {
+            Delimiter::Tab
+        } else if first_line.contains(';') {
+            Delimiter::Semicolon
+        } else {
+            Delimiter::Comma
+        }
+    }
+}
+
+impl std::fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Delimiter::Comma => write!(f, "comma"),
+            Delimiter::Tab => write!(f, "tab"),
+            Delimiter::Semicolon => write!(f, "semicolon"),
+        }
+    }
+}
+
+/// Error when converting from str to `Delimiter`
+#[derive(Clone, Debug)]
+pub enum DelimiterFromStrError {
+    UnknownDelimiter(String),
+}
+
+impl std::fmt::Display for DelimiterFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DelimiterFromStrError::UnknownDelimiter(dstr) => {
+                write!(f, "Unknown delimiter: {}", dstr)
+            }
+        }
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = DelimiterFromStrError;
+
+    /// Parse delimiter description
+    fn from_str(dstr: &str) -> Result<Self, Self::Err> {
+        match dstr.to_lowercase().as_str() {
+            "comma" | "," => Ok(Delimiter::Comma),
+            "tab" | "\t" => Ok(Delimiter::Tab),
+            "semicolon" | ";" => Ok(Delimiter::Semicolon),
+            other => Err(Self::Err::UnknownDelimiter(other.to_string())),
+        }
+    }
+}
+
+/// A column of a table, chosen by its header or its position
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum TableColumn {
+    /// Position, starting from 0
+    Index(usize),
+    Name(String),
+}
+
+impl std::fmt::Display for TableColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableColumn::Index(index) => write!(f, "{}", index + 1),
+            TableColumn::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<&str> for TableColumn {
+    /// Positive integers are positions starting from 1, anything else is a header
+    fn from(cstr: &str) -> Self {
+        match cstr.trim().parse::<usize>() {
+            Ok(position) if position > 0 => TableColumn::Index(position - 1),
+            _ => TableColumn::Name(cstr.trim().to_string()),
+        }
+    }
+}
+
+/// Columns holding the values and the uncertainties.
+/// Without an uncertainty column or where its cell is empty, uncertainties
+/// written in the value cells such as `9.81 ± 0.02` are used, or else the defaults
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ColumnMapping {
+    pub x: TableColumn,
+    pub x_uncertainty: Option<TableColumn>,
+    pub y: TableColumn,
+    pub y_uncertainty: Option<TableColumn>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            x: TableColumn::Index(0),
+            x_uncertainty: None,
+            y: TableColumn::Index(1),
+            y_uncertainty: None,
+        }
+    }
+}
+
+/// Error while reading values from a table
+#[derive(Clone, Debug)]
+pub enum TableError {
+    UnknownColumn(TableColumn),
+    Parse(ParseError),
+}
+
+impl std::fmt::Display for TableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableError::UnknownColumn(TableColumn::Index(index)) => {
+                write!(f, "There is no column {}", index + 1)
+            }
+            TableError::UnknownColumn(TableColumn::Name(name)) => {
+                write!(f, "There is no column named {}", name)
+            }
+            TableError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+impl From<ParseError> for TableError {
+    fn from(error: ParseError) -> Self {
+        TableError::Parse(error)
+    }
+}
+
+/// A cell of a table and where it is in the text
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Cell {
+    /// Without the quotes
+    pub text: String,
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Character columns in the line, starting from 0
+    pub columns: Range<usize>,
+}
+
+impl Cell {
+    /// Parse the cell as a single number, possibly with its uncertainty
+    fn measurement(&self, separator: DecimalSeparator) -> Result<Measurement, ParseError> {
+        let at_cell = |error: ParseError| ParseError {
+            line: self.line,
+            columns: self.columns.start + error.columns.start
+                ..self.columns.start + error.columns.end,
+            ..error
+        };
+        let mut measurements = tokenize(&self.text, separator).map_err(at_cell)?;
+        let is_whole =
+            measurements.len() == 1
+                && self.text.chars().enumerate().all(|(idx, chr)| {
+                    measurements[0].columns.contains(&idx) || chr.is_whitespace()
+                });
+        if !is_whole {
+            return Err(ParseError {
+                line: self.line,
+                columns: self.columns.clone(),
+                kind: ParseErrorKind::NotANumber,
+            });
+        }
+        let measurement = measurements.remove(0);
+        if !measurement.value.is_finite()
+            || matches!(measurement.uncertainty, Some(u) if !u.is_finite())
+        {
+            return Err(ParseError {
+                line: self.line,
+                columns: self.columns.clone(),
+                kind: ParseErrorKind::NonFinite,
+            });
+        }
+        Ok(measurement)
+    }
+
+    /// Parse the cell as a single non-negative number, where percentages
+    /// such as `2%` are relative to value
+    fn uncertainty(&self, value: f64, separator: DecimalSeparator) -> Result<f64, ParseError> {
+        let measurement = self.measurement(separator)?;
+        if measurement.value < 0.0 || measurement.uncertainty.is_some() {
+            return Err(ParseError {
+                line: self.line,
+                columns: self.columns.clone(),
+                kind: if measurement.value < 0.0 {
+                    ParseErrorKind::NegativeUncertainty
+                } else {
+                    ParseErrorKind::NotANumber
+                },
+            });
+        }
+        if measurement.percentage {
+            Ok(measurement.value / 100.0 * value.abs())
+        } else {
+            Ok(measurement.value)
+        }
+    }
+}
+
+/// A value and its uncertainty, if given
+type ValueWithUncertainty = (f64, Option<f64>);
+
+/// Split a line into cells, where quoted cells may contain the delimiter
+/// and `""` stands for a quote in them
+fn split_line(line: &str, number: usize, delimiter: char) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    let mut text = String::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut chars = line.chars().enumerate().peekable();
+    while let Some((idx, chr)) = chars.next() {
+        match chr {
+            '"' if quoted && chars.peek().map(|(_, next)| *next) == Some('"') => {
+                text.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            chr if chr == delimiter && !quoted => {
+                cells.push(Cell {
+                    text: std::mem::take(&mut text),
+                    line: number,
+                    columns: start..idx,
+                });
+                start = idx + 1;
+            }
+            chr => text.push(chr),
+        }
+    }
+    cells.push(Cell {
+        text,
+        line: number,
+        columns: start..line.chars().count(),
+    });
+    cells
+}
+
+/// A table read from CSV or TSV
+#[derive(PartialEq, Eq, Debug, Default, Clone)]
+pub struct Table {
+    /// Names of the columns, if the first row is a header
+    pub headers: Option<Vec<String>>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    /// Parse a table, guessing the delimiter if it is None.
+    /// The first row is a header if any of its cells is not a number
+    #[must_use]
+    pub fn parse(text: &str, delimiter: Option<Delimiter>, separator: DecimalSeparator) -> Self {
+        let delimiter = delimiter
+            .unwrap_or_else(|| Delimiter::detect(text))
+            .as_char();
+        let mut rows: Vec<Vec<Cell>> = text
+            .split('\n')
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| split_line(line.trim_end_matches('\r'), idx + 1, delimiter))
+            .collect();
+        let is_header = matches!(rows.first(), Some(first) if first.iter().any(|cell| {
+            !cell.text.trim().is_empty() && cell.measurement(separator).is_err()
+        }));
+        let headers = if is_header {
+            Some(
+                rows.remove(0)
+                    .into_iter()
+                    .map(|cell| cell.text.trim().to_string())
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        Self { headers, rows }
+    }
+
+    /// Number of columns of the widest row
+    #[must_use]
+    pub fn column_count(&self) -> usize {
+        self.rows
+            .iter()
+            .map(Vec::len)
+            .chain(self.headers.iter().map(Vec::len))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Header of the column at index, if there is one
+    #[must_use]
+    pub fn header(&self, index: usize) -> Option<&str> {
+        self.headers
+            .as_ref()
+            .and_then(|headers| headers.get(index))
+            .map(String::as_str)
+            .filter(|header| !header.is_empty())
+    }
+
+    /// Name of the column at index shown to the user
+    #[must_use]
+    pub fn column_title(&self, index: usize) -> String {
+        match self.header(index) {
+            Some(header) => header.to_string(),
+            None => format!("Column {}", index + 1),
+        }
+    }
+
+    /// Find the position of a column
+    pub fn column_index(&self, column: &TableColumn) -> Result<usize, TableError> {
+        let found = match column {
+            TableColumn::Index(index) => Some(*index).filter(|index| *index < self.column_count()),
+            TableColumn::Name(name) => self
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.iter().position(|header| header == name)),
+        };
+        found.ok_or_else(|| TableError::UnknownColumn(column.clone()))
+    }
+
+    /// Values and uncertainties of the mapped columns in each row.
+    /// Rows where all of these cells are empty are skipped
+    fn values(
+        &self,
+        mapping: &ColumnMapping,
+        separator: DecimalSeparator,
+    ) -> Result<Vec<[ValueWithUncertainty; 2]>, TableError> {
+        let x = self.column_index(&mapping.x)?;
+        let y = self.column_index(&mapping.y)?;
+        let x_uncertainty = mapping
+            .x_uncertainty
+            .as_ref()
+            .map(|column| self.column_index(column))
+            .transpose()?;
+        let y_uncertainty = mapping
+            .y_uncertainty
+            .as_ref()
+            .map(|column| self.column_index(column))
+            .transpose()?;
+        let mut result = Vec::with_capacity(self.rows.len());
+        for row in &self.rows {
+            // Missing cells at the end of a row are empty
+            let empty = || Cell {
+                text: String::new(),
+                line: row.first().map_or(0, |cell| cell.line),
+                columns: row.last().map_or(0, |cell| cell.columns.end)
+                    ..row.last().map_or(0, |cell| cell.columns.end),
+            };
+            let cell = |index: usize| row.get(index).cloned().unwrap_or_else(empty);
+            let used = [Some(x), x_uncertainty, Some(y), y_uncertainty];
+            if used
+                .iter()
+                .flatten()
+                .all(|index| cell(*index).text.trim().is_empty())
+            {
+                continue;
+            }
+            let value = |index: usize, uncertainty: Option<usize>| {
+                let measurement = cell(index).measurement(separator)?;
+                // Empty uncertainty cells fall back as if there were no such column
+                let uncertainty = match uncertainty.map(cell) {
+                    Some(cell) if !cell.text.trim().is_empty() => {
+                        Some(cell.uncertainty(measurement.value, separator)?)
+                    }
+                    _ => measurement.uncertainty,
+                };
+                Ok::<_, ParseError>((measurement.value, uncertainty))
+            };
+            result.push([value(x, x_uncertainty)?, value(y, y_uncertainty)?]);
+        }
+        Ok(result)
+    }
+
+    /// Read the mapped columns as a dataset
+    /// dux: Default x uncertainty, possibly relative to each value
+    /// duy: Default y uncertainty, possibly relative to each value
+    pub fn to_dataset(
+        &self,
+        mapping: &ColumnMapping,
+        dux: Uncertainty,
+        duy: Uncertainty,
+        separator: DecimalSeparator,
+    ) -> Result<TwoVarDataSet, TableError> {
+        let mut dataset = TwoVarDataSet::default();
+        for [(x_value, x_uncertainty), (y_value, y_uncertainty)] in
+            self.values(mapping, separator)?
+        {
+            dataset.push(TwoVarDataPoint {
+                x_value,
+                x_uncertainty: x_uncertainty.unwrap_or_else(|| dux.of(x_value)),
+                y_value,
+                y_uncertainty: y_uncertainty.unwrap_or_else(|| duy.of(y_value)),
+                excluded: false,
+            });
+        }
+        Ok(dataset)
+    }
+
+    /// Write the mapped columns as dataset text with decimal points, where
+    /// uncertainties follow their values so that missing ones take the defaults
+    pub fn to_dataset_text(
+        &self,
+        mapping: &ColumnMapping,
+        separator: DecimalSeparator,
+    ) -> Result<String, TableError> {
+        let mut text = String::new();
+        for values in self.values(mapping, separator)? {
+            let fields: Vec<String> = values
+                .iter()
+                .map(|(value, uncertainty)| match uncertainty {
+                    Some(uncertainty) => format!("{} ± {}", value, uncertainty),
+                    None => value.to_string(),
+                })
+                .collect();
+            // Cannot fail
+            let _ = writeln!(text, "{}", fields.join(", "));
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(cells: &[Cell]) -> Vec<&str> {
+        cells.iter().map(|cell| cell.text.as_str()).collect()
+    }
+
+    fn mapping(x: &str, x_uncertainty: &str, y: &str, y_uncertainty: &str) -> ColumnMapping {
+        let optional =
+            |column: &str| Some(TableColumn::from(column)).filter(|_| !column.is_empty());
+        ColumnMapping {
+            x: TableColumn::from(x),
+            x_uncertainty: optional(x_uncertainty),
+            y: TableColumn::from(y),
+            y_uncertainty: optional(y_uncertainty),
+        }
+    }
+
+    fn parse_error(result: Result<String, TableError>) -> ParseError {
+        match result {
+            Err(TableError::Parse(error)) => error,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn quoted_cells() {
+        let cells = split_line(r#"a,"b, c","say ""hi""",,"""#, 3, ',');
+        assert_eq!(texts(&cells), ["a", "b, c", "say \"hi\"", "", ""]);
+        assert!(cells.iter().all(|cell| cell.line == 3));
+        // Columns cover the quotes
+        assert_eq!(cells[1].columns, 2..8);
+        assert_eq!(cells[2].columns, 9..21);
+        assert_eq!(cells[4].columns, 23..25);
+        // Other delimiters are kept inside cells
+        assert_eq!(texts(&split_line("1,5;2\t3", 1, ';')), ["1,5", "2\t3"]);
+    }
+
+    #[test]
+    fn delimiter_detection() {
+        assert_eq!(Delimiter::detect("t\tv, m\n1\t2"), Delimiter::Tab);
+        assert_eq!(Delimiter::detect("1,5;2,5\n"), Delimiter::Semicolon);
+        assert_eq!(Delimiter::detect("1,2\n3;4"), Delimiter::Comma);
+        assert_eq!(Delimiter::detect("\n  \n1;2"), Delimiter::Semicolon);
+        assert_eq!(Delimiter::detect(""), Delimiter::Comma);
+        let table = Table::parse("1,5;2,5\n3;4", None, DecimalSeparator::Comma);
+        assert_eq!(texts(&table.rows[0]), ["1,5", "2,5"]);
+    }
+
+    #[test]
+    fn header_detection() {
+        let table = Table::parse("t,\"v (m/s)\",\n1,2,3", None, DecimalSeparator::Point);
+        assert_eq!(
+            table.headers,
+            Some(vec![
+                String::from("t"),
+                String::from("v (m/s)"),
+                String::new()
+            ])
+        );
+        assert_eq!(table.rows.len(), 1);
+        assert_eq!(table.column_title(1), "v (m/s)");
+        assert_eq!(table.column_title(2), "Column 3");
+        // Numbers, uncertainties and empty cells are data
+        for text in ["1,2\n3,4", "1 ± 0.1,,2%\n3,4", "\r\n1,2\r\n"] {
+            let table = Table::parse(text, Some(Delimiter::Comma), DecimalSeparator::Point);
+            assert_eq!(table.headers, None, "{:?} has no header", text);
+        }
+        // Blank lines are skipped, keeping the line numbers
+        let table = Table::parse("1,2\n\n3,4", None, DecimalSeparator::Point);
+        assert_eq!(table.rows[1][0].line, 3);
+        assert_eq!(table.column_count(), 2);
+    }
+
+    #[test]
+    fn column_mapping() {
+        assert_eq!(TableColumn::from("2"), TableColumn::Index(1));
+        assert_eq!(
+            TableColumn::from(" v "),
+            TableColumn::Name(String::from("v"))
+        );
+        assert_eq!(TableColumn::from("0"), TableColumn::Name(String::from("0")));
+        let table = Table::parse("t,v\n1,2", None, DecimalSeparator::Point);
+        assert_eq!(table.column_index(&TableColumn::from("v")).unwrap(), 1);
+        for (column, message) in [
+            ("3", "There is no column 3"),
+            ("m", "There is no column named m"),
+        ] {
+            let error = table.column_index(&TableColumn::from(column)).unwrap_err();
+            assert_eq!(error.to_string(), message);
+            assert!(table
+                .to_dataset_text(&mapping(column, "", "v", ""), DecimalSeparator::Point)
+                .is_err());
+        }
+        // Columns are found by position without a header
+        let table = Table::parse("1,2", None, DecimalSeparator::Point);
+        assert!(table.column_index(&TableColumn::from("t")).is_err());
+        assert_eq!(
+            table
+                .to_dataset_text(&mapping("2", "", "1", ""), DecimalSeparator::Point)
+                .unwrap(),
+            "2, 1\n"
+        );
+    }
+
+    #[test]
+    fn dataset_text() {
+        let table = Table::parse(
+            "t;dt;v;dv\n1,5;0,1;2 ± 0,2;\n2,5;;4,5;10%\n;;;\n3;0,1;6;0,3",
+            None,
+            DecimalSeparator::Comma,
+        );
+        let text = table
+            .to_dataset_text(&mapping("t", "dt", "v", "dv"), DecimalSeparator::Comma)
+            .unwrap();
+        // Empty uncertainty cells fall back to the value cells, then the defaults
+        assert_eq!(
+            text,
+            "1.5 ± 0.1, 2 ± 0.2\n2.5, 4.5 ± 0.45\n3 ± 0.1, 6 ± 0.3\n"
+        );
+        let dataset = table
+            .to_dataset(
+                &mapping("t", "dt", "v", "dv"),
+                Uncertainty::from(0.05),
+                Uncertainty {
+                    percentage: 1.0,
+                    absolute: 0.0,
+                },
+                DecimalSeparator::Comma,
+            )
+            .unwrap();
+        let point = &dataset[1];
+        assert_eq!(point.x_uncertainty, 0.05);
+        assert!((point.y_uncertainty - 0.45).abs() < 1e-12);
+        assert_eq!(dataset.len(), 3);
+    }
+
+    #[test]
+    fn cell_errors_are_located() {
+        let table = Table::parse("t,v,dv\n1,2,x\n2,3 4,0.1", None, DecimalSeparator::Point);
+        let error = parse_error(
+            table.to_dataset_text(&mapping("t", "", "v", "dv"), DecimalSeparator::Point),
+        );
+        assert_eq!(
+            (error.line, error.columns, error.kind),
+            (2, 4..5, ParseErrorKind::NotANumber)
+        );
+        let error =
+            parse_error(table.to_dataset_text(&mapping("t", "", "v", ""), DecimalSeparator::Point));
+        assert_eq!(
+            (error.line, error.columns, error.kind),
+            (3, 2..5, ParseErrorKind::NotANumber)
+        );
+        let table = Table::parse("1,2,-0.1", None, DecimalSeparator::Point);
+        let error = parse_error(
+            table.to_dataset_text(&mapping("1", "", "2", "3"), DecimalSeparator::Point),
+        );
+        assert_eq!(error.kind, ParseErrorKind::NegativeUncertainty);
+    }
+}
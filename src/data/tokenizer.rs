@@ -0,0 +1,608 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::{ParseError, ParseErrorKind};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Unicode minus sign, as produced by some word processors and spreadsheets
+const UNICODE_MINUS: char = '\u{2212}';
+
+/// Character separating the integer and the fractional parts of numbers
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum DecimalSeparator {
+    /// `1.23`, commas separate fields
+    #[default]
+    Point,
+    /// `1,23`, points separate fields
+    Comma,
+}
+
+impl DecimalSeparator {
+    /// All separators, in the order shown to the user
+    pub const ALL: [DecimalSeparator; 2] = [DecimalSeparator::Point, DecimalSeparator::Comma];
+
+    /// The separator character
+    #[must_use]
+    pub fn as_char(self) -> char {
+        match self {
+            DecimalSeparator::Point => '.',
+            DecimalSeparator::Comma => ',',
+        }
+    }
+}
+
+impl std::fmt::Display for DecimalSeparator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecimalSeparator::Point => write!(f, "point"),
+            DecimalSeparator::Comma => write!(f, "comma"),
+        }
+    }
+}
+
+/// Error when converting from str to `DecimalSeparator`
+#[derive(Clone, Debug)]
+pub enum DecimalSeparatorFromStrError {
+    UnknownSeparator(String),
+}
+
+impl std::fmt::Display for DecimalSeparatorFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecimalSeparatorFromStrError::UnknownSeparator(sstr) => {
+                write!(f, "Unknown decimal separator: {}", sstr)
+            }
+        }
+    }
+}
+
+impl FromStr for DecimalSeparator {
+    type Err = DecimalSeparatorFromStrError;
+
+    /// Parse decimal separator description
+    fn from_str(sstr: &str) -> Result<Self, Self::Err> {
+        match sstr.to_lowercase().as_str() {
+            "point" | "." => Ok(DecimalSeparator::Point),
+            "comma" | "," => Ok(DecimalSeparator::Comma),
+            other => Err(Self::Err::UnknownSeparator(other.to_string())),
+        }
+    }
+}
+
+/// Whether the character is a sign
+fn is_sign(chr: char) -> bool {
+    chr == '+' || is_minus(chr)
+}
+
+/// Whether the character is a minus sign
+fn is_minus(chr: char) -> bool {
+    chr == '-' || chr == UNICODE_MINUS
+}
+
+/// Number of consecutive ASCII digits starting at `from`
+fn count_digits(chars: &[char], from: usize) -> usize {
+    chars
+        .iter()
+        .skip(from)
+        .take_while(|chr| chr.is_ascii_digit())
+        .count()
+}
+
+/// Number of consecutive whitespace characters starting at `from`
+fn count_whitespace(chars: &[char], from: usize) -> usize {
+    chars
+        .iter()
+        .skip(from)
+        .take_while(|chr| chr.is_whitespace())
+        .count()
+}
+
+/// Length of the exponent such as `e-3` starting at `from`, 0 if there is none.
+/// The exponent is only taken if it has digits, so `2e` is followed by text
+fn exponent_length(chars: &[char], from: usize) -> usize {
+    if !matches!(chars.get(from), Some('e' | 'E')) {
+        return 0;
+    }
+    let mut idx = from + 1;
+    if matches!(chars.get(idx), Some(&chr) if is_sign(chr)) {
+        idx += 1;
+    }
+    let digits = count_digits(chars, idx);
+    if digits == 0 {
+        0
+    } else {
+        idx + digits - from
+    }
+}
+
+/// Length of the number starting at `from`, 0 if there is none.
+/// A number is an optional sign, digits with at most one decimal separator
+/// and an optional exponent
+fn number_length(chars: &[char], from: usize, separator: char) -> usize {
+    let mut idx = from;
+    if matches!(chars.get(idx), Some(&chr) if is_sign(chr)) {
+        idx += 1;
+    }
+    let integer_digits = count_digits(chars, idx);
+    idx += integer_digits;
+    let mut fraction_digits = 0;
+    if chars.get(idx) == Some(&separator) {
+        fraction_digits = count_digits(chars, idx + 1);
+        // A trailing separator such as `1.` belongs to the number, a lone one does not
+        if integer_digits + fraction_digits > 0 {
+            idx += 1 + fraction_digits;
+        }
+    }
+    if integer_digits + fraction_digits == 0 {
+        return 0;
+    }
+    idx += exponent_length(chars, idx);
+    idx - from
+}
+
+/// Normalise a number to what `f64::from_str` understands
+fn normalise(chars: &[char], separator: char) -> String {
+    chars
+        .iter()
+        .map(|chr| match *chr {
+            UNICODE_MINUS => '-',
+            chr if chr == separator => '.',
+            chr => chr,
+        })
+        .collect()
+}
+
+/// Columns of the `±`, `+/-` or `+-` after `from` and any whitespace
+fn plus_minus_columns(chars: &[char], from: usize) -> Option<Range<usize>> {
+    let start = from + count_whitespace(chars, from);
+    let length = match chars.get(start..).unwrap_or_default() {
+        ['\u{b1}', ..] => 1,
+        ['+', '/', minus, ..] if is_minus(*minus) => 3,
+        ['+', minus, ..] if is_minus(*minus) => 2,
+        _ => return None,
+    };
+    Some(start..start + length)
+}
+
+/// Length of the `%` after `from` and any whitespace, or 0 if there is none
+fn percent_length(chars: &[char], from: usize) -> usize {
+    let whitespace = count_whitespace(chars, from);
+    if chars.get(from + whitespace) == Some(&'%') {
+        whitespace + 1
+    } else {
+        0
+    }
+}
+
+/// Value and uncertainty written concisely such as `9.81(2)`, `1.234(5)e-3`
+/// or `9.81(0.02)`, where `mantissa` is the normalised number before the
+/// parenthesis. Returns them with the end of the text
+fn concise(
+    chars: &[char],
+    from: usize,
+    mantissa: &str,
+    separator: char,
+) -> Option<(f64, f64, usize)> {
+    if chars.get(from) != Some(&'(') {
+        return None;
+    }
+    let inner_start = from + 1;
+    let inner_length = number_length(chars, inner_start, separator);
+    let close = inner_start + inner_length;
+    let inner = &chars[inner_start..close];
+    if inner_length == 0
+        || chars.get(close) != Some(&')')
+        || is_sign(inner[0])
+        || inner.iter().any(|chr| *chr == 'e' || *chr == 'E')
+    {
+        return None;
+    }
+    // An exponent written after the parenthesis applies to both
+    let end = close + 1 + exponent_length(chars, close + 1);
+    let exponent_after: i32 = if end > close + 1 {
+        normalise(&chars[close + 2..end], separator).parse().ok()?
+    } else {
+        0
+    };
+    let (digits, exponent_before) = match mantissa.split_once(['e', 'E']) {
+        Some((digits, exponent)) => (digits, exponent.parse::<i32>().ok()?),
+        None => (mantissa, 0),
+    };
+    let exponent = exponent_before.checked_add(exponent_after)?;
+    let value = format!("{}e{}", digits, exponent).parse().ok()?;
+    let inner = normalise(inner, separator);
+    let uncertainty = if inner.contains('.') {
+        // In the same unit as the value
+        format!("{}e{}", inner, exponent)
+    } else {
+        // In units of the last digit of the value
+        let decimals = digits
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len());
+        format!("{}e{}", inner, exponent.checked_sub(decimals as i32)?)
+    };
+    Some((value, uncertainty.parse().ok()?, end))
+}
+
+/// A number, possibly with its uncertainty written right after it
+#[derive(PartialEq, Debug, Clone)]
+pub struct Measurement {
+    pub value: f64,
+    /// Written as `value ± uncertainty`, `value +/- uncertainty` or `value(uncertainty)`.
+    /// Percentages such as `value ± 2%` are converted to absolute uncertainties
+    pub uncertainty: Option<f64>,
+    /// Whether the value is followed by `%` without an uncertainty, such as
+    /// an uncertainty written in its own column as a percentage
+    pub percentage: bool,
+    /// Character columns of the value and its uncertainty
+    pub columns: Range<usize>,
+}
+
+/// Find all numbers in the line along with their uncertainties, if written
+/// explicitly. Everything else separates numbers. Errors are reported as on line 1
+pub fn tokenize(line: &str, separator: DecimalSeparator) -> Result<Vec<Measurement>, ParseError> {
+    let error = |columns, kind| ParseError {
+        line: 1,
+        columns,
+        kind,
+    };
+    let separator = separator.as_char();
+    let chars: Vec<char> = line.chars().collect();
+    let mut measurements = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let length = number_length(&chars, idx, separator);
+        if length == 0 {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        idx += length;
+        let mantissa = normalise(&chars[start..idx], separator);
+        // Cannot fail as the text has been checked above
+        let mut value = mantissa.parse().unwrap_or(f64::NAN);
+        let mut uncertainty = None;
+        let mut percentage = false;
+        if let Some((concise_value, concise_uncertainty, end)) =
+            concise(&chars, idx, &mantissa, separator)
+        {
+            value = concise_value;
+            uncertainty = Some(concise_uncertainty);
+            idx = end;
+        } else if let Some(plus_minus) = plus_minus_columns(&chars, idx) {
+            let number_start = plus_minus.end + count_whitespace(&chars, plus_minus.end);
+            let number_length = number_length(&chars, number_start, separator);
+            if number_length == 0 {
+                return Err(error(plus_minus, ParseErrorKind::MissingUncertainty));
+            }
+            idx = number_start + number_length;
+            let number = normalise(&chars[number_start..idx], separator)
+                .parse()
+                .unwrap_or(f64::NAN);
+            if number < 0.0 {
+                return Err(error(
+                    number_start..idx,
+                    ParseErrorKind::NegativeUncertainty,
+                ));
+            }
+            // Relative to the value if followed by `%`
+            let percent_length = percent_length(&chars, idx);
+            idx += percent_length;
+            uncertainty = Some(if percent_length == 0 {
+                number
+            } else {
+                number / 100.0 * value.abs()
+            });
+        } else {
+            let percent_length = percent_length(&chars, idx);
+            idx += percent_length;
+            percentage = percent_length != 0;
+        }
+        measurements.push(Measurement {
+            value,
+            uncertainty,
+            percentage,
+            columns: start..idx,
+        });
+    }
+    Ok(measurements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Numbers in the line and their columns, none of which has an uncertainty
+    fn numbers(line: &str, separator: DecimalSeparator) -> Vec<(f64, Range<usize>)> {
+        tokenize(line, separator)
+            .unwrap()
+            .into_iter()
+            .map(|measurement| {
+                assert_eq!(measurement.uncertainty, None, "{:?}", line);
+                (measurement.value, measurement.columns)
+            })
+            .collect()
+    }
+
+    /// Check that `text` is exactly one number which `f64::from_str` agrees with
+    fn assert_single(text: &str, separator: DecimalSeparator, expected: &str) {
+        let numbers = numbers(text, separator);
+        assert_eq!(numbers.len(), 1, "{:?} gave {:?}", text, numbers);
+        let (value, columns) = &numbers[0];
+        assert_eq!(*value, expected.parse::<f64>().unwrap(), "{:?}", text);
+        assert_eq!(*columns, 0..text.chars().count(), "{:?}", text);
+    }
+
+    #[test]
+    fn plain_forms_match_std() {
+        for text in [
+            "0",
+            "7",
+            "42",
+            "-3",
+            "+3",
+            "1.5",
+            "-1.5",
+            "+1.5",
+            ".5",
+            "-.5",
+            "+.5",
+            "5.",
+            "-5.",
+            "007",
+            "0.000123",
+            "123456789012345678901234567890",
+        ] {
+            assert_single(text, DecimalSeparator::Point, text);
+        }
+    }
+
+    #[test]
+    fn scientific_notation_matches_std() {
+        for text in [
+            "1e3",
+            "1E3",
+            "1.2e-3",
+            "1.2E+3",
+            "-1.2e-3",
+            "+1.2e3",
+            ".5e2",
+            "5.e-1",
+            "6.02214076e23",
+            "1e-320",
+        ] {
+            assert_single(text, DecimalSeparator::Point, text);
+        }
+    }
+
+    #[test]
+    fn unicode_minus() {
+        assert_single("\u{2212}3", DecimalSeparator::Point, "-3");
+        assert_single("\u{2212}1.5e\u{2212}2", DecimalSeparator::Point, "-1.5e-2");
+        assert_single("\u{2212}.25", DecimalSeparator::Point, "-.25");
+    }
+
+    #[test]
+    fn decimal_comma() {
+        assert_single("1,23", DecimalSeparator::Comma, "1.23");
+        assert_single("\u{2212}0,5e3", DecimalSeparator::Comma, "-0.5e3");
+        assert_single(",75", DecimalSeparator::Comma, ".75");
+        let numbers = numbers("1,5;2,25\t-3,0", DecimalSeparator::Comma);
+        let values: Vec<f64> = numbers.iter().map(|(value, _)| *value).collect();
+        assert_eq!(values, [1.5, 2.25, -3.0]);
+    }
+
+    #[test]
+    fn commas_separate_fields_with_decimal_point() {
+        let numbers = numbers("1,23", DecimalSeparator::Point);
+        assert_eq!(numbers, [(1.0, 0..1), (23.0, 2..4)]);
+    }
+
+    #[test]
+    fn points_separate_fields_with_decimal_comma() {
+        let numbers = numbers("1.5", DecimalSeparator::Comma);
+        assert_eq!(numbers, [(1.0, 0..1), (5.0, 2..3)]);
+    }
+
+    #[test]
+    fn exponent_is_not_split() {
+        let numbers = numbers("1.2e-3 4", DecimalSeparator::Point);
+        assert_eq!(numbers, [(1.2e-3, 0..6), (4.0, 7..8)]);
+    }
+
+    #[test]
+    fn incomplete_exponent_is_text() {
+        assert_eq!(
+            numbers("2e 5", DecimalSeparator::Point),
+            [(2.0, 0..1), (5.0, 3..4)]
+        );
+        assert_eq!(numbers("2e-x", DecimalSeparator::Point), [(2.0, 0..1)]);
+    }
+
+    #[test]
+    fn separators_and_text_between_fields() {
+        let numbers = numbers("  x=1.5,\ty=-2e1 ; 3 ", DecimalSeparator::Point);
+        let values: Vec<f64> = numbers.iter().map(|(value, _)| *value).collect();
+        assert_eq!(values, [1.5, -20.0, 3.0]);
+    }
+
+    #[test]
+    fn lone_signs_and_points_are_not_numbers() {
+        assert!(numbers("- + . \u{2212} e", DecimalSeparator::Point).is_empty());
+        assert!(numbers("", DecimalSeparator::Point).is_empty());
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let numbers = numbers("é \u{2212}1 2", DecimalSeparator::Point);
+        assert_eq!(numbers, [(-1.0, 2..4), (2.0, 5..6)]);
+    }
+
+    #[test]
+    fn overflow_is_infinite() {
+        let numbers = numbers("1e400", DecimalSeparator::Point);
+        assert_eq!(numbers.len(), 1);
+        assert!(numbers[0].0.is_infinite());
+    }
+
+    #[test]
+    fn round_trips_formatted_values() {
+        for value in [
+            0.1,
+            -2.5e-7,
+            1.0 / 3.0,
+            6.62607015e-34,
+            -1.7976931348623157e308,
+        ] {
+            for text in [format!("{}", value), format!("{:e}", value)] {
+                assert_single(&text, DecimalSeparator::Point, &text);
+            }
+        }
+    }
+
+    /// Value and uncertainty of the only measurement in the line
+    fn single_with_uncertainty(line: &str, separator: DecimalSeparator) -> (f64, f64) {
+        let measurements = tokenize(line, separator).unwrap();
+        assert_eq!(measurements.len(), 1, "{:?} gave {:?}", line, measurements);
+        let measurement = &measurements[0];
+        assert_eq!(measurement.columns, 0..line.chars().count(), "{:?}", line);
+        (measurement.value, measurement.uncertainty.unwrap())
+    }
+
+    #[test]
+    fn plus_minus_forms() {
+        for line in [
+            "9.81 \u{b1} 0.02",
+            "9.81\u{b1}0.02",
+            "9.81 +/- 0.02",
+            "9.81+/-0.02",
+            "9.81 +- 0.02",
+            "9.81 +/\u{2212} 0.02",
+        ] {
+            assert_eq!(
+                single_with_uncertainty(line, DecimalSeparator::Point),
+                (9.81, 0.02),
+                "{:?}",
+                line
+            );
+        }
+        assert_eq!(
+            single_with_uncertainty("9,81 \u{b1} 0,02", DecimalSeparator::Comma),
+            (9.81, 0.02)
+        );
+    }
+
+    #[test]
+    fn concise_forms() {
+        let cases = [
+            ("9.81(2)", 9.81, 0.02),
+            ("12(3)", 12.0, 3.0),
+            ("1.234(56)", 1.234, 0.056),
+            ("9.81(0.02)", 9.81, 0.02),
+            ("1.234(5)e-3", 1.234e-3, 5e-6),
+            ("1.23e-3(4)", 1.23e-3, 4e-5),
+            ("6.62607015(81)e-34", 6.62607015e-34, 81e-42),
+        ];
+        for (line, value, uncertainty) in cases {
+            assert_eq!(
+                single_with_uncertainty(line, DecimalSeparator::Point),
+                (value, uncertainty),
+                "{:?}",
+                line
+            );
+        }
+        assert_eq!(
+            single_with_uncertainty("9,81(2)", DecimalSeparator::Comma),
+            (9.81, 0.02)
+        );
+    }
+
+    #[test]
+    fn uncertainties_bind_to_preceding_values() {
+        let measurements = tokenize("1.5 \u{b1} 0.1, 2.25(3)", DecimalSeparator::Point).unwrap();
+        let pairs: Vec<(f64, Option<f64>)> = measurements
+            .iter()
+            .map(|measurement| (measurement.value, measurement.uncertainty))
+            .collect();
+        assert_eq!(pairs, [(1.5, Some(0.1)), (2.25, Some(0.03))]);
+    }
+
+    #[test]
+    fn separated_parentheses_are_not_uncertainties() {
+        assert_eq!(
+            numbers("9.81 (2)", DecimalSeparator::Point),
+            [(9.81, 0..4), (2.0, 6..7)]
+        );
+        assert_eq!(
+            numbers("9.81(-2)", DecimalSeparator::Point),
+            [(9.81, 0..4), (-2.0, 5..7)]
+        );
+    }
+
+    #[test]
+    fn plus_minus_errors() {
+        let error = tokenize("1 \u{b1} x", DecimalSeparator::Point).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::MissingUncertainty);
+        assert_eq!(error.columns, 2..3);
+        let error = tokenize("1 +/- -0.5", DecimalSeparator::Point).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::NegativeUncertainty);
+        assert_eq!(error.columns, 6..10);
+    }
+
+    #[test]
+    fn percentage_uncertainties_are_relative() {
+        assert_eq!(
+            single_with_uncertainty("200 \u{b1} 2%", DecimalSeparator::Point),
+            (200.0, 4.0)
+        );
+        assert_eq!(
+            single_with_uncertainty("-200 +/- 2 %", DecimalSeparator::Point),
+            (-200.0, 4.0)
+        );
+        assert_eq!(
+            single_with_uncertainty("1,5e2 \u{b1} 10%", DecimalSeparator::Comma),
+            (150.0, 15.0)
+        );
+    }
+
+    #[test]
+    fn percent_signs_are_marked() {
+        let marked: Vec<(f64, bool, Range<usize>)> =
+            tokenize("10, 0.5 %, 20", DecimalSeparator::Point)
+                .unwrap()
+                .into_iter()
+                .map(|measurement| {
+                    (
+                        measurement.value,
+                        measurement.percentage,
+                        measurement.columns,
+                    )
+                })
+                .collect();
+        assert_eq!(
+            marked,
+            [
+                (10.0, false, 0..2),
+                (0.5, true, 4..9),
+                (20.0, false, 11..13)
+            ]
+        );
+    }
+}
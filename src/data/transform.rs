@@ -0,0 +1,303 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::str::FromStr;
+
+/// Functions to linearise one variable before fitting
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Transform {
+    #[default]
+    Identity,
+    /// Natural logarithm
+    Ln,
+    /// Base-10 logarithm
+    Log10,
+    /// 1/v
+    Reciprocal,
+    /// 1/v²
+    ReciprocalSquare,
+    Square,
+    Sqrt,
+}
+
+impl Transform {
+    /// All transforms, in the order shown to the user
+    pub const ALL: [Transform; 7] = [
+        Transform::Identity,
+        Transform::Ln,
+        Transform::Log10,
+        Transform::Reciprocal,
+        Transform::ReciprocalSquare,
+        Transform::Square,
+        Transform::Sqrt,
+    ];
+
+    /// Transform a value and propagate its uncertainty to first order.
+    /// None if the value is outside the domain
+    #[must_use]
+    pub fn apply(self, value: f64, uncertainty: f64) -> Option<(f64, f64)> {
+        let in_domain = match self {
+            Transform::Identity | Transform::Square => true,
+            Transform::Ln | Transform::Log10 | Transform::Sqrt => value > 0.0,
+            Transform::Reciprocal | Transform::ReciprocalSquare => value != 0.0,
+        };
+        if !in_domain {
+            return None;
+        }
+        let (value, uncertainty) = match self {
+            Transform::Identity => (value, uncertainty),
+            Transform::Ln => (value.ln(), uncertainty / value),
+            Transform::Log10 => (
+                value.log10(),
+                uncertainty / (value * std::f64::consts::LN_10),
+            ),
+            Transform::Reciprocal => (value.recip(), uncertainty / value.powi(2)),
+            Transform::ReciprocalSquare => (value.powi(-2), 2.0 * uncertainty / value.powi(3)),
+            Transform::Square => (value.powi(2), 2.0 * value * uncertainty),
+            Transform::Sqrt => (value.sqrt(), uncertainty / (2.0 * value.sqrt())),
+        };
+        Some((value, uncertainty.abs()))
+    }
+
+    /// Axis label of the transformed variable
+    #[must_use]
+    pub fn label(self, label: &str) -> String {
+        match self {
+            Transform::Identity => label.to_string(),
+            Transform::Ln => format!("ln({})", label),
+            Transform::Log10 => format!("log({})", label),
+            Transform::Reciprocal => format!("1/{}", label),
+            Transform::ReciprocalSquare => format!("1/{}²", label),
+            Transform::Square => format!("{}²", label),
+            Transform::Sqrt => format!("√{}", label),
+        }
+    }
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transform::Identity => write!(f, "none"),
+            Transform::Ln => write!(f, "ln"),
+            Transform::Log10 => write!(f, "log"),
+            Transform::Reciprocal => write!(f, "reciprocal"),
+            Transform::ReciprocalSquare => write!(f, "reciprocal-square"),
+            Transform::Square => write!(f, "square"),
+            Transform::Sqrt => write!(f, "sqrt"),
+        }
+    }
+}
+
+/// Error when converting from str to `Transform`
+#[derive(Clone, Debug)]
+pub enum TransformFromStrError {
+    UnknownTransform(String),
+}
+
+impl std::fmt::Display for TransformFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformFromStrError::UnknownTransform(tstr) => {
+                write!(f, "Unknown transform: {}", tstr)
+            }
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = TransformFromStrError;
+
+    /// Parse transform description
+    fn from_str(tstr: &str) -> Result<Self, Self::Err> {
+        match tstr.to_lowercase().as_str() {
+            "none" | "identity" => Ok(Transform::Identity),
+            "ln" => Ok(Transform::Ln),
+            "log" | "log10" => Ok(Transform::Log10),
+            "reciprocal" | "inverse" => Ok(Transform::Reciprocal),
+            "reciprocal-square" | "inverse-square" => Ok(Transform::ReciprocalSquare),
+            "square" => Ok(Transform::Square),
+            "sqrt" => Ok(Transform::Sqrt),
+            other => Err(Self::Err::UnknownTransform(other.to_string())),
+        }
+    }
+}
+
+/// Transforms applied to the dataset before plotting and fitting
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Transforms {
+    #[serde(default)]
+    pub x_transform: Transform,
+    #[serde(default)]
+    pub y_transform: Transform,
+}
+
+/// Error when a transform cannot be applied to a dataset
+#[derive(Clone, Debug)]
+pub enum TransformError {
+    /// Transform, axis name and the offending value
+    OutOfDomain(Transform, char, f64),
+}
+
+impl std::fmt::Display for TransformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformError::OutOfDomain(transform, axis, value) => {
+                write!(f, "Cannot apply {} to {} value {}", transform, axis, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{DecimalSeparator, Repeats, TwoVarDataSet, Uncertainty};
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        assert!(
+            (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+            "{}: got {}, expected {}",
+            what,
+            actual,
+            expected
+        );
+    }
+
+    fn dataset(text: &str) -> TwoVarDataSet {
+        TwoVarDataSet::from_string(
+            text,
+            Uncertainty::default(),
+            Uncertainty::default(),
+            Repeats::default(),
+            DecimalSeparator::Point,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn logarithms_and_reciprocals() {
+        let cases = [
+            (Transform::Ln, 2.0, 0.1, 2f64.ln(), 0.05),
+            (
+                Transform::Log10,
+                100.0,
+                2.0,
+                2.0,
+                0.02 / std::f64::consts::LN_10,
+            ),
+            (Transform::Reciprocal, 4.0, 0.2, 0.25, 0.0125),
+            (Transform::ReciprocalSquare, 2.0, 0.1, 0.25, 0.025),
+            (Transform::Square, -3.0, 0.1, 9.0, 0.6),
+            (Transform::Sqrt, 16.0, 0.4, 4.0, 0.05),
+            (Transform::Identity, -1.5, 0.3, -1.5, 0.3),
+        ];
+        for (transform, value, uncertainty, expected, expected_uncertainty) in cases {
+            let (transformed, propagated) = transform.apply(value, uncertainty).unwrap();
+            assert_close(transformed, expected, &transform.to_string());
+            assert_close(propagated, expected_uncertainty, &transform.to_string());
+        }
+    }
+
+    #[test]
+    fn propagation_follows_derivative() {
+        // Uncertainties are the slopes of the transforms times the uncertainty,
+        // and never negative
+        for transform in Transform::ALL {
+            for value in [0.3, 2.0, 7.5] {
+                let step = 1e-6;
+                let (plus, _) = transform.apply(value + step, 0.0).unwrap();
+                let (minus, _) = transform.apply(value - step, 0.0).unwrap();
+                let slope = (plus - minus) / (2.0 * step);
+                let (_, propagated) = transform.apply(value, 0.01).unwrap();
+                assert!(
+                    (propagated - 0.01 * slope.abs()).abs() <= 1e-6 * propagated.max(1e-3),
+                    "{} at {}: got {}, slope {}",
+                    transform,
+                    value,
+                    propagated,
+                    slope
+                );
+            }
+        }
+        assert_eq!(Transform::Reciprocal.apply(-2.0, 0.1), Some((-0.5, 0.025)));
+    }
+
+    #[test]
+    fn out_of_domain() {
+        for (transform, value) in [
+            (Transform::Ln, 0.0),
+            (Transform::Ln, -1.0),
+            (Transform::Log10, 0.0),
+            (Transform::Log10, -1e-3),
+            (Transform::Sqrt, -4.0),
+            (Transform::Sqrt, 0.0),
+            (Transform::Reciprocal, 0.0),
+            (Transform::ReciprocalSquare, 0.0),
+        ] {
+            assert_eq!(
+                transform.apply(value, 0.1),
+                None,
+                "{} of {}",
+                transform,
+                value
+            );
+        }
+        let data = dataset("1 2\n0 3\n-1 4");
+        let transforms = Transforms {
+            x_transform: Transform::Ln,
+            y_transform: Transform::Identity,
+        };
+        let error = data.transform(transforms).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot apply ln to x value 0");
+    }
+
+    #[test]
+    fn datasets() {
+        let data = dataset("1 ± 0.1, 2 ± 0.2\n4 ± 0.2, 10 ± 0.1");
+        let transforms = Transforms {
+            x_transform: Transform::Reciprocal,
+            y_transform: Transform::Ln,
+        };
+        let transformed = data.transform(transforms).unwrap();
+        assert_eq!(transformed.get_x_value(), [1.0, 0.25]);
+        assert_eq!(transformed.get_x_uncertainty(), [0.1, 0.0125]);
+        assert_close(transformed[1].y_value, 10f64.ln(), "ln 10");
+        assert_close(transformed[0].y_uncertainty, 0.1, "uncertainty of ln 2");
+        assert_close(transformed[1].y_uncertainty, 0.01, "uncertainty of ln 10");
+    }
+
+    #[test]
+    fn names_and_labels() {
+        for transform in Transform::ALL {
+            assert_eq!(
+                transform.to_string().parse::<Transform>().unwrap(),
+                transform
+            );
+        }
+        assert_eq!(
+            Transform::from_str("Inverse").unwrap(),
+            Transform::Reciprocal
+        );
+        assert!(Transform::from_str("exp").is_err());
+        assert_eq!(Transform::Ln.label("t"), "ln(t)");
+        assert_eq!(Transform::ReciprocalSquare.label("r"), "1/r²");
+    }
+}
@@ -17,17 +17,129 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
-use crate::data::{Line, Point};
+use crate::data::matrix::least_squares;
+use crate::data::nonlinear_fit::{levenberg_marquardt, parse_initial_guesses};
+use crate::data::tokenizer::{tokenize, Measurement};
+use crate::data::{
+    DecimalSeparator, Expression, FitMethod, FitResults, GradientLineMethod, Line, LineFit,
+    LineFitError, NonlinearFit, NonlinearFitError, Point, Polynomial, Repeats, ThroughAllError,
+    TransformError, Transforms, Uncertainty,
+};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{Error, ErrorKind};
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 use std::path::Path;
 
-/// Parse error types
-pub enum ParseError {
-    EmptyField,
-    BadFields,
+/// Reasons why a line of a dataset cannot be parsed
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ParseErrorKind {
+    /// Only one number on the line
+    TooFewFields,
+    /// More than four numbers, with the number of them
+    TooManyFields(usize),
+    NegativeUncertainty,
+    /// `±` or `+/-` not followed by a number
+    MissingUncertainty,
+    /// A table cell is not a single number
+    NotANumber,
+    /// Some but not all uncertainties written after their values,
+    /// with more than two numbers on the line
+    MixedNotation,
+    /// The number is too large to be represented
+    NonFinite,
+    /// Repeated readings in brackets without an x value before them
+    MissingX,
+    /// Fewer than two repeated readings in the brackets
+    TooFewReadings,
+    /// `[` without a matching `]`
+    UnclosedBracket,
+    /// Numbers other than x and its uncertainty outside the brackets
+    ReadingsOutsideBrackets,
+}
+
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::TooFewFields => write!(f, "Too few fields, expected 2 to 4 numbers"),
+            ParseErrorKind::TooManyFields(count) => {
+                write!(f, "Too many fields ({}), expected 2 to 4 numbers", count)
+            }
+            ParseErrorKind::NegativeUncertainty => write!(f, "Uncertainty cannot be negative"),
+            ParseErrorKind::MissingUncertainty => write!(f, "Expected an uncertainty after ±"),
+            ParseErrorKind::NotANumber => write!(f, "Expected a single number"),
+            ParseErrorKind::MixedNotation => write!(
+                f,
+                "Write either all uncertainties after their values or all in columns"
+            ),
+            ParseErrorKind::NonFinite => write!(f, "Number is too large"),
+            ParseErrorKind::MissingX => write!(f, "Expected an x value before the readings"),
+            ParseErrorKind::TooFewReadings => write!(f, "Expected at least two readings"),
+            ParseErrorKind::UnclosedBracket => write!(f, "Expected ] after the readings"),
+            ParseErrorKind::ReadingsOutsideBrackets => {
+                write!(f, "Write all readings of y inside the brackets")
+            }
+        }
+    }
+}
+
+/// Error while parsing a dataset, locating the offending text
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ParseError {
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Character columns of the offending text in the line, starting from 0
+    pub columns: Range<usize>,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    /// Byte range of the offending line in the parsed text, without the line break
+    #[must_use]
+    pub fn line_span(&self, buf: &str) -> Range<usize> {
+        let mut start = 0;
+        for (number, line) in buf.split('\n').enumerate() {
+            if number + 1 == self.line {
+                return start..start + line.len();
+            }
+            start += line.len() + 1;
+        }
+        buf.len()..buf.len()
+    }
+
+    /// The offending line of the parsed text, with the offending text marked
+    /// by carets on the next line
+    #[must_use]
+    pub fn highlight(&self, buf: &str) -> String {
+        let line = &buf[self.line_span(buf)];
+        let marker_width = self.columns.len().max(1);
+        format!(
+            "{}\n{}{}",
+            line.trim_end(),
+            " ".repeat(self.columns.start),
+            "^".repeat(marker_width)
+        )
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line,
+            self.columns.start + 1,
+            self.kind
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::new(ErrorKind::InvalidData, error)
+    }
 }
 
 /// Struct representing a two-variable data and the uncertainties
@@ -37,6 +149,16 @@ pub struct TwoVarDataPoint {
     pub x_uncertainty: f64,
     pub y_value: f64,
     pub y_uncertainty: f64,
+    /// Shown on the plot but left out of the fits
+    pub excluded: bool,
+}
+
+/// Mark at the start of a line of a dataset excluding its point from the fits
+pub const EXCLUDED_MARKER: char = '*';
+
+/// Whether the line of a dataset has a point on it, assuming it parses
+fn has_point(line: &str, separator: DecimalSeparator) -> bool {
+    matches!(tokenize(line, separator), Ok(fields) if !fields.is_empty())
 }
 
 /// Rules to define the function to get minimum/maximum x/y
@@ -73,47 +195,250 @@ macro_rules! raw_defun_minmax {
     };
 }
 
+/// Find the numbers in text, which starts at character column `offset` of
+/// its line, rejecting those too large to be represented
+fn finite_fields(
+    text: &str,
+    offset: usize,
+    separator: DecimalSeparator,
+) -> Result<Vec<Measurement>, ParseError> {
+    let shift = |columns: &Range<usize>| offset + columns.start..offset + columns.end;
+    let mut fields = tokenize(text, separator).map_err(|error| ParseError {
+        columns: shift(&error.columns),
+        ..error
+    })?;
+    for field in &mut fields {
+        field.columns = shift(&field.columns);
+        if !field.value.is_finite() || matches!(field.uncertainty, Some(u) if !u.is_finite()) {
+            return Err(ParseError {
+                line: 1,
+                columns: field.columns.clone(),
+                kind: ParseErrorKind::NonFinite,
+            });
+        }
+    }
+    Ok(fields)
+}
+
 impl TwoVarDataPoint {
-    /// Parse a line
+    /// Combine repeated readings of y at the same x into one point
+    #[must_use]
+    pub fn from_readings(
+        x_value: f64,
+        x_uncertainty: f64,
+        readings: &[f64],
+        repeats: Repeats,
+    ) -> Self {
+        let (y_value, y_uncertainty) = repeats.combine(readings);
+        Self {
+            x_value,
+            x_uncertainty,
+            y_value,
+            y_uncertainty,
+            excluded: false,
+        }
+    }
+
+    /// Parse a line of repeated y readings such as `x [y1 y2 y3]`,
+    /// where x may be followed by its uncertainty
+    fn from_readings_line(
+        line: &str,
+        dux: Uncertainty,
+        repeats: Repeats,
+        separator: DecimalSeparator,
+    ) -> Result<Self, ParseError> {
+        let error = |columns, kind| ParseError {
+            line: 1,
+            columns,
+            kind,
+        };
+        let (before, rest) = line.split_once('[').unwrap_or((line, ""));
+        let open = before.chars().count();
+        let (inside, after) = rest
+            .split_once(']')
+            .ok_or_else(|| error(open..line.chars().count(), ParseErrorKind::UnclosedBracket))?;
+        let close = open + 1 + inside.chars().count();
+        let fields = finite_fields(before, 0, separator)?;
+        let readings = finite_fields(inside, open + 1, separator)?;
+        let trailing = finite_fields(after, close + 1, separator)?;
+        let outside = |extra: &[Measurement]| {
+            error(
+                extra[0].columns.start..extra[extra.len() - 1].columns.end,
+                ParseErrorKind::ReadingsOutsideBrackets,
+            )
+        };
+        if let Some(reading) = readings
+            .iter()
+            .find(|reading| reading.uncertainty.is_some() || reading.percentage)
+        {
+            return Err(error(reading.columns.clone(), ParseErrorKind::NotANumber));
+        }
+        if readings.len() < 2 {
+            return Err(error(open..close + 1, ParseErrorKind::TooFewReadings));
+        }
+        if !trailing.is_empty() {
+            return Err(outside(&trailing));
+        }
+        let (x_value, x_uncertainty) = match fields.as_slice() {
+            [] => return Err(error(open..open + 1, ParseErrorKind::MissingX)),
+            [x] => (x.value, x.uncertainty.unwrap_or_else(|| dux.of(x.value))),
+            [x, ux] if x.uncertainty.is_none() && ux.uncertainty.is_none() => {
+                if ux.value < 0.0 {
+                    return Err(error(
+                        ux.columns.clone(),
+                        ParseErrorKind::NegativeUncertainty,
+                    ));
+                }
+                // Percentages are relative to x
+                if ux.percentage {
+                    (x.value, ux.value / 100.0 * x.value.abs())
+                } else {
+                    (x.value, ux.value)
+                }
+            }
+            [_, extra @ ..] => return Err(outside(extra)),
+        };
+        let readings: Vec<f64> = readings.iter().map(|reading| reading.value).collect();
+        Ok(Self::from_readings(
+            x_value,
+            x_uncertainty,
+            &readings,
+            repeats,
+        ))
+    }
+
+    /// Uncertainty of the residual y − f(x) from a fit whose gradient at the
+    /// point is `gradient`. The weighted and York fits carry the x uncertainty
+    /// through the gradient, while ordinary least squares ignores it
+    #[must_use]
+    pub fn residual_uncertainty(&self, method: FitMethod, gradient: f64) -> f64 {
+        match method {
+            FitMethod::Ordinary => self.y_uncertainty,
+            FitMethod::Weighted | FitMethod::York => {
+                self.y_uncertainty.hypot(gradient * self.x_uncertainty)
+            }
+        }
+    }
+
+    /// Parse a line, None if there are no numbers on it.
+    /// The point is excluded from the fits if the line starts with `*`.
+    /// Errors are reported as on line 1
     /// line: The line to parse
-    /// dux: Default x uncertainty
-    /// duy: Default y uncertainty
-    pub fn from_line(line: &str, dux: f64, duy: f64) -> Result<Self, ParseError> {
-        let mut fields: Vec<f64> = Vec::with_capacity(4);
-        let mut line = line.to_string();
-        // Exhaust this line by taking all numeric fields
-        while let Some((number, (_, end_point))) = atof(&line) {
-            fields.push(number);
-            line = line.chars().skip(end_point).collect::<String>();
-            // break when no data can be processed anymore
+    /// dux: Default x uncertainty, possibly relative to each value
+    /// duy: Default y uncertainty, possibly relative to each value
+    /// repeats: How repeated readings of y in brackets are combined
+    /// separator: Decimal separator of the numbers
+    pub fn from_line(
+        line: &str,
+        dux: Uncertainty,
+        duy: Uncertainty,
+        repeats: Repeats,
+        separator: DecimalSeparator,
+    ) -> Result<Option<Self>, ParseError> {
+        let excluded = line.trim_start().starts_with(EXCLUDED_MARKER);
+        Ok(Self::from_numbers(line, dux, duy, repeats, separator)?
+            .map(|point| Self { excluded, ..point }))
+    }
+
+    /// Parse the numbers on a line, None if there are none
+    fn from_numbers(
+        line: &str,
+        dux: Uncertainty,
+        duy: Uncertainty,
+        repeats: Repeats,
+        separator: DecimalSeparator,
+    ) -> Result<Option<Self>, ParseError> {
+        let error = |columns, kind| ParseError {
+            line: 1,
+            columns,
+            kind,
+        };
+        // Repeated readings of y are written in brackets
+        if line.contains('[') {
+            return Self::from_readings_line(line, dux, repeats, separator).map(Some);
+        }
+        let fields = finite_fields(line, 0, separator)?;
+        // Uncertainties written after their values are bound to them,
+        // otherwise the number of columns decides
+        if fields.iter().any(|field| field.uncertainty.is_some()) {
+            return match fields.len() {
+                1 => Err(error(
+                    fields[0].columns.clone(),
+                    ParseErrorKind::TooFewFields,
+                )),
+                2 => Ok(Some(TwoVarDataPoint {
+                    x_value: fields[0].value,
+                    x_uncertainty: fields[0]
+                        .uncertainty
+                        .unwrap_or_else(|| dux.of(fields[0].value)),
+                    y_value: fields[1].value,
+                    y_uncertainty: fields[1]
+                        .uncertainty
+                        .unwrap_or_else(|| duy.of(fields[1].value)),
+                    excluded: false,
+                })),
+                count => Err(error(
+                    fields[2].columns.start..fields[count - 1].columns.end,
+                    if fields.iter().all(|field| field.uncertainty.is_some()) {
+                        ParseErrorKind::TooManyFields(count)
+                    } else {
+                        ParseErrorKind::MixedNotation
+                    },
+                )),
+            };
         }
+        // Percentages are relative to the value before them
+        let uncertainty = |index: usize| {
+            let field = &fields[index];
+            if field.value < 0.0 {
+                Err(error(
+                    field.columns.clone(),
+                    ParseErrorKind::NegativeUncertainty,
+                ))
+            } else if field.percentage {
+                Ok(field.value / 100.0 * fields[index - 1].value.abs())
+            } else {
+                Ok(field.value)
+            }
+        };
         // Append to the object
         match fields.len() {
-            0 => Err(ParseError::EmptyField),
-            2 => Ok(TwoVarDataPoint {
-                x_value: fields[0],
-                x_uncertainty: dux,
-                y_value: fields[1],
-                y_uncertainty: duy,
-            }),
-            3 => Ok(TwoVarDataPoint {
-                x_value: fields[0],
-                x_uncertainty: dux,
-                y_value: fields[1],
-                y_uncertainty: fields[2],
-            }),
-            4 => Ok(TwoVarDataPoint {
-                x_value: fields[0],
-                x_uncertainty: fields[1],
-                y_value: fields[2],
-                y_uncertainty: fields[3],
-            }),
-            _ => Err(ParseError::BadFields),
+            0 => Ok(None),
+            1 => Err(error(
+                fields[0].columns.clone(),
+                ParseErrorKind::TooFewFields,
+            )),
+            2 => Ok(Some(TwoVarDataPoint {
+                x_value: fields[0].value,
+                x_uncertainty: dux.of(fields[0].value),
+                y_value: fields[1].value,
+                y_uncertainty: duy.of(fields[1].value),
+                excluded: false,
+            })),
+            3 => Ok(Some(TwoVarDataPoint {
+                x_value: fields[0].value,
+                x_uncertainty: dux.of(fields[0].value),
+                y_value: fields[1].value,
+                y_uncertainty: uncertainty(2)?,
+                excluded: false,
+            })),
+            4 => Ok(Some(TwoVarDataPoint {
+                x_value: fields[0].value,
+                x_uncertainty: uncertainty(1)?,
+                y_value: fields[2].value,
+                y_uncertainty: uncertainty(3)?,
+                excluded: false,
+            })),
+            count => Err(error(
+                fields[4].columns.start..fields[count - 1].columns.end,
+                ParseErrorKind::TooManyFields(count),
+            )),
         }
     }
 }
 
 /// Struct representing a set of two-variable data and their uncertainties
+/// Points excluded from the fits are left out of every line and curve fitted
 #[derive(Debug, Default, Clone)]
 pub struct TwoVarDataSet(Vec<TwoVarDataPoint>);
 
@@ -134,39 +459,143 @@ impl DerefMut for TwoVarDataSet {
 impl TwoVarDataSet {
     /// Parse a data file
     /// filename: Path to the file
-    /// dux: Default x uncertainty
-    /// duy: Default y uncertainty
-    pub fn from_file<P: AsRef<Path>>(filename: P, dux: f64, duy: f64) -> Result<Self, Error> {
+    /// dux: Default x uncertainty, possibly relative to each value
+    /// duy: Default y uncertainty, possibly relative to each value
+    /// repeats: How repeated readings of y in brackets are combined
+    /// separator: Decimal separator of the numbers
+    pub fn from_file<P: AsRef<Path>>(
+        filename: P,
+        dux: Uncertainty,
+        duy: Uncertainty,
+        repeats: Repeats,
+        separator: DecimalSeparator,
+    ) -> Result<Self, Error> {
         // Read the data file
         let mut data_file = File::open(filename)?;
         let mut contents = String::new();
         data_file.read_to_string(&mut contents)?;
-        Self::from_string(&contents, dux, duy)
+        Ok(Self::from_string(&contents, dux, duy, repeats, separator)?)
     }
 
     /// Parse a data string
     /// buf: data string
-    /// dux: Default x uncertainty
-    /// duy: Default y uncertainty
-    pub fn from_string(buf: &str, dux: f64, duy: f64) -> Result<Self, Error> {
+    /// dux: Default x uncertainty, possibly relative to each value
+    /// duy: Default y uncertainty, possibly relative to each value
+    /// repeats: How repeated readings of y in brackets are combined
+    /// separator: Decimal separator of the numbers
+    pub fn from_string(
+        buf: &str,
+        dux: Uncertainty,
+        duy: Uncertainty,
+        repeats: Repeats,
+        separator: DecimalSeparator,
+    ) -> Result<Self, ParseError> {
         // Split into lines
         let lines: Vec<&str> = buf.split('\n').collect();
         let mut result: Vec<TwoVarDataPoint> = Vec::with_capacity(lines.len());
-        for line in lines {
-            match TwoVarDataPoint::from_line(line, dux, duy) {
-                Ok(data) => result.push(data),
-                Err(ParseError::EmptyField) => continue,
-                Err(ParseError::BadFields) => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        format!("unknown fields {:?}", line),
-                    ))
+        for (number, line) in lines.into_iter().enumerate() {
+            match TwoVarDataPoint::from_line(line, dux, duy, repeats, separator) {
+                Ok(Some(data)) => result.push(data),
+                Ok(None) => continue,
+                Err(error) => {
+                    return Err(ParseError {
+                        line: number + 1,
+                        ..error
+                    })
                 }
             }
         }
         Ok(Self(result))
     }
 
+    /// Toggle the exclusion mark of the point at `index` in a data string,
+    /// returning the changed string. None if there are not enough points
+    /// buf: data string, which must parse
+    /// index: Index of the point in the parsed dataset
+    /// separator: Decimal separator of the numbers
+    #[must_use]
+    pub fn toggle_excluded(buf: &str, index: usize, separator: DecimalSeparator) -> Option<String> {
+        let mut lines: Vec<String> = buf.split('\n').map(String::from).collect();
+        let line = lines
+            .iter_mut()
+            .filter(|line| has_point(line, separator))
+            .nth(index)?;
+        let indent = line.len() - line.trim_start().len();
+        if let Some(rest) = line[indent..].strip_prefix(EXCLUDED_MARKER) {
+            *line = format!(
+                "{}{}",
+                &line[..indent],
+                rest.strip_prefix(' ').unwrap_or(rest)
+            );
+        } else {
+            line.insert_str(indent, &format!("{} ", EXCLUDED_MARKER));
+        }
+        Some(lines.join("\n"))
+    }
+
+    /// Whether any point is excluded from the fits
+    #[must_use]
+    pub fn has_excluded(&self) -> bool {
+        self.iter().any(|data| data.excluded)
+    }
+
+    /// Get the points not excluded from the fits
+    #[must_use]
+    pub fn included(&self) -> Self {
+        Self(self.iter().filter(|data| !data.excluded).copied().collect())
+    }
+
+    /// Exclude the points whose studentised residuals are larger than
+    /// `threshold`, returning how many are excluded. Each point is compared
+    /// with the line fitted to the other included points, so that an outlier
+    /// does not pull the line or inflate the spread it is measured against.
+    /// Points are kept if the other points leave no degrees of freedom
+    pub fn exclude_outliers(&mut self, method: FitMethod, threshold: f64) -> usize {
+        let outliers: Vec<usize> = (0..self.len())
+            .filter(|&index| !self[index].excluded)
+            .filter(|&index| {
+                matches!(
+                    self.studentised_residual(index, method),
+                    Some(residual) if residual.abs() > threshold
+                )
+            })
+            .collect();
+        for &index in &outliers {
+            self.0[index].excluded = true;
+        }
+        outliers.len()
+    }
+
+    /// Residual of the point at `index` from the line fitted to the other
+    /// included points, divided by its standard deviation. That is the spread
+    /// of the other residuals for ordinary least squares and the uncertainty
+    /// of the point otherwise, each with the uncertainty of the line there.
+    /// None if the line cannot be fitted or has no degrees of freedom
+    fn studentised_residual(&self, index: usize, method: FitMethod) -> Option<f64> {
+        let data = self[index];
+        let mut others = self.clone();
+        others.0[index].excluded = true;
+        let line_fit = others
+            .line_fit(method)
+            .ok()
+            .filter(|line_fit| line_fit.degrees_of_freedom > 0)?;
+        let point_variance = match method {
+            FitMethod::Ordinary => line_fit.residual_sd?.powi(2),
+            _ => data
+                .residual_uncertainty(method, line_fit.line.gradient)
+                .powi(2),
+        };
+        // Variance of the y value of the line at x
+        let gradient_sd = line_fit.gradient_uncertainty.unwrap_or(0.0);
+        let y_intercept_sd = line_fit.y_intercept_uncertainty.unwrap_or(0.0);
+        let x = data.x_value;
+        let line_variance = y_intercept_sd.powi(2)
+            + (x * gradient_sd).powi(2)
+            + 2.0 * x * line_fit.correlation * gradient_sd * y_intercept_sd;
+        let sd = (point_variance + line_variance).sqrt();
+        (sd > 0.0).then(|| (data.y_value - line_fit.line.y(x)) / sd)
+    }
+
     /// Get the arithmetic average value of x
     #[must_use]
     pub fn mean_x(&self) -> f64 {
@@ -187,6 +616,38 @@ impl TwoVarDataSet {
         sum / self.len() as f64
     }
 
+    /// Apply the transforms to the values and the uncertainties of every
+    /// point, for example to linearise the data before fitting a line
+    pub fn transform(&self, transforms: Transforms) -> Result<Self, TransformError> {
+        let mut result = Vec::with_capacity(self.len());
+        for data in self.iter() {
+            let (x_value, x_uncertainty) = transforms
+                .x_transform
+                .apply(data.x_value, data.x_uncertainty)
+                .ok_or(TransformError::OutOfDomain(
+                    transforms.x_transform,
+                    'x',
+                    data.x_value,
+                ))?;
+            let (y_value, y_uncertainty) = transforms
+                .y_transform
+                .apply(data.y_value, data.y_uncertainty)
+                .ok_or(TransformError::OutOfDomain(
+                    transforms.y_transform,
+                    'y',
+                    data.y_value,
+                ))?;
+            result.push(TwoVarDataPoint {
+                x_value,
+                x_uncertainty,
+                y_value,
+                y_uncertainty,
+                excluded: data.excluded,
+            });
+        }
+        Ok(Self(result))
+    }
+
     /// Get all x values as a vector
     #[must_use]
     pub fn get_x_value(&self) -> Vec<f64> {
@@ -227,6 +688,9 @@ impl TwoVarDataSet {
     /// Get line of best fit
     #[must_use]
     pub fn line_best_fit(&self) -> Line {
+        if self.has_excluded() {
+            return self.included().line_best_fit();
+        }
         let ax = self.mean_x();
         let ay = self.mean_y();
         let mut numerator: f64 = 0.0;
@@ -245,8 +709,318 @@ impl TwoVarDataSet {
         }
     }
 
+    /// Get line of best fit by ordinary least squares, along with the standard
+    /// errors of the gradient and the y-intercept estimated from the residuals
+    #[must_use]
+    pub fn line_best_fit_ordinary(&self) -> LineFit {
+        if self.has_excluded() {
+            return self.included().line_best_fit_ordinary();
+        }
+        let line = self.line_best_fit();
+        let (r_squared, residual_sd, degrees_of_freedom) = self.residual_statistics(&line);
+        let ax = self.mean_x();
+        let mut sxx: f64 = 0.0;
+        let mut sum_xx: f64 = 0.0;
+        for data in self.iter() {
+            sxx += (data.x_value - ax).powi(2);
+            sum_xx += data.x_value.powi(2);
+        }
+        let mean_xx = sum_xx / self.len() as f64;
+        LineFit {
+            line,
+            gradient_uncertainty: residual_sd.map(|sd| sd / sxx.sqrt()),
+            y_intercept_uncertainty: residual_sd.map(|sd| sd * (mean_xx / sxx).sqrt()),
+            correlation: -ax / mean_xx.sqrt(),
+            r_squared,
+            residual_sd,
+            degrees_of_freedom,
+        }
+    }
+
+    /// Get line of best fit by least squares weighted with 1/σy², along with
+    /// the standard errors of the gradient and the y-intercept
+    pub fn line_best_fit_weighted(&self) -> Result<LineFit, LineFitError> {
+        if self.has_excluded() {
+            return self.included().line_best_fit_weighted();
+        }
+        if self.len() < 2 {
+            return Err(LineFitError::TooFewPoints);
+        }
+        if self.iter().any(|data| data.y_uncertainty <= 0.0) {
+            return Err(LineFitError::NonPositiveUncertainty);
+        }
+        let mut sum_w: f64 = 0.0;
+        let mut sum_wx: f64 = 0.0;
+        let mut sum_wy: f64 = 0.0;
+        let mut sum_wxx: f64 = 0.0;
+        let mut sum_wxy: f64 = 0.0;
+        for data in self.iter() {
+            let w = data.y_uncertainty.powi(-2);
+            let x = data.x_value;
+            let y = data.y_value;
+            sum_w += w;
+            sum_wx += w * x;
+            sum_wy += w * y;
+            sum_wxx += w * x * x;
+            sum_wxy += w * x * y;
+        }
+        let delta = sum_w * sum_wxx - sum_wx.powi(2);
+        if delta == 0.0 {
+            return Err(LineFitError::VerticalLine);
+        }
+        let b = (sum_w * sum_wxy - sum_wx * sum_wy) / delta;
+        let a = (sum_wxx * sum_wy - sum_wx * sum_wxy) / delta;
+        let line = Line {
+            gradient: b,
+            y_intercept: a,
+        };
+        let (r_squared, residual_sd, degrees_of_freedom) = self.residual_statistics(&line);
+        Ok(LineFit {
+            line,
+            gradient_uncertainty: Some((sum_w / delta).sqrt()),
+            y_intercept_uncertainty: Some((sum_wxx / delta).sqrt()),
+            correlation: -sum_wx / (sum_w * sum_wxx).sqrt(),
+            r_squared,
+            residual_sd,
+            degrees_of_freedom,
+        })
+    }
+
+    /// Get line of best fit by York regression, which takes both the x and
+    /// y uncertainties into account (York et al., 2004, Am. J. Phys. 72, 367).
+    /// The errors of x and y are assumed to be uncorrelated
+    pub fn line_best_fit_york(&self) -> Result<LineFit, LineFitError> {
+        const MAX_ITERATIONS: usize = 100;
+        const TOLERANCE: f64 = 1e-12;
+        if self.has_excluded() {
+            return self.included().line_best_fit_york();
+        }
+        if self.len() < 2 {
+            return Err(LineFitError::TooFewPoints);
+        }
+        // Changes of the gradient are measured against the gradient itself, or
+        // against the spread of y over that of x if the gradient is near zero,
+        // so that flat lines converge without reaching an exact fixed point
+        let ax = self.mean_x();
+        let ay = self.mean_y();
+        let (sxx, syy) = self.iter().fold((0.0, 0.0), |(sxx, syy), data| {
+            (
+                sxx + (data.x_value - ax).powi(2),
+                syy + (data.y_value - ay).powi(2),
+            )
+        });
+        let gradient_scale = (syy / sxx).sqrt();
+        // Start from the ordinary least-squares gradient
+        let mut b = self.line_best_fit().gradient;
+        if !b.is_finite() {
+            return Err(LineFitError::VerticalLine);
+        }
+        let mut converged = false;
+        let mut weights: Vec<f64> = Vec::with_capacity(self.len());
+        let mut betas: Vec<f64> = Vec::with_capacity(self.len());
+        let mut mean_x: f64 = 0.0;
+        let mut mean_y: f64 = 0.0;
+        for _ in 0..MAX_ITERATIONS {
+            if !b.is_finite() {
+                return Err(LineFitError::NotConverged);
+            }
+            weights = self
+                .iter()
+                .map(|data| 1.0 / (data.y_uncertainty.powi(2) + (b * data.x_uncertainty).powi(2)))
+                .collect();
+            if weights.iter().any(|w| !w.is_finite()) {
+                return Err(LineFitError::MissingUncertainty);
+            }
+            let sum_w: f64 = weights.iter().sum();
+            mean_x = self
+                .iter()
+                .zip(weights.iter())
+                .map(|(data, w)| w * data.x_value)
+                .sum::<f64>()
+                / sum_w;
+            mean_y = self
+                .iter()
+                .zip(weights.iter())
+                .map(|(data, w)| w * data.y_value)
+                .sum::<f64>()
+                / sum_w;
+            betas = self
+                .iter()
+                .zip(weights.iter())
+                .map(|(data, w)| {
+                    let u = data.x_value - mean_x;
+                    let v = data.y_value - mean_y;
+                    w * (u * data.y_uncertainty.powi(2) + b * v * data.x_uncertainty.powi(2))
+                })
+                .collect();
+            let mut numerator: f64 = 0.0;
+            let mut denominator: f64 = 0.0;
+            for ((data, w), beta) in self.iter().zip(weights.iter()).zip(betas.iter()) {
+                numerator += w * beta * (data.y_value - mean_y);
+                denominator += w * beta * (data.x_value - mean_x);
+            }
+            let new_b = numerator / denominator;
+            let difference = (new_b - b).abs();
+            b = new_b;
+            if difference <= TOLERANCE * b.abs().max(gradient_scale) {
+                converged = true;
+                break;
+            }
+        }
+        if !converged || !b.is_finite() {
+            return Err(LineFitError::NotConverged);
+        }
+        let a = mean_y - b * mean_x;
+        // Uncertainties from the least-squares-adjusted x values
+        let sum_w: f64 = weights.iter().sum();
+        let adjusted_x: Vec<f64> = betas.iter().map(|beta| mean_x + beta).collect();
+        let mean_adjusted_x = adjusted_x
+            .iter()
+            .zip(weights.iter())
+            .map(|(x, w)| w * x)
+            .sum::<f64>()
+            / sum_w;
+        let sum_wuu: f64 = adjusted_x
+            .iter()
+            .zip(weights.iter())
+            .map(|(x, w)| w * (x - mean_adjusted_x).powi(2))
+            .sum();
+        let b_variance = 1.0 / sum_wuu;
+        let a_variance = 1.0 / sum_w + mean_adjusted_x.powi(2) * b_variance;
+        let line = Line {
+            gradient: b,
+            y_intercept: a,
+        };
+        let (r_squared, residual_sd, degrees_of_freedom) = self.residual_statistics(&line);
+        Ok(LineFit {
+            line,
+            gradient_uncertainty: Some(b_variance.sqrt()),
+            y_intercept_uncertainty: Some(a_variance.sqrt()),
+            correlation: -mean_adjusted_x * b_variance.sqrt() / a_variance.sqrt(),
+            r_squared,
+            residual_sd,
+            degrees_of_freedom,
+        })
+    }
+
+    /// Get the coefficient of determination, the standard deviation of the
+    /// y residuals and the degrees of freedom of a line fitted to this dataset.
+    /// The standard deviation is None without degrees of freedom
+    fn residual_statistics(&self, line: &Line) -> (f64, Option<f64>, usize) {
+        let ay = self.mean_y();
+        let mut ss_residual: f64 = 0.0;
+        let mut ss_total: f64 = 0.0;
+        for data in self.iter() {
+            ss_residual += (data.y_value - line.y(data.x_value)).powi(2);
+            ss_total += (data.y_value - ay).powi(2);
+        }
+        // Two parameters are estimated from the data
+        let degrees_of_freedom = self.len().saturating_sub(2);
+        (
+            1.0 - ss_residual / ss_total,
+            (degrees_of_freedom > 0).then(|| (ss_residual / degrees_of_freedom as f64).sqrt()),
+            degrees_of_freedom,
+        )
+    }
+
+    /// Get line of best fit and its statistics with the specified method
+    pub fn line_fit(&self, method: FitMethod) -> Result<LineFit, LineFitError> {
+        match method {
+            FitMethod::Ordinary => Ok(self.line_best_fit_ordinary()),
+            FitMethod::Weighted => self.line_best_fit_weighted(),
+            FitMethod::York => self.line_best_fit_york(),
+        }
+    }
+
+    /// Get line of best fit with the specified method
+    #[must_use]
+    pub fn line_best_fit_by(&self, method: FitMethod) -> Option<Line> {
+        self.line_fit(method).ok().map(|fit| fit.line)
+    }
+
+    /// Get polynomial of best fit of the specified degree by least squares.
+    /// If `weighted` is true, each point is weighted by 1/σy².
+    /// None if there are too few points, or any y uncertainty is not
+    /// positive when weighted
+    #[must_use]
+    pub fn polynomial_best_fit(&self, degree: usize, weighted: bool) -> Option<Polynomial> {
+        if self.has_excluded() {
+            return self.included().polynomial_best_fit(degree, weighted);
+        }
+        if weighted && self.iter().any(|data| data.y_uncertainty <= 0.0) {
+            return None;
+        }
+        // Rows of the Vandermonde matrix, each divided by σy if weighted
+        let mut rows: Vec<Vec<f64>> = Vec::with_capacity(self.len());
+        let mut values: Vec<f64> = Vec::with_capacity(self.len());
+        for data in self.iter() {
+            let scale = if weighted { data.y_uncertainty } else { 1.0 };
+            let mut row = Vec::with_capacity(degree + 1);
+            let mut power = 1.0;
+            for _ in 0..=degree {
+                row.push(power / scale);
+                power *= data.x_value;
+            }
+            rows.push(row);
+            values.push(data.y_value / scale);
+        }
+        Some(Polynomial {
+            coefficients: least_squares(rows, values)?,
+        })
+    }
+
+    /// Fit an expression by nonlinear least squares, starting from the
+    /// initial parameter values ordered as in `expression.parameters()`.
+    /// Points are weighted by 1/σy² if `method` is weighted, or by the
+    /// effective variance σy² + (f'(x)σx)² if it is York
+    pub fn expression_best_fit(
+        &self,
+        expression: &Expression,
+        initial: &[f64],
+        method: FitMethod,
+    ) -> Result<NonlinearFit, NonlinearFitError> {
+        if self.has_excluded() {
+            return self
+                .included()
+                .expression_best_fit(expression, initial, method);
+        }
+        levenberg_marquardt(self, expression, initial, method)
+    }
+
+    /// Parse and fit an expression with initial guesses such as "a=1, tau=2"
+    pub fn expression_fit(
+        &self,
+        expression: &str,
+        initial_guesses: &str,
+        method: FitMethod,
+    ) -> Result<NonlinearFit, NonlinearFitError> {
+        let expression: Expression = expression.parse()?;
+        let initial = parse_initial_guesses(&expression, initial_guesses)?;
+        self.expression_best_fit(&expression, &initial, method)
+    }
+
+    /// Get the residuals y − f(x) from the best fit made with `method`, as
+    /// points whose y uncertainties are those of the residuals.
+    /// None if the model cannot be fitted
+    #[must_use]
+    pub fn residuals(&self, fit: &FitResults, method: FitMethod) -> Option<Self> {
+        self.iter()
+            .map(|data| {
+                Some(TwoVarDataPoint {
+                    y_value: data.y_value - fit.y(data.x_value)?,
+                    y_uncertainty: data.residual_uncertainty(method, fit.gradient(data.x_value)?),
+                    ..*data
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Self)
+    }
+
     /// Permute all possible lines by connecting the ends
     fn lines(&self) -> Option<Vec<Line>> {
+        if self.has_excluded() {
+            return self.included().lines();
+        }
         // Error check
         if self.is_empty() {
             return None;
@@ -331,56 +1105,844 @@ impl TwoVarDataSet {
                 .unwrap_or(std::cmp::Ordering::Greater)
         })?)
     }
+
+    /// Find the shallowest and the steepest lines that pass through the error
+    /// boxes of every point, as a tuple of (minimum, maximum) gradient lines
+    pub fn lines_through_all(&self) -> Result<(Line, Line), ThroughAllError> {
+        if self.has_excluded() {
+            return self.included().lines_through_all();
+        }
+        if self.len() < 2 {
+            return Err(ThroughAllError::TooFewPoints);
+        }
+        // A vertical line through all boxes exists iff the x ranges all overlap
+        if self.max_left() <= self.min_right() {
+            return Err(ThroughAllError::Unbounded);
+        }
+        let boxes: Vec<ErrorBox> = self
+            .iter()
+            .map(|data| ErrorBox {
+                left: data.x_value - data.x_uncertainty,
+                right: data.x_value + data.x_uncertainty,
+                bottom: data.y_value - data.y_uncertainty,
+                top: data.y_value + data.y_uncertainty,
+            })
+            .collect();
+        let rising = rising_lines_through(&boxes);
+        // Negative gradients are the non-negative ones of the boxes mirrored in
+        // the y axis, with the gradients negated back
+        let mirrored: Vec<ErrorBox> = boxes
+            .iter()
+            .map(|error_box| ErrorBox {
+                left: -error_box.right,
+                right: -error_box.left,
+                ..*error_box
+            })
+            .collect();
+        let mirror = |line: Line| Line {
+            gradient: -line.gradient,
+            ..line
+        };
+        let falling = rising_lines_through(&mirrored)
+            .map(|(min_line, max_line)| (mirror(max_line), mirror(min_line)));
+        match (falling, rising) {
+            (Some((min_line, _)), Some((_, max_line))) => Ok((min_line, max_line)),
+            (Some(lines), None) | (None, Some(lines)) => Ok(lines),
+            (None, None) => Err(ThroughAllError::Infeasible),
+        }
+    }
+
+    /// Get the largest left edge of the error boxes
+    fn max_left(&self) -> f64 {
+        self.iter()
+            .map(|data| data.x_value - data.x_uncertainty)
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    /// Get the smallest right edge of the error boxes
+    fn min_right(&self) -> f64 {
+        self.iter()
+            .map(|data| data.x_value + data.x_uncertainty)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Get the minimum and maximum gradient lines with the specified method,
+    /// as a tuple of (minimum, maximum) gradient lines
+    pub fn gradient_lines_by(
+        &self,
+        method: GradientLineMethod,
+    ) -> Result<(Line, Line), ThroughAllError> {
+        match method {
+            GradientLineMethod::Endpoints => self
+                .line_min_grad()
+                .zip(self.line_max_grad())
+                .ok_or(ThroughAllError::TooFewPoints),
+            GradientLineMethod::AllPoints => self.lines_through_all(),
+        }
+    }
 }
 
-/// Convert string to float number, returning a tuple of
-/// the number and a tuple of the index of the first digit and the first non-digit,
-/// or None if no number found
-fn atof(string: &str) -> Option<(f64, (usize, usize))> {
-    // Resulting number without decimal point
-    let mut result: f64 = 0.0;
-    // -1 for a negative number
-    let mut sign: f64 = 1.0;
-    // Numbers of fractional digits processed (or to be processed)
-    // Negative as it's 10's power
-    let mut fracdig: i32 = 0;
-    // Index of the first number
-    let mut startpoint: Option<usize> = None;
-    // Index of the first non-number
-    let mut endpoint: usize = string.len();
-    // Go over every character
-    for (idx, chr) in string.chars().enumerate() {
-        // Found a digit
-        if chr.is_ascii_digit() {
-            if startpoint == None {
-                // Set processing start point
-                startpoint = if idx != 0 && Some('-') == string.chars().nth(idx - 1) {
-                    sign = -1.0;
-                    Some(idx - 1)
-                } else {
-                    Some(idx)
+/// The error box of a point
+#[derive(Debug, Clone, Copy)]
+struct ErrorBox {
+    left: f64,
+    right: f64,
+    bottom: f64,
+    top: f64,
+}
+
+/// Find the shallowest and the steepest lines of non-negative gradient that
+/// pass through every error box, if any do
+///
+/// y = mx + c with m >= 0 passes through a box iff c >= bottom - m right and
+/// c <= top - m left. The gap between the largest lower bound and the smallest
+/// upper bound of c is convex and linear between the corners of the two
+/// envelopes, so the gradients are the interval where it is not positive. This
+/// assumes the x ranges of the boxes do not all overlap
+fn rising_lines_through(boxes: &[ErrorBox]) -> Option<(Line, Line)> {
+    let lowest = upper_envelope(
+        boxes
+            .iter()
+            .map(|error_box| Line {
+                gradient: -error_box.right,
+                y_intercept: error_box.bottom,
+            })
+            .collect(),
+    );
+    let negated_highest = upper_envelope(
+        boxes
+            .iter()
+            .map(|error_box| Line {
+                gradient: error_box.left,
+                y_intercept: -error_box.top,
+            })
+            .collect(),
+    );
+    let bounds = |gradient: f64| {
+        (
+            envelope_y(&lowest, gradient),
+            -envelope_y(&negated_highest, gradient),
+        )
+    };
+    let mut corners: Vec<f64> = lowest
+        .iter()
+        .chain(&negated_highest)
+        .map(|(start, _)| *start)
+        .collect();
+    corners.sort_by(f64::total_cmp);
+    corners.dedup();
+    let gaps: Vec<f64> = corners
+        .iter()
+        .map(|gradient| {
+            let (low, high) = bounds(*gradient);
+            low - high
+        })
+        .collect();
+    let narrowest = (0..gaps.len()).min_by(|one, another| gaps[*one].total_cmp(&gaps[*another]))?;
+    let (low, high) = bounds(corners[narrowest]);
+    if low - high > 1e-9 * (1.0 + low.abs() + high.abs()) {
+        return None;
+    }
+    // Where the gap crosses zero between two corners
+    let root = |one: usize, another: usize| {
+        corners[one] + (corners[another] - corners[one]) * gaps[one] / (gaps[one] - gaps[another])
+    };
+    let shallowest = match (0..=narrowest).rev().find(|idx| gaps[*idx] > 0.0) {
+        None => corners[0],
+        Some(idx) if idx == narrowest => corners[narrowest],
+        Some(idx) => root(idx, idx + 1),
+    };
+    let steepest = match (narrowest..gaps.len()).find(|idx| gaps[*idx] > 0.0) {
+        None => {
+            // Past the last corner the gap rises as the boxes' x ranges don't
+            // all overlap
+            let last = gaps.len() - 1;
+            let rise = lowest.last()?.1.gradient + negated_highest.last()?.1.gradient;
+            corners[last] - gaps[last] / rise
+        }
+        Some(idx) if idx == narrowest => corners[narrowest],
+        Some(idx) => root(idx - 1, idx),
+    };
+    let line = |gradient: f64| {
+        let (low, high) = bounds(gradient);
+        Line {
+            gradient,
+            y_intercept: (low + high) / 2.0,
+        }
+    };
+    Some((line(shallowest), line(steepest)))
+}
+
+/// Find the upper envelope of the lines for x from 0 up, as its pieces in order
+/// with the x where each starts
+fn upper_envelope(mut lines: Vec<Line>) -> Vec<(f64, Line)> {
+    lines.sort_by(|one, another| {
+        one.gradient
+            .total_cmp(&another.gradient)
+            .then(one.y_intercept.total_cmp(&another.y_intercept))
+    });
+    let crossing = |one: &Line, another: &Line| {
+        (one.y_intercept - another.y_intercept) / (another.gradient - one.gradient)
+    };
+    let mut pieces: Vec<(f64, Line)> = Vec::with_capacity(lines.len());
+    for line in lines {
+        // A steeper line covers the last piece if it is above it where it starts
+        while let Some((start, last)) = pieces.last() {
+            if last.gradient == line.gradient || crossing(last, &line) <= *start {
+                pieces.pop();
+            } else {
+                break;
+            }
+        }
+        let start = pieces
+            .last()
+            .map_or(f64::NEG_INFINITY, |(_, last)| crossing(last, &line));
+        pieces.push((start, line));
+    }
+    let first = pieces.partition_point(|(start, _)| *start <= 0.0) - 1;
+    pieces.drain(..first);
+    pieces[0].0 = 0.0;
+    pieces
+}
+
+/// y value of the upper envelope at x >= 0
+fn envelope_y(pieces: &[(f64, Line)], x: f64) -> f64 {
+    let idx = pieces.partition_point(|(start, _)| *start <= x) - 1;
+    pieces[idx].1.y(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{FitModel, FitOptions};
+
+    /// Dataset of `(x, σx, y, σy)` points
+    fn dataset(points: &[(f64, f64, f64, f64)]) -> TwoVarDataSet {
+        TwoVarDataSet(
+            points
+                .iter()
+                .map(
+                    |&(x_value, x_uncertainty, y_value, y_uncertainty)| TwoVarDataPoint {
+                        x_value,
+                        x_uncertainty,
+                        y_value,
+                        y_uncertainty,
+                        excluded: false,
+                    },
+                )
+                .collect(),
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn two_points_have_no_residual_statistics() {
+        let data = dataset(&[(1.0, 0.1, 3.0, 0.2), (2.0, 0.1, 5.0, 0.2)]);
+        let fit = data.line_best_fit_ordinary();
+        assert_eq!(fit.line.gradient, 2.0);
+        assert_eq!(fit.line.y_intercept, 1.0);
+        assert_eq!(fit.degrees_of_freedom, 0);
+        assert_eq!(fit.residual_sd, None);
+        assert_eq!(fit.gradient_uncertainty, None);
+        assert_eq!(fit.y_intercept_uncertainty, None);
+        // The other methods take the uncertainties from the points
+        for method in [FitMethod::Weighted, FitMethod::York] {
+            let fit = data.line_fit(method).unwrap();
+            assert_eq!(fit.residual_sd, None);
+            assert!(fit.gradient_uncertainty.unwrap().is_finite());
+            assert!(fit.y_intercept_uncertainty.unwrap().is_finite());
+        }
+        let mut data = data;
+        assert_eq!(data.exclude_outliers(FitMethod::Ordinary, 1.0), 0);
+    }
+
+    #[test]
+    fn york_pearson_data() {
+        // Pearson's data with York's weights, as in York et al. (2004)
+        let x = [0.0, 0.9, 1.8, 2.6, 3.3, 4.4, 5.2, 6.1, 6.5, 7.4];
+        let x_weights: [f64; 10] = [
+            1000.0, 1000.0, 500.0, 800.0, 200.0, 80.0, 60.0, 20.0, 1.8, 1.0,
+        ];
+        let y = [5.9, 5.4, 4.4, 4.6, 3.5, 3.7, 2.8, 2.8, 2.4, 1.5];
+        let y_weights: [f64; 10] = [1.0, 1.8, 4.0, 8.0, 20.0, 20.0, 70.0, 70.0, 100.0, 500.0];
+        let points: Vec<_> = (0..x.len())
+            .map(|idx| {
+                (
+                    x[idx],
+                    x_weights[idx].powf(-0.5),
+                    y[idx],
+                    y_weights[idx].powf(-0.5),
+                )
+            })
+            .collect();
+        let fit = dataset(&points).line_best_fit_york().unwrap();
+        assert_close(fit.line.gradient, -0.4805, 5e-5);
+        assert_close(fit.gradient_uncertainty.unwrap(), 0.0580, 5e-5);
+        assert_close(fit.line.y_intercept, 5.480, 5e-4);
+        assert_close(fit.y_intercept_uncertainty.unwrap(), 0.295, 5e-4);
+        assert_eq!(fit.degrees_of_freedom, 8);
+    }
+
+    #[test]
+    fn york_converges_to_rounding() {
+        // The gradient ends up changing by a few ulp between iterations
+        let data = dataset(&[
+            (0.0, 0.5, 1.9, 0.3),
+            (1.0, 0.9, 1.1, 0.1),
+            (2.0, 0.6, 2.0, 0.7),
+            (3.0, 0.8, 1.4, 0.4),
+        ]);
+        let fit = data.line_best_fit_york().unwrap();
+        assert_close(fit.line.gradient, -0.34786, 1e-5);
+        assert_close(fit.line.y_intercept, 1.88287, 1e-5);
+    }
+
+    #[test]
+    fn york_converges_on_flat_lines() {
+        let level = dataset(&[
+            (0.0, 0.1, 3.0, 0.2),
+            (1.0, 0.2, 3.0, 0.1),
+            (2.0, 0.1, 3.0, 0.2),
+        ]);
+        let fit = level.line_best_fit_york().unwrap();
+        assert_eq!(fit.line.gradient, 0.0);
+        assert_eq!(fit.line.y_intercept, 3.0);
+        let nearly_level = dataset(&[
+            (0.0, 0.1, 3.0, 0.2),
+            (1.0, 0.2, 3.0 + 1e-13, 0.1),
+            (2.0, 0.1, 3.0, 0.2),
+            (3.0, 0.3, 3.0 - 1e-13, 0.1),
+        ]);
+        let fit = nearly_level.line_best_fit_york().unwrap();
+        assert!(fit.line.gradient.abs() < 1e-12, "{:?}", fit.line);
+    }
+
+    #[test]
+    fn york_without_uncertainties() {
+        let data = dataset(&[(0.0, 0.0, 1.0, 0.0), (1.0, 0.0, 2.0, 0.0)]);
+        assert_eq!(
+            data.line_best_fit_york().unwrap_err(),
+            LineFitError::MissingUncertainty
+        );
+    }
+
+    #[test]
+    fn weighted_fit() {
+        // Exactly on y = 2x + 1, so only the weights decide the uncertainties
+        let data = dataset(&[
+            (0.0, 0.0, 1.0, 0.5),
+            (1.0, 0.0, 3.0, 1.0),
+            (2.0, 0.0, 5.0, 0.5),
+            (4.0, 0.0, 9.0, 0.25),
+        ]);
+        let fit = data.line_best_fit_weighted().unwrap();
+        assert_close(fit.line.gradient, 2.0, 1e-12);
+        assert_close(fit.line.y_intercept, 1.0, 1e-12);
+        // Σw = 25, Σwx = 73, Σwx² = 273, Δ = 25 × 273 − 73² = 1496
+        let delta = 1496.0_f64;
+        assert_close(
+            fit.gradient_uncertainty.unwrap(),
+            (25.0 / delta).sqrt(),
+            1e-12,
+        );
+        assert_close(
+            fit.y_intercept_uncertainty.unwrap(),
+            (273.0 / delta).sqrt(),
+            1e-12,
+        );
+        assert_close(fit.correlation, -73.0 / (25.0_f64 * 273.0).sqrt(), 1e-12);
+        assert_close(fit.r_squared, 1.0, 1e-12);
+        assert_eq!(fit.degrees_of_freedom, 2);
+    }
+
+    #[test]
+    fn weighted_fit_favours_precise_points() {
+        let data = dataset(&[
+            (0.0, 0.0, 0.0, 0.01),
+            (1.0, 0.0, 5.0, 100.0),
+            (2.0, 0.0, 2.0, 0.01),
+        ]);
+        let weighted = data.line_best_fit_weighted().unwrap();
+        let ordinary = data.line_best_fit_ordinary();
+        assert_close(weighted.line.gradient, 1.0, 1e-6);
+        assert_close(weighted.line.y_intercept, 0.0, 1e-6);
+        assert_close(ordinary.line.y_intercept, 4.0 / 3.0, 1e-12);
+    }
+
+    #[test]
+    fn weighted_fit_errors() {
+        let zero = dataset(&[(0.0, 0.1, 1.0, 0.2), (1.0, 0.1, 2.0, 0.0)]);
+        assert_eq!(
+            zero.line_best_fit_weighted().unwrap_err(),
+            LineFitError::NonPositiveUncertainty
+        );
+        assert_eq!(
+            zero.line_fit(FitMethod::Weighted).unwrap_err(),
+            LineFitError::NonPositiveUncertainty
+        );
+        assert!(zero.line_best_fit_by(FitMethod::Weighted).is_none());
+        let single = dataset(&[(0.0, 0.1, 1.0, 0.2)]);
+        assert_eq!(
+            single.line_best_fit_weighted().unwrap_err(),
+            LineFitError::TooFewPoints
+        );
+        let vertical = dataset(&[(1.0, 0.1, 1.0, 0.2), (1.0, 0.1, 2.0, 0.2)]);
+        assert_eq!(
+            vertical.line_best_fit_weighted().unwrap_err(),
+            LineFitError::VerticalLine
+        );
+    }
+
+    #[test]
+    fn excluded_points_are_not_fitted() {
+        let mut data = dataset(&[
+            (0.0, 0.0, 1.0, 0.5),
+            (1.0, 0.0, 3.0, 0.5),
+            (2.0, 0.0, 0.0, 0.0),
+            (3.0, 0.0, 7.0, 0.5),
+        ]);
+        data[2].excluded = true;
+        let fit = data.line_best_fit_weighted().unwrap();
+        assert_close(fit.line.gradient, 2.0, 1e-12);
+        assert_eq!(fit.degrees_of_freedom, 1);
+    }
+
+    fn assert_line(line: Line, gradient: f64, y_intercept: f64) {
+        assert_close(line.gradient, gradient, 1e-9);
+        assert_close(line.y_intercept, y_intercept, 1e-9);
+    }
+
+    #[test]
+    fn lines_through_all_boxes() {
+        // The middle point limits both lines
+        let data = dataset(&[
+            (0.0, 0.0, 0.0, 1.0),
+            (1.0, 0.0, 0.5, 0.25),
+            (2.0, 0.0, 2.0, 1.0),
+        ]);
+        let (min_line, max_line) = data.lines_through_all().unwrap();
+        assert_line(min_line, 0.25, 0.5);
+        assert_line(max_line, 1.75, -1.0);
+        let (min_line, max_line) = data
+            .gradient_lines_by(GradientLineMethod::AllPoints)
+            .unwrap();
+        assert_line(min_line, 0.25, 0.5);
+        assert_line(max_line, 1.75, -1.0);
+    }
+
+    #[test]
+    fn lines_through_all_boxes_with_negative_gradients() {
+        let data = dataset(&[(0.0, 0.5, 2.0, 0.5), (2.0, 0.5, 0.0, 0.5)]);
+        let (min_line, max_line) = data.lines_through_all().unwrap();
+        assert_line(min_line, -3.0, 4.0);
+        assert_line(max_line, -1.0 / 3.0, 4.0 / 3.0);
+    }
+
+    #[test]
+    fn no_line_through_all_boxes() {
+        let data = dataset(&[
+            (0.0, 0.0, 0.0, 0.1),
+            (1.0, 0.0, 5.0, 0.1),
+            (2.0, 0.0, 0.0, 0.1),
+        ]);
+        assert_eq!(
+            data.lines_through_all().unwrap_err(),
+            ThroughAllError::Infeasible
+        );
+        assert_eq!(
+            data.gradient_lines_by(GradientLineMethod::AllPoints)
+                .unwrap_err(),
+            ThroughAllError::Infeasible
+        );
+    }
+
+    #[test]
+    fn vertical_line_through_all_boxes() {
+        let data = dataset(&[(0.0, 1.0, 0.0, 1.0), (1.0, 1.0, 5.0, 1.0)]);
+        assert_eq!(
+            data.lines_through_all().unwrap_err(),
+            ThroughAllError::Unbounded
+        );
+        let single = dataset(&[(0.0, 0.1, 0.0, 0.1)]);
+        assert_eq!(
+            single.lines_through_all().unwrap_err(),
+            ThroughAllError::TooFewPoints
+        );
+        assert_eq!(
+            dataset(&[])
+                .gradient_lines_by(GradientLineMethod::Endpoints)
+                .unwrap_err(),
+            ThroughAllError::TooFewPoints
+        );
+    }
+
+    /// Whether some line of the gradient passes through every error box
+    fn some_line_through_all(data: &TwoVarDataSet, gradient: f64) -> bool {
+        let (lowest, highest) = data.iter().fold(
+            (f64::NEG_INFINITY, f64::INFINITY),
+            |(lowest, highest), point| {
+                let left = gradient * (point.x_value - point.x_uncertainty);
+                let right = gradient * (point.x_value + point.x_uncertainty);
+                (
+                    lowest.max(point.y_value - point.y_uncertainty - left.max(right)),
+                    highest.min(point.y_value + point.y_uncertainty - left.min(right)),
+                )
+            },
+        );
+        lowest <= highest + 1e-9
+    }
+
+    #[test]
+    fn lines_through_all_boxes_are_the_extremes() {
+        // Noisy points about y = 2x - 1 from a fixed linear congruential generator
+        let mut state: u64 = 12345;
+        let mut random = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let (mut feasible, mut infeasible) = (0, 0);
+        for _ in 0..200 {
+            let points: Vec<(f64, f64, f64, f64)> = (0..8)
+                .map(|idx| {
+                    let x = idx as f64 + random() - 0.5;
+                    let y = 2.0 * x - 1.0 + 2.0 * random() - 1.0;
+                    (x, random(), y, 1.5 * random())
+                })
+                .collect();
+            let data = dataset(&points);
+            match data.lines_through_all() {
+                Ok((min_line, max_line)) => {
+                    feasible += 1;
+                    for line in [min_line, max_line] {
+                        assert!(some_line_through_all(&data, line.gradient));
+                        assert!(data.iter().all(|point| {
+                            let left = line.y(point.x_value - point.x_uncertainty);
+                            let right = line.y(point.x_value + point.x_uncertainty);
+                            left.min(right) <= point.y_value + point.y_uncertainty + 1e-9
+                                && left.max(right) >= point.y_value - point.y_uncertainty - 1e-9
+                        }));
+                    }
+                    assert!(!some_line_through_all(&data, min_line.gradient - 1e-6));
+                    assert!(!some_line_through_all(&data, max_line.gradient + 1e-6));
+                }
+                Err(error) => {
+                    assert_eq!(error, ThroughAllError::Infeasible);
+                    infeasible += 1;
+                    assert!(
+                        (-4000..4000).all(|idx| !some_line_through_all(&data, idx as f64 / 1000.0))
+                    );
                 }
             }
-            if fracdig == 0 {
-                result = result * 10.0 + f64::from(chr.to_digit(10).unwrap());
-            } else {
-                /* We cannot use the multiply-shift method as it may cause overflow */
-                result += f64::from(chr.to_digit(10).unwrap()) * 10_f64.powi(fracdig);
-                fracdig -= 1;
+        }
+        assert!(feasible > 0 && infeasible > 0);
+    }
+
+    #[test]
+    fn polynomial_recovers_quadratic() {
+        let points: Vec<_> = (-2..=3)
+            .map(|x| {
+                let x = f64::from(x);
+                (x, 0.0, 1.5 - 2.0 * x + 0.5 * x * x, 0.1)
+            })
+            .collect();
+        let data = dataset(&points);
+        for weighted in [false, true] {
+            let polynomial = data.polynomial_best_fit(2, weighted).unwrap();
+            assert_eq!(polynomial.degree(), 2);
+            for (actual, expected) in polynomial.coefficients.iter().zip([1.5, -2.0, 0.5]) {
+                assert_close(*actual, expected, 1e-12);
             }
-        } else if chr == '.' && fracdig == 0 {
-            // Start of decimal point
-            fracdig = -1;
-        } else if startpoint != None {
-            // Processing has started, now end it
-            endpoint = idx;
-            break;
-        }
-    }
-    if startpoint == None {
-        // Still no digits
-        None
-    } else {
-        Some((result * sign, (startpoint.unwrap(), endpoint)))
+        }
+        // A cubic term is fitted as zero
+        let cubic = data.polynomial_best_fit(3, false).unwrap();
+        assert_close(cubic.coefficients[3], 0.0, 1e-12);
+    }
+
+    #[test]
+    fn polynomial_degree_needs_enough_points() {
+        let data = dataset(&[
+            (0.0, 0.0, 1.0, 0.1),
+            (1.0, 0.0, 2.0, 0.1),
+            (2.0, 0.0, 5.0, 0.1),
+        ]);
+        // Exactly through three points
+        let quadratic = data.polynomial_best_fit(2, false).unwrap();
+        assert_close(quadratic.y(3.0), 10.0, 1e-12);
+        assert!(data.polynomial_best_fit(3, false).is_none());
+        assert!(data.polynomial_best_fit(4, true).is_none());
+    }
+
+    #[test]
+    fn polynomial_rank_deficient() {
+        // Only two distinct x values cannot determine a quadratic
+        let data = dataset(&[
+            (1.0, 0.0, 1.0, 0.1),
+            (1.0, 0.0, 2.0, 0.1),
+            (2.0, 0.0, 3.0, 0.1),
+            (2.0, 0.0, 4.0, 0.1),
+        ]);
+        assert!(data.polynomial_best_fit(1, false).is_some());
+        assert!(data.polynomial_best_fit(2, false).is_none());
+    }
+
+    #[test]
+    fn weighted_polynomial_needs_positive_uncertainties() {
+        let data = dataset(&[
+            (0.0, 0.0, 1.0, 0.1),
+            (1.0, 0.0, 2.0, 0.0),
+            (2.0, 0.0, 5.0, 0.1),
+        ]);
+        assert!(data.polynomial_best_fit(1, false).is_some());
+        assert!(data.polynomial_best_fit(1, true).is_none());
+    }
+
+    fn parse(buf: &str) -> Result<TwoVarDataSet, ParseError> {
+        TwoVarDataSet::from_string(
+            buf,
+            Uncertainty::default(),
+            Uncertainty::default(),
+            Repeats::default(),
+            DecimalSeparator::Point,
+        )
+    }
+
+    /// Check where the error in the dataset is reported and how it is shown
+    fn assert_parse_error(
+        buf: &str,
+        line: usize,
+        columns: Range<usize>,
+        kind: ParseErrorKind,
+        highlight: &str,
+    ) {
+        let error = parse(buf).unwrap_err();
+        assert_eq!(
+            (error.line, error.columns.clone(), error.kind.clone()),
+            (line, columns, kind),
+            "error in {:?}",
+            buf
+        );
+        assert_eq!(error.highlight(buf), highlight, "highlight of {:?}", buf);
+    }
+
+    #[test]
+    fn too_many_fields_are_located() {
+        // Text-only lines such as headers have no points but count as lines
+        assert_parse_error(
+            "# t v\n\n1 2 3 4 5 6\n7 8",
+            3,
+            8..11,
+            ParseErrorKind::TooManyFields(6),
+            "1 2 3 4 5 6\n        ^^^",
+        );
+        assert_parse_error(
+            "1 2\r\n\r\n1 ± 0.1, 2 ± 0.2, 3\r\n",
+            3,
+            18..19,
+            ParseErrorKind::MixedNotation,
+            "1 ± 0.1, 2 ± 0.2, 3\n                  ^",
+        );
+        assert_eq!(
+            parse("\n\n1 2 3 4 5 6").unwrap_err().to_string(),
+            "line 3, column 9: Too many fields (6), expected 2 to 4 numbers"
+        );
+    }
+
+    #[test]
+    fn negative_uncertainty_is_located() {
+        assert_parse_error(
+            "1 2\n\n# comment\n1 -0.1 2 0.2",
+            4,
+            2..6,
+            ParseErrorKind::NegativeUncertainty,
+            "1 -0.1 2 0.2\n  ^^^^",
+        );
+        assert_parse_error(
+            "1, 2 ± -0.3",
+            1,
+            7..11,
+            ParseErrorKind::NegativeUncertainty,
+            "1, 2 ± -0.3\n       ^^^^",
+        );
+    }
+
+    #[test]
+    fn non_finite_numbers_are_located() {
+        assert_parse_error(
+            "\n1e400 2",
+            2,
+            0..5,
+            ParseErrorKind::NonFinite,
+            "1e400 2\n^^^^^",
+        );
+        assert_parse_error(
+            "x y\n1 2\n3, 4 ± 1e999",
+            3,
+            3..12,
+            ParseErrorKind::NonFinite,
+            "3, 4 ± 1e999\n   ^^^^^^^^^",
+        );
+    }
+
+    #[test]
+    fn excludes_an_outlier_among_few_points() {
+        // The outlier inflates the spread of all six residuals, so that none
+        // is three of their standard deviations away from the line
+        let mut data = dataset(&[
+            (0.0, 0.0, 1.1, 0.0),
+            (1.0, 0.0, 2.9, 0.0),
+            (2.0, 0.0, 5.1, 0.0),
+            (3.0, 0.0, 12.0, 0.0),
+            (4.0, 0.0, 9.1, 0.0),
+            (5.0, 0.0, 10.9, 0.0),
+        ]);
+        let fit = data.line_best_fit_ordinary();
+        let largest = data
+            .iter()
+            .map(|point| (point.y_value - fit.line.y(point.x_value)).abs())
+            .fold(0.0, f64::max);
+        assert!(largest / fit.residual_sd.unwrap() < 3.0);
+        assert_eq!(data.exclude_outliers(FitMethod::Ordinary, 3.0), 1);
+        let excluded: Vec<bool> = data.iter().map(|point| point.excluded).collect();
+        assert_eq!(excluded, [false, false, false, true, false, false]);
+        // Nothing more is excluded the second time
+        assert_eq!(data.exclude_outliers(FitMethod::Ordinary, 3.0), 0);
+        assert_eq!(data.line_best_fit_ordinary().degrees_of_freedom, 3);
+    }
+
+    #[test]
+    fn excludes_outliers_by_the_uncertainties() {
+        let points: Vec<(f64, f64, f64, f64)> = (0..8)
+            .map(|i| {
+                let x = f64::from(i);
+                let y = if i == 5 { 2.0 * x + 3.5 } else { 2.0 * x + 1.0 };
+                (x, 0.1, y, 0.5)
+            })
+            .collect();
+        for method in [FitMethod::Weighted, FitMethod::York] {
+            let mut data = dataset(&points);
+            assert_eq!(data.exclude_outliers(method, 3.0), 1);
+            assert!(data[5].excluded);
+            // A larger threshold keeps the point
+            let mut data = dataset(&points);
+            assert_eq!(data.exclude_outliers(method, 10.0), 0);
+            assert!(!data.has_excluded());
+        }
+    }
+
+    #[test]
+    fn keeps_points_without_degrees_of_freedom() {
+        // The other two points fix the line exactly
+        let mut data = dataset(&[
+            (0.0, 0.0, 1.0, 0.1),
+            (1.0, 0.0, 9.0, 0.1),
+            (2.0, 0.0, 5.0, 0.1),
+        ]);
+        for method in [FitMethod::Ordinary, FitMethod::Weighted, FitMethod::York] {
+            assert_eq!(data.exclude_outliers(method, 1.0), 0);
+        }
+        // Points excluded already are neither compared nor counted
+        data[1].excluded = true;
+        assert_eq!(data.exclude_outliers(FitMethod::Weighted, 1.0), 0);
+        assert!(data[1].excluded);
+    }
+
+    #[test]
+    fn marker_excludes_points() {
+        let data = parse("x y\n1 2\n  * 3 4\n*5 6").unwrap();
+        let excluded: Vec<bool> = data.iter().map(|point| point.excluded).collect();
+        assert_eq!(excluded, [false, true, true]);
+        assert_eq!(data[1].x_value, 3.0);
+        assert_eq!(data[2].y_value, 6.0);
+        assert_eq!(data.included().len(), 1);
+    }
+
+    #[test]
+    fn toggles_the_marker() {
+        let buf = "x y\n1 2\n\n  3 4\n* 5 6";
+        let separator = DecimalSeparator::Point;
+        // Headers and blank lines are not counted
+        let toggled = TwoVarDataSet::toggle_excluded(buf, 1, separator).unwrap();
+        assert_eq!(toggled, "x y\n1 2\n\n  * 3 4\n* 5 6");
+        assert_eq!(
+            TwoVarDataSet::toggle_excluded(&toggled, 1, separator).unwrap(),
+            buf
+        );
+        assert_eq!(
+            TwoVarDataSet::toggle_excluded(buf, 2, separator).unwrap(),
+            "x y\n1 2\n\n  3 4\n5 6"
+        );
+        assert_eq!(
+            TwoVarDataSet::toggle_excluded("*5 6", 0, separator).unwrap(),
+            "5 6"
+        );
+        assert!(TwoVarDataSet::toggle_excluded(buf, 3, separator).is_none());
+        // The parsed points follow the marks
+        let data = parse(&toggled).unwrap();
+        let excluded: Vec<bool> = data.iter().map(|point| point.excluded).collect();
+        assert_eq!(excluded, [false, true, true]);
+    }
+
+    #[test]
+    fn residuals_carry_the_x_uncertainties() {
+        let mut data = dataset(&[
+            (0.0, 0.5, 1.5, 0.3),
+            (1.0, 0.5, 2.5, 0.3),
+            (2.0, 0.5, 4.5, 0.3),
+            (3.0, 0.5, 7.5, 0.3),
+            (4.0, 0.5, 12.0, 0.3),
+        ]);
+        data[4].excluded = true;
+        // y = 2x + 1 through the included points
+        let fits = FitResults::new(&data, &FitOptions::default());
+        let residuals = data.residuals(&fits, FitMethod::Ordinary).unwrap();
+        for (residual, expected) in residuals.iter().zip([0.5, -0.5, -0.5, 0.5, 3.0]) {
+            assert_close(residual.y_value, expected, 1e-9);
+            assert_eq!(residual.y_uncertainty, 0.3);
+        }
+        assert!(residuals[4].excluded);
+        // σ² = 0.3² + (2 × 0.5)²
+        for method in [FitMethod::Weighted, FitMethod::York] {
+            let residuals = data.residuals(&fits, method).unwrap();
+            for residual in residuals.iter() {
+                assert_close(residual.y_uncertainty, 1.09_f64.sqrt(), 1e-9);
+                assert_eq!(residual.x_uncertainty, 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn residuals_of_curves() {
+        let data = dataset(&[
+            (0.0, 0.1, 1.0, 0.2),
+            (1.0, 0.1, 2.0, 0.2),
+            (2.0, 0.1, 5.0, 0.2),
+            (3.0, 0.1, 10.0, 0.2),
+        ]);
+        // y = x² + 1, whose gradient is 2x
+        let fit = FitOptions {
+            model: FitModel::Polynomial { degree: 2 },
+            ..FitOptions::default()
+        };
+        let residuals = data
+            .residuals(&FitResults::new(&data, &fit), FitMethod::Weighted)
+            .unwrap();
+        for (residual, x) in residuals.iter().zip([0.0, 1.0, 2.0, 3.0]) {
+            assert_close(residual.y_value, 0.0, 1e-9);
+            assert_close(residual.y_uncertainty, 0.2_f64.hypot(0.2 * x), 1e-9);
+        }
+        // Nothing to take residuals from
+        let fits = FitResults::Polynomial(None);
+        assert!(data.residuals(&fits, FitMethod::Ordinary).is_none());
     }
 }
@@ -0,0 +1,419 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Significant figures of a value without a usable uncertainty
+const FALLBACK_DIGITS: i32 = 6;
+
+/// SI prefixes from 10⁻²⁴ to 10²⁴, one for every third power of ten
+const SI_PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
+
+/// How the powers of ten of a value are written
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Notation {
+    /// Plain decimals, or scientific notation for values below 10⁻³ or
+    /// from 10⁵ up
+    #[default]
+    Auto,
+    Plain,
+    /// Such as (1.23 ± 0.04) × 10⁵
+    Scientific,
+    /// Such as (123 ± 4) k, falling back to scientific notation beyond the
+    /// prefixes
+    SiPrefix,
+}
+
+impl Notation {
+    /// All notations, in the order shown to the user
+    pub const ALL: [Notation; 4] = [
+        Notation::Auto,
+        Notation::Plain,
+        Notation::Scientific,
+        Notation::SiPrefix,
+    ];
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notation::Auto => write!(f, "auto"),
+            Notation::Plain => write!(f, "plain"),
+            Notation::Scientific => write!(f, "scientific"),
+            Notation::SiPrefix => write!(f, "si-prefix"),
+        }
+    }
+}
+
+/// Error when converting from str to `Notation`
+#[derive(Clone, Debug)]
+pub enum NotationFromStrError {
+    UnknownNotation(String),
+}
+
+impl fmt::Display for NotationFromStrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationFromStrError::UnknownNotation(nstr) => {
+                write!(f, "Unknown notation: {}", nstr)
+            }
+        }
+    }
+}
+
+impl FromStr for Notation {
+    type Err = NotationFromStrError;
+
+    /// Parse notation description
+    fn from_str(nstr: &str) -> Result<Self, Self::Err> {
+        match nstr.to_lowercase().as_str() {
+            "auto" => Ok(Notation::Auto),
+            "plain" => Ok(Notation::Plain),
+            "scientific" | "sci" => Ok(Notation::Scientific),
+            "si-prefix" | "si" => Ok(Notation::SiPrefix),
+            other => Err(Self::Err::UnknownNotation(other.to_string())),
+        }
+    }
+}
+
+/// Exponent of the leading digit of a non-zero value
+fn exponent(value: f64) -> i32 {
+    let magnitude = value.abs();
+    let exponent = magnitude.log10().floor() as i32;
+    // log10 may be off by a little at powers of ten
+    if magnitude >= 10.0_f64.powi(exponent + 1) {
+        exponent + 1
+    } else if magnitude < 10.0_f64.powi(exponent) {
+        exponent - 1
+    } else {
+        exponent
+    }
+}
+
+/// Write the exponent in superscript digits
+fn superscript(exponent: i32) -> String {
+    exponent
+        .to_string()
+        .chars()
+        .map(|digit| match digit {
+            '-' => '⁻',
+            '0' => '⁰',
+            '1' => '¹',
+            '2' => '²',
+            '3' => '³',
+            '4' => '⁴',
+            '5' => '⁵',
+            '6' => '⁶',
+            '7' => '⁷',
+            '8' => '⁸',
+            _ => '⁹',
+        })
+        .collect()
+}
+
+/// A value with its uncertainty, written to the place of the leading digit of
+/// the uncertainty, or of the next digit if the leading one is 1, as results
+/// are given in lab reports. Values without a usable uncertainty are written
+/// to six significant figures
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct UncertainValue {
+    pub value: f64,
+    pub uncertainty: f64,
+    pub notation: Notation,
+}
+
+impl UncertainValue {
+    #[must_use]
+    pub fn new(value: f64, uncertainty: f64, notation: Notation) -> Self {
+        Self {
+            value,
+            uncertainty,
+            notation,
+        }
+    }
+
+    /// A value without an uncertainty, such as a statistic or a coefficient,
+    /// written to six significant figures
+    #[must_use]
+    pub fn without_uncertainty(value: f64, notation: Notation) -> Self {
+        Self::new(value, 0.0, notation)
+    }
+
+    /// Whether the uncertainty decides the place of the last digit
+    fn uncertainty_usable(&self) -> bool {
+        self.uncertainty.is_finite() && self.uncertainty > 0.0
+    }
+
+    /// Exponent of the place of the last digit written
+    fn last_place(&self) -> i32 {
+        if self.uncertainty_usable() {
+            let leading = exponent(self.uncertainty);
+            // Two digits if the leading one is 1 after rounding to two digits,
+            // as 0.19 stays 0.19 but 0.195 becomes 0.2. Rounding to one digit
+            // may carry into the next place, as 0.96 becomes 1.0, which has
+            // two digits already
+            let digits = (self.uncertainty / 10.0_f64.powi(leading - 1)).round();
+            if digits < 20.0 {
+                leading - 1
+            } else {
+                leading
+            }
+        } else if self.value != 0.0 && self.value.is_finite() {
+            exponent(self.value) - (FALLBACK_DIGITS - 1)
+        } else {
+            0
+        }
+    }
+
+    /// Round to a multiple of the power of ten
+    fn round_to(value: f64, place: i32) -> f64 {
+        // Powers of ten below one are inexact, so those places are scaled up
+        if place < 0 {
+            let scale = 10.0_f64.powi(-place);
+            (value * scale).round() / scale
+        } else {
+            let scale = 10.0_f64.powi(place);
+            (value / scale).round() * scale
+        }
+    }
+
+    /// The value rounded to the place of its last digit written
+    #[must_use]
+    pub fn rounded_value(&self) -> f64 {
+        Self::round_to(self.value, self.last_place())
+    }
+
+    /// Power of ten taken out of the value and the uncertainty, and how it is
+    /// written after them
+    fn power(&self) -> (i32, String) {
+        if !self.value.is_finite() {
+            return (0, String::new());
+        }
+        let last = self.last_place();
+        let rounded = self.rounded_value();
+        let leading = if rounded != 0.0 {
+            exponent(rounded)
+        } else if self.uncertainty_usable() {
+            exponent(Self::round_to(self.uncertainty, last))
+        } else {
+            0
+        };
+        let scientific = |leading: i32| {
+            if leading == 0 {
+                (0, String::new())
+            } else {
+                (leading, format!(" × 10{}", superscript(leading)))
+            }
+        };
+        match self.notation {
+            Notation::Plain => (0, String::new()),
+            Notation::Auto if (-3..5).contains(&leading) => (0, String::new()),
+            Notation::Auto | Notation::Scientific => scientific(leading),
+            Notation::SiPrefix => {
+                let power = leading.div_euclid(3) * 3;
+                if !(-24..=24).contains(&power) {
+                    scientific(leading)
+                } else if power == 0 {
+                    (0, String::new())
+                } else {
+                    let prefix = SI_PREFIXES[((power + 24) / 3) as usize];
+                    (power, format!(" {}", prefix))
+                }
+            }
+        }
+    }
+
+    /// Write a number divided by the power of ten to the place of the last digit
+    fn write_scaled(&self, number: f64, power: i32) -> String {
+        let last = self.last_place();
+        let decimals = (power - last).max(0) as usize;
+        let scaled = Self::round_to(number, last) / 10.0_f64.powi(power);
+        let written = format!("{:.*}", decimals, scaled);
+        // Rounding may leave a negative zero
+        match written.strip_prefix('-') {
+            Some(magnitude) if magnitude.chars().all(|c| c == '0' || c == '.') => {
+                magnitude.to_string()
+            }
+            _ => written,
+        }
+    }
+
+    /// The value alone, to the same place as with the uncertainty
+    #[must_use]
+    pub fn value_string(&self) -> String {
+        let (power, suffix) = self.power();
+        format!("{}{}", self.write_scaled(self.value, power), suffix)
+    }
+
+    /// The value alone, in brackets if it has a power of ten or a prefix so
+    /// that it can be multiplied, such as by x in an equation
+    #[must_use]
+    pub fn value_term(&self) -> String {
+        let (power, suffix) = self.power();
+        if suffix.is_empty() {
+            self.write_scaled(self.value, power)
+        } else {
+            format!("({}{})", self.write_scaled(self.value, power), suffix)
+        }
+    }
+}
+
+impl fmt::Display for UncertainValue {
+    /// Such as 1.23 ± 0.04, or with only the value if there is no usable
+    /// uncertainty
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.uncertainty_usable() {
+            return write!(f, "{}", self.value_string());
+        }
+        let (power, suffix) = self.power();
+        let value = self.write_scaled(self.value, power);
+        let uncertainty = self.write_scaled(self.uncertainty, power);
+        if suffix.is_empty() {
+            write!(f, "{} ± {}", value, uncertainty)
+        } else {
+            write!(f, "({} ± {}){}", value, uncertainty, suffix)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(value: f64, uncertainty: f64, notation: Notation) -> String {
+        UncertainValue::new(value, uncertainty, notation).to_string()
+    }
+
+    #[test]
+    fn one_digit_of_uncertainty() {
+        assert_eq!(written(1.234, 0.27, Notation::Auto), "1.2 ± 0.3");
+        assert_eq!(written(1.234, 0.05, Notation::Auto), "1.23 ± 0.05");
+        assert_eq!(written(1234.0, 40.0, Notation::Plain), "1230 ± 40");
+        assert_eq!(written(-5.678, 0.9, Notation::Auto), "-5.7 ± 0.9");
+    }
+
+    #[test]
+    fn two_digits_if_leading_one() {
+        assert_eq!(written(1.234, 0.0123, Notation::Auto), "1.234 ± 0.012");
+        assert_eq!(written(1.234, 0.19, Notation::Auto), "1.23 ± 0.19");
+        assert_eq!(written(1.234, 0.195, Notation::Auto), "1.2 ± 0.2");
+        assert_eq!(written(2.5, 1.0, Notation::Auto), "2.5 ± 1.0");
+        // 0.096 rounds up to a leading 1, which keeps the second digit
+        assert_eq!(written(9.96, 0.096, Notation::Auto), "9.96 ± 0.10");
+    }
+
+    #[test]
+    fn carries_into_the_next_place() {
+        assert_eq!(written(9.96, 0.3, Notation::Auto), "10.0 ± 0.3");
+        assert_eq!(written(-9.96, 0.3, Notation::Auto), "-10.0 ± 0.3");
+        // The uncertainty keeps the value to the hundredths
+        assert_eq!(written(9.96, 0.05, Notation::Auto), "9.96 ± 0.05");
+        assert_eq!(
+            written(9.996e5, 3e3, Notation::Scientific),
+            "(1.000 ± 0.003) × 10⁶"
+        );
+        assert_eq!(written(999.6, 3.0, Notation::SiPrefix), "(1.000 ± 0.003) k");
+    }
+
+    #[test]
+    fn without_usable_uncertainty() {
+        for uncertainty in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(written(1.23456789, uncertainty, Notation::Auto), "1.23457");
+        }
+        assert_eq!(written(12345678.9, 0.0, Notation::Auto), "1.23457 × 10⁷");
+        assert_eq!(written(0.0, 0.0, Notation::Auto), "0");
+        let tiny = UncertainValue::without_uncertainty(4.2e-7, Notation::Auto);
+        assert_eq!(tiny.to_string(), "4.20000 × 10⁻⁷");
+    }
+
+    #[test]
+    fn no_negative_zero() {
+        assert_eq!(written(-0.004, 0.3, Notation::Auto), "0.0 ± 0.3");
+    }
+
+    #[test]
+    fn automatic_notation() {
+        assert_eq!(written(12345.0, 20.0, Notation::Auto), "12350 ± 20");
+        assert_eq!(
+            written(123456.0, 200.0, Notation::Auto),
+            "(1.235 ± 0.002) × 10⁵"
+        );
+        assert_eq!(
+            written(0.00123, 0.00002, Notation::Auto),
+            "0.00123 ± 0.00002"
+        );
+        assert_eq!(
+            written(0.000123, 0.000002, Notation::Auto),
+            "(1.23 ± 0.02) × 10⁻⁴"
+        );
+        assert_eq!(written(123456.0, 200.0, Notation::Plain), "123500 ± 200");
+    }
+
+    #[test]
+    fn si_prefixes() {
+        assert_eq!(written(999.5, 0.4, Notation::SiPrefix), "999.5 ± 0.4");
+        assert_eq!(written(1234.0, 20.0, Notation::SiPrefix), "(1.23 ± 0.02) k");
+        assert_eq!(
+            written(0.00123, 0.00002, Notation::SiPrefix),
+            "(1.23 ± 0.02) m"
+        );
+        assert_eq!(written(4.7e-6, 2e-7, Notation::SiPrefix), "(4.7 ± 0.2) µ");
+        // The largest and the smallest prefixes
+        assert_eq!(
+            written(1.5e24, 1e22, Notation::SiPrefix),
+            "(1.500 ± 0.010) Y"
+        );
+        assert_eq!(
+            written(1.5e-24, 2e-26, Notation::SiPrefix),
+            "(1.50 ± 0.02) y"
+        );
+        // Scientific notation beyond them
+        assert_eq!(
+            written(1.5e27, 1e25, Notation::SiPrefix),
+            "(1.500 ± 0.010) × 10²⁷"
+        );
+        assert_eq!(
+            written(1.5e-25, 2e-26, Notation::SiPrefix),
+            "(1.5 ± 0.2) × 10⁻²⁵"
+        );
+    }
+
+    #[test]
+    fn value_alone() {
+        let value = UncertainValue::new(1234.0, 20.0, Notation::SiPrefix);
+        assert_eq!(value.value_string(), "1.23 k");
+        assert_eq!(value.value_term(), "(1.23 k)");
+        assert_eq!(value.rounded_value(), 1230.0);
+        let value = UncertainValue::new(1.234, 0.05, Notation::Auto);
+        assert_eq!(value.value_term(), "1.23");
+    }
+
+    #[test]
+    fn notation_from_str() {
+        for notation in Notation::ALL {
+            assert_eq!(notation.to_string().parse::<Notation>().unwrap(), notation);
+        }
+        assert_eq!("SI".parse::<Notation>().unwrap(), Notation::SiPrefix);
+        assert_eq!("sci".parse::<Notation>().unwrap(), Notation::Scientific);
+        assert!("engineering".parse::<Notation>().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::tokenizer::{tokenize, DecimalSeparator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+
+/// An uncertainty made of a percentage of the value plus an absolute part,
+/// as in `0.5% + 0.01`
+#[derive(PartialEq, Debug, Default, Copy, Clone)]
+pub struct Uncertainty {
+    /// Percentage of the absolute value
+    pub percentage: f64,
+    pub absolute: f64,
+}
+
+impl Uncertainty {
+    /// The uncertainty of a particular value
+    #[must_use]
+    pub fn of(self, value: f64) -> f64 {
+        self.absolute + self.percentage / 100.0 * value.abs()
+    }
+}
+
+impl From<f64> for Uncertainty {
+    fn from(absolute: f64) -> Self {
+        Self {
+            percentage: 0.0,
+            absolute,
+        }
+    }
+}
+
+impl std::fmt::Display for Uncertainty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.percentage == 0.0 {
+            write!(f, "{}", self.absolute)
+        } else if self.absolute == 0.0 {
+            write!(f, "{}%", self.percentage)
+        } else {
+            write!(f, "{}% + {}", self.percentage, self.absolute)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum UncertaintyFromStrError {
+    InvalidTerm(String),
+    Negative(String),
+}
+
+impl std::fmt::Display for UncertaintyFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UncertaintyFromStrError::InvalidTerm(term) => {
+                write!(f, "{:?} is not a number or percentage", term)
+            }
+            UncertaintyFromStrError::Negative(term) => {
+                write!(f, "{:?} is negative", term)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UncertaintyFromStrError {}
+
+/// Split a sum into its terms at the `+` signs, except those of exponents
+/// such as in `1e+3`
+fn terms(ustr: &str) -> Vec<String> {
+    let chars: Vec<char> = ustr.chars().collect();
+    let mut terms = vec![String::new()];
+    for (idx, chr) in chars.iter().enumerate() {
+        let in_exponent = idx >= 2
+            && matches!(chars[idx - 1], 'e' | 'E')
+            && matches!(chars[idx - 2], '0'..='9' | '.' | ',')
+            && matches!(chars.get(idx + 1), Some('0'..='9'));
+        if *chr == '+' && !in_exponent {
+            terms.push(String::new());
+        } else if let Some(term) = terms.last_mut() {
+            term.push(*chr);
+        }
+    }
+    terms
+}
+
+impl FromStr for Uncertainty {
+    type Err = UncertaintyFromStrError;
+
+    /// Parse a sum of percentages and absolute values such as `2%`,
+    /// `0.5% + 0.01` or `0.01`. Numbers are read as in data files, so a decimal
+    /// comma is used if the text has a comma
+    fn from_str(ustr: &str) -> Result<Self, Self::Err> {
+        let separator = if ustr.contains(',') {
+            DecimalSeparator::Comma
+        } else {
+            DecimalSeparator::Point
+        };
+        let mut result = Self::default();
+        for term in terms(ustr) {
+            let term = term.trim();
+            let invalid = || Self::Err::InvalidTerm(term.to_string());
+            // The term must be a single number with nothing else around it
+            let measurement = match tokenize(term, separator).map_err(|_| invalid())?[..] {
+                [ref measurement] => measurement.clone(),
+                _ => return Err(invalid()),
+            };
+            if measurement.columns != (0..term.chars().count())
+                || measurement.uncertainty.is_some()
+                || !measurement.value.is_finite()
+            {
+                return Err(invalid());
+            }
+            if measurement.value < 0.0 {
+                return Err(Self::Err::Negative(term.to_string()));
+            }
+            if measurement.percentage {
+                result.percentage += measurement.value;
+            } else {
+                result.absolute += measurement.value;
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Plain absolute uncertainties are saved as numbers, as they were before
+/// percentages were introduced
+impl Serialize for Uncertainty {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.percentage == 0.0 {
+            serializer.serialize_f64(self.absolute)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Uncertainty {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Saved {
+            Absolute(f64),
+            Text(String),
+        }
+        match Saved::deserialize(deserializer)? {
+            Saved::Absolute(absolute) => Ok(Self::from(absolute)),
+            Saved::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(ustr: &str) -> (f64, f64) {
+        let uncertainty: Uncertainty = ustr
+            .parse()
+            .unwrap_or_else(|error| panic!("{:?} not parsed: {}", ustr, error));
+        (uncertainty.percentage, uncertainty.absolute)
+    }
+
+    #[test]
+    fn sums_of_terms() {
+        assert_eq!(parse("0.01"), (0.0, 0.01));
+        assert_eq!(parse("2%"), (2.0, 0.0));
+        assert_eq!(parse("0.5% + 0.01"), (0.5, 0.01));
+        assert_eq!(parse(" 1 %+2+ 3 "), (1.0, 5.0));
+    }
+
+    #[test]
+    fn exponents_with_plus() {
+        assert_eq!(parse("1e+3"), (0.0, 1000.0));
+        assert_eq!(parse("2.5e+2%"), (250.0, 0.0));
+        assert_eq!(parse("1E+1% + 5e+0"), (10.0, 5.0));
+    }
+
+    #[test]
+    fn decimal_comma() {
+        assert_eq!(parse("0,5% + 0,01"), (0.5, 0.01));
+        assert_eq!(parse("2,5e+2%"), (250.0, 0.0));
+    }
+
+    #[test]
+    fn negative_terms() {
+        for ustr in ["-1", "\u{2212}1", "2% + \u{2212}0.5%", "1e+3 + -1e-3"] {
+            assert!(
+                matches!(
+                    ustr.parse::<Uncertainty>(),
+                    Err(UncertaintyFromStrError::Negative(_))
+                ),
+                "{:?} is negative",
+                ustr
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_terms() {
+        for ustr in [
+            "", "1 +", "+ 1", "a", "1 2", "1e", "1 ± 0.1", "2%%", "1e999",
+        ] {
+            assert!(
+                matches!(
+                    ustr.parse::<Uncertainty>(),
+                    Err(UncertaintyFromStrError::InvalidTerm(_))
+                ),
+                "{:?} is invalid",
+                ustr
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for uncertainty in [
+            Uncertainty::from(0.25),
+            Uncertainty {
+                percentage: 1.5,
+                absolute: 0.0,
+            },
+            Uncertainty {
+                percentage: 0.5,
+                absolute: 1e-7,
+            },
+        ] {
+            assert_eq!(
+                uncertainty.to_string().parse::<Uncertainty>().unwrap(),
+                uncertainty
+            );
+        }
+    }
+}
@@ -5,5 +5,21 @@ pub const Y_LABEL: &str = "y";
 pub const X_UNCERTAINTY: &str = "0.01";
 pub const Y_UNCERTAINTY: &str = "0.01";
 pub const BACKEND: &str = "plotters";
+pub const FIT_METHOD: &str = "ordinary";
+pub const GRADIENT_LINE_METHOD: &str = "endpoints";
+pub const POLYNOMIAL_DEGREE: &str = "1";
+pub const TRANSFORM: &str = "none";
+pub const AXIS_SCALE: &str = "linear";
+pub const DECIMAL_SEPARATOR: &str = "point";
+pub const REPEAT_UNCERTAINTY: &str = "half-range";
+pub const RESOLUTION: &str = "0";
+pub const OUTLIER_THRESHOLD: &str = "0";
+pub const LEGEND_POSITION: &str = "upper-right";
+pub const NOTATION: &str = "auto";
+pub const WIDTH: &str = "960";
+pub const HEIGHT: &str = "540";
+pub const FONT_FAMILY: &str = "Times";
+pub const TITLE_FONT_SIZE: &str = "22";
+pub const LABEL_FONT_SIZE: &str = "13";
 pub const APP_ID: &str = "xyz.myzhangll.Physics.Plotter";
 pub const COPYRIGHT: &str = "Copyright (C) 2021-2022 Zhang Maiyun.";
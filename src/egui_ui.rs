@@ -1,24 +1,40 @@
-use super::data::TwoVarDataSet;
+use super::data;
+use super::data::{
+    ColumnMapping, DecimalSeparator, Delimiter, FitMethod, FitModel, FitOptions, FitResults,
+    GradientLineMethod, Notation, RepeatUncertainty, Repeats, Table, TableColumn, Transform,
+    Transforms, TwoVarDataPoint, TwoVarDataSet, Uncertainty, UncertaintyFromStrError,
+};
 use super::default_values as defv;
 use super::plot;
-use super::plot::Backends;
-use super::save_format::PhysPlotterFile;
+use super::plot::{
+    axis_ranges, clipped_bar, fit_x_range, line_intercepts, samples, AxisScale, Backends, Colour,
+    FittedSeries, LegendPosition, Marker, PlotSpec, Series, SeriesStyle, TickSpacing,
+    CURVE_SAMPLES,
+};
+use super::save_format::{PhysPlotterFile, SavedSeries};
 use clap::crate_version;
 use eframe::egui::{
     self,
-    plot::{Legend, Line, LineStyle, MarkerShape, Plot, Points, Value, Values},
-    Color32,
+    plot::{
+        Corner, HLine, Legend, Line, LineStyle, MarkerShape, Plot, Points, Text, Value, Values,
+    },
+    Align2, Color32,
 };
 #[cfg(target_arch = "wasm32")]
 use futures::executor::block_on;
-use plotters::prelude::*;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::Read;
 #[cfg(not(target_arch = "wasm32"))]
 use std::io::Write;
-use std::ops::Deref;
+use std::ops::{Deref, Range, RangeInclusive};
+use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
+
+/// Largest distance of a click from a point toggling its exclusion,
+/// as a fraction of the size of the plot
+const CLICK_DISTANCE: f64 = 0.02;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[serde(default)]
@@ -34,14 +50,241 @@ pub struct App {
     /// If is a Some(), the error message is wrapped.
     #[cfg_attr(feature = "persistence", serde(skip))]
     error: Option<String>,
+    /// If is a Some(), a table is being imported into the current series.
+    #[serde(skip)]
+    import: Option<TableImport>,
+    /// The series parsed and fitted for the preview, kept between frames
+    #[serde(skip)]
+    fitted: Option<FittedInputs>,
 
     backend: Backends,
-    title: String,
+    spec: PlotSpec,
+    transforms: Transforms,
+    series: Vec<SeriesInput>,
+    /// Index of the series being edited
+    current_series: usize,
+}
+
+/// Series parsed and fitted from inputs, which are reused until the inputs change
+#[derive(Debug, Clone)]
+struct FittedInputs {
+    series: Vec<SeriesInput>,
+    transforms: Transforms,
+    fit: FitOptions,
+    fitted: Result<Rc<Vec<FittedSeries>>, String>,
+}
+
+/// A series as being edited
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+struct SeriesInput {
+    name: String,
     dataset: String,
-    x_label: String,
-    y_label: String,
     default_x_uncertainty: String,
     default_y_uncertainty: String,
+    #[serde(default)]
+    decimal_separator: DecimalSeparator,
+    #[serde(default)]
+    repeats: Repeats,
+    style: SeriesStyle,
+}
+
+impl SeriesInput {
+    /// Create the nth series with default values
+    fn nth(index: usize) -> Self {
+        Self {
+            name: String::default(),
+            dataset: String::default(),
+            default_x_uncertainty: String::from(defv::X_UNCERTAINTY),
+            default_y_uncertainty: String::from(defv::Y_UNCERTAINTY),
+            decimal_separator: DecimalSeparator::from_str(defv::DECIMAL_SEPARATOR).unwrap(),
+            repeats: Repeats {
+                uncertainty: RepeatUncertainty::from_str(defv::REPEAT_UNCERTAINTY).unwrap(),
+                resolution: defv::RESOLUTION.parse().unwrap(),
+            },
+            style: SeriesStyle::nth(index),
+        }
+    }
+
+    /// Name shown in the series selection
+    fn title(&self, index: usize) -> String {
+        if self.name.is_empty() {
+            format!("Series {}", index + 1)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    fn parse(&self, transforms: Transforms) -> Result<Series, String> {
+        match (
+            self.default_x_uncertainty.parse(),
+            self.default_y_uncertainty.parse(),
+        ) {
+            (Ok(dux), Ok(duy)) => {
+                match TwoVarDataSet::from_string(
+                    &self.dataset,
+                    dux,
+                    duy,
+                    self.repeats,
+                    self.decimal_separator,
+                ) {
+                    Ok(dataset) => {
+                        // Empty values can crash some backends
+                        if dataset.is_empty() {
+                            Err(String::from("Empty dataset"))
+                        } else {
+                            Ok(Series {
+                                name: self.name.clone(),
+                                data: dataset
+                                    .transform(transforms)
+                                    .map_err(|error| format!("Invalid transform: {}", error))?,
+                                style: self.style,
+                            })
+                        }
+                    }
+                    Err(error) => Err(format!("Invalid dataset: {}", error)),
+                }
+            }
+            (Err(_), _) => Err(format!(
+                "Invalid x uncertainty: {}",
+                self.default_x_uncertainty
+            )),
+            (_, Err(_)) => Err(format!(
+                "Invalid y uncertainty: {}",
+                self.default_y_uncertainty
+            )),
+        }
+    }
+}
+
+/// A table being imported, with the columns chosen so far
+#[derive(Debug, Clone)]
+struct TableImport {
+    text: String,
+    delimiter: Delimiter,
+    decimal_separator: DecimalSeparator,
+    table: Table,
+    x: usize,
+    x_uncertainty: Option<usize>,
+    y: usize,
+    y_uncertainty: Option<usize>,
+}
+
+impl TableImport {
+    fn new(text: String) -> Self {
+        let delimiter = Delimiter::detect(&text);
+        let decimal_separator = DecimalSeparator::from_str(defv::DECIMAL_SEPARATOR).unwrap();
+        let table = Table::parse(&text, Some(delimiter), decimal_separator);
+        Self {
+            text,
+            delimiter,
+            decimal_separator,
+            table,
+            x: 0,
+            x_uncertainty: None,
+            y: 1,
+            y_uncertainty: None,
+        }
+    }
+
+    /// Parse the text again after the delimiter or decimal separator changed
+    fn reparse(&mut self) {
+        self.table = Table::parse(&self.text, Some(self.delimiter), self.decimal_separator);
+    }
+
+    fn mapping(&self) -> ColumnMapping {
+        ColumnMapping {
+            x: TableColumn::Index(self.x),
+            x_uncertainty: self.x_uncertainty.map(TableColumn::Index),
+            y: TableColumn::Index(self.y),
+            y_uncertainty: self.y_uncertainty.map(TableColumn::Index),
+        }
+    }
+}
+
+/// Combo box choosing one column of `table`
+fn column_choice(ui: &mut egui::Ui, label: &str, table: &Table, column: &mut usize) {
+    egui::ComboBox::from_label(label)
+        .selected_text(table.column_title(*column))
+        .show_ui(ui, |ui| {
+            for index in 0..table.column_count() {
+                ui.selectable_value(column, index, table.column_title(index));
+            }
+        });
+}
+
+/// Combo box choosing an optional column of `table`
+fn optional_column_choice(
+    ui: &mut egui::Ui,
+    label: &str,
+    table: &Table,
+    column: &mut Option<usize>,
+) {
+    let selected = column.map_or_else(|| String::from("None"), |index| table.column_title(index));
+    egui::ComboBox::from_label(label)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(column, None, "None");
+            for index in 0..table.column_count() {
+                ui.selectable_value(column, Some(index), table.column_title(index));
+            }
+        });
+}
+
+/// Checkbox fixing the range of an axis, with inputs for its ends.
+/// Returns whether anything changed
+fn range_input(ui: &mut egui::Ui, label: &str, range: &mut Option<Range<f64>>) -> bool {
+    let mut fixed = range.is_some();
+    let mut changed = ui.checkbox(&mut fixed, label).changed();
+    if changed {
+        *range = fixed.then_some(0.0..10.0);
+    }
+    if let Some(range) = range {
+        changed |= ui
+            .add(egui::DragValue::new(&mut range.start).speed(0.1))
+            .changed();
+        ui.label("to");
+        changed |= ui
+            .add(egui::DragValue::new(&mut range.end).speed(0.1))
+            .changed();
+    }
+    changed
+}
+
+/// Checkboxes setting the spacing of the ticks of an axis, with inputs for
+/// the spacings. Returns whether anything changed
+fn ticks_input(ui: &mut egui::Ui, label: &str, ticks: &mut TickSpacing) -> bool {
+    let mut set = ticks.major.is_some();
+    let mut changed = ui.checkbox(&mut set, label).changed();
+    if changed {
+        *ticks = TickSpacing {
+            major: set.then_some(1.0),
+            minor: None,
+        };
+    }
+    if let Some(major) = &mut ticks.major {
+        changed |= ui
+            .add(
+                egui::DragValue::new(major)
+                    .clamp_range(0.0..=f64::INFINITY)
+                    .speed(0.1),
+            )
+            .changed();
+        let mut minor_set = ticks.minor.is_some();
+        if ui.checkbox(&mut minor_set, "Minor").changed() {
+            ticks.minor = minor_set.then_some(*major / 5.0);
+            changed = true;
+        }
+        if let Some(minor) = &mut ticks.minor {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(minor)
+                        .clamp_range(0.0..=f64::INFINITY)
+                        .speed(0.1),
+                )
+                .changed();
+        }
+    }
+    changed
 }
 
 impl Default for App {
@@ -53,14 +296,17 @@ impl Default for App {
             show_confirm_then_open: false,
             file_path: String::default(),
             error: None,
+            import: None,
+            fitted: None,
 
             backend: Backends::from_str(defv::BACKEND).unwrap(),
-            title: String::from(defv::TITLE),
-            dataset: String::default(),
-            x_label: String::from(defv::X_LABEL),
-            y_label: String::from(defv::Y_LABEL),
-            default_x_uncertainty: String::from(defv::X_UNCERTAINTY),
-            default_y_uncertainty: String::from(defv::Y_UNCERTAINTY),
+            spec: PlotSpec::default(),
+            transforms: Transforms {
+                x_transform: Transform::from_str(defv::TRANSFORM).unwrap(),
+                y_transform: Transform::from_str(defv::TRANSFORM).unwrap(),
+            },
+            series: vec![SeriesInput::nth(0)],
+            current_series: 0,
         }
     }
 }
@@ -76,6 +322,7 @@ impl eframe::App for App {
         self.draw_preview_area(ctx);
         self.draw_about_window(ctx);
         self.draw_confirm_window(ctx);
+        self.draw_import_window(ctx);
         self.draw_error_window(ctx);
         self.ui_file_drag_and_drop(ctx);
     }
@@ -107,6 +354,9 @@ impl App {
                         self.show_confirm_then_open = true;
                     }
                 }
+                if ui.button("Import Table").clicked() {
+                    self.import_table();
+                }
                 if ui.button("Save").clicked() {
                     self.save(false);
                 }
@@ -116,6 +366,15 @@ impl App {
                 if ui.button("Plot").clicked() {
                     self.plot();
                 }
+                // The web version cannot run GNU Plot or write files
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Export").clicked() {
+                    self.export();
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Export Script").clicked() {
+                    self.export_script();
+                }
                 if ui.button("About").clicked() {
                     self.show_about = true;
                 }
@@ -126,13 +385,27 @@ impl App {
     fn draw_side_panel(&mut self, ctx: &egui::Context) {
         let Self {
             saved,
-            title,
-            x_label,
-            y_label,
-            dataset,
-            default_x_uncertainty,
-            default_y_uncertainty,
+            spec:
+                PlotSpec {
+                    title,
+                    x_label,
+                    y_label,
+                    fit,
+                    axis_scale,
+                    x_range,
+                    y_range,
+                    include_origin,
+                    fit_x_range,
+                    annotate_intercepts,
+                    notation,
+                    x_ticks,
+                    y_ticks,
+                    ..
+                },
+            series,
+            current_series,
             backend,
+            transforms,
             ..
         } = self;
 
@@ -158,37 +431,347 @@ impl App {
                 }
             });
             ui.horizontal(|ui| {
-                ui.label("Default x uncertainty");
-                if ui.text_edit_singleline(default_x_uncertainty).changed() {
+                ui.label("Plotting backend");
+                for choice in Backends::all() {
+                    ui.selectable_value(backend, choice, choice.display_name());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Fit model");
+                let model = &mut fit.model;
+                let line = ui.selectable_value(model, FitModel::Line, "Straight line");
+                // Keep the degree when already fitting a polynomial
+                let degree = match *model {
+                    FitModel::Polynomial { degree } => degree,
+                    _ => 2,
+                };
+                let polynomial =
+                    ui.selectable_value(model, FitModel::Polynomial { degree }, "Polynomial");
+                // Keep the expression when already fitting one
+                let expression_model = match *model {
+                    FitModel::Expression { .. } => model.clone(),
+                    _ => FitModel::Expression {
+                        expression: String::default(),
+                        initial_guesses: String::default(),
+                    },
+                };
+                let expression = ui.selectable_value(model, expression_model, "Expression");
+                if line.changed() || polynomial.changed() || expression.changed() {
+                    *saved = false;
+                }
+                if let FitModel::Polynomial { degree } = model {
+                    let degree_input = egui::DragValue::new(degree)
+                        .clamp_range(0..=10)
+                        .prefix("degree ");
+                    if ui.add(degree_input).changed() {
+                        *saved = false;
+                    }
+                }
+            });
+            if let FitModel::Expression {
+                expression,
+                initial_guesses,
+            } = &mut fit.model
+            {
+                ui.horizontal(|ui| {
+                    ui.label("y =");
+                    let expression_input =
+                        egui::TextEdit::singleline(expression).hint_text("a*exp(-x/tau)+c");
+                    if ui.add(expression_input).changed() {
+                        *saved = false;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Initial guesses");
+                    let guesses_input =
+                        egui::TextEdit::singleline(initial_guesses).hint_text("a=1, tau=2");
+                    if ui.add(guesses_input).changed() {
+                        *saved = false;
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("Best-fit method");
+                let fit_method = &mut fit.fit_method;
+                let ordinary = ui.selectable_value(fit_method, FitMethod::Ordinary, "Ordinary");
+                let weighted = ui.selectable_value(fit_method, FitMethod::Weighted, "Weighted");
+                let york = ui.selectable_value(fit_method, FitMethod::York, "York");
+                if ordinary.changed() || weighted.changed() || york.changed() {
                     *saved = false;
                 }
             });
             ui.horizontal(|ui| {
-                ui.label("Default y uncertainty");
-                if ui.text_edit_singleline(default_y_uncertainty).changed() {
+                ui.label("Gradient lines through");
+                let method = &mut fit.gradient_line_method;
+                let endpoints =
+                    ui.selectable_value(method, GradientLineMethod::Endpoints, "End points");
+                let all_points =
+                    ui.selectable_value(method, GradientLineMethod::AllPoints, "All points");
+                if endpoints.changed() || all_points.changed() {
                     *saved = false;
                 }
             });
+            if ui.checkbox(&mut fit.residuals, "Show residuals").changed() {
+                *saved = false;
+            }
             ui.horizontal(|ui| {
-                ui.label("Plotting backend");
-                ui.selectable_value(backend, Backends::Gnuplot, "GNU Plot");
-                ui.selectable_value(backend, Backends::Plotters, "Plotters");
+                ui.label("Exclude outliers beyond");
+                let threshold_input = egui::DragValue::new(&mut fit.outlier_threshold)
+                    .clamp_range(0.0..=f64::INFINITY)
+                    .speed(0.1)
+                    .suffix(" σ");
+                if ui
+                    .add(threshold_input)
+                    .on_hover_text(
+                        "Studentised residual from the line fitted to the other points, 0 to keep all points",
+                    )
+                    .changed()
+                {
+                    *saved = false;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Transform x");
+                egui::ComboBox::from_id_source("x_transform")
+                    .selected_text(transforms.x_transform.label("x"))
+                    .show_ui(ui, |ui| {
+                        for transform in Transform::ALL {
+                            let label = transform.label("x");
+                            let option =
+                                ui.selectable_value(&mut transforms.x_transform, transform, label);
+                            if option.changed() {
+                                *saved = false;
+                            }
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Transform y");
+                egui::ComboBox::from_id_source("y_transform")
+                    .selected_text(transforms.y_transform.label("y"))
+                    .show_ui(ui, |ui| {
+                        for transform in Transform::ALL {
+                            let label = transform.label("y");
+                            let option =
+                                ui.selectable_value(&mut transforms.y_transform, transform, label);
+                            if option.changed() {
+                                *saved = false;
+                            }
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Axis scale");
+                let linear = ui.selectable_value(axis_scale, AxisScale::Linear, "Linear");
+                let log_x = ui.selectable_value(axis_scale, AxisScale::LogX, "Log x");
+                let log_y = ui.selectable_value(axis_scale, AxisScale::LogY, "Log y");
+                let log_log = ui.selectable_value(axis_scale, AxisScale::LogLog, "Log-log");
+                if linear.changed() || log_x.changed() || log_y.changed() || log_log.changed() {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                if range_input(ui, "Fix x range", x_range) {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                if range_input(ui, "Fix y range", y_range) {
+                    *saved = false;
+                }
+            });
+            if ui
+                .checkbox(include_origin, "Include origin")
+                .on_hover_text("Extend the linear axes without a fixed range to the origin and the y intercepts of the fits")
+                .changed()
+            {
+                *saved = false;
+            }
+            ui.horizontal(|ui| {
+                if ticks_input(ui, "Space x ticks", x_ticks) {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                if ticks_input(ui, "Space y ticks", y_ticks) {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                if range_input(ui, "Extrapolate fits over x", fit_x_range) {
+                    *saved = false;
+                }
+            });
+            if ui
+                .checkbox(annotate_intercepts, "Annotate intercepts")
+                .on_hover_text("Mark where the lines of best fit cross the axes, with the uncertainties from the gradient lines, on linear charts")
+                .changed()
+            {
+                *saved = false;
+            }
+            ui.horizontal(|ui| {
+                ui.label("Notation");
+                egui::ComboBox::from_id_source("notation")
+                    .selected_text(notation.to_string())
+                    .show_ui(ui, |ui| {
+                        for choice in Notation::ALL {
+                            let option = ui.selectable_value(notation, choice, choice.to_string());
+                            if option.changed() {
+                                *saved = false;
+                            }
+                        }
+                    });
             });
 
             ui.add_space(20.0);
 
             ui.heading("Dataset");
+            // There is always a series to edit
+            if series.is_empty() {
+                series.push(SeriesInput::nth(0));
+            }
+            *current_series = (*current_series).min(series.len() - 1);
+            ui.horizontal(|ui| {
+                ui.label("Series");
+                egui::ComboBox::from_id_source("series")
+                    .selected_text(series[*current_series].title(*current_series))
+                    .show_ui(ui, |ui| {
+                        for (index, one) in series.iter().enumerate() {
+                            ui.selectable_value(current_series, index, one.title(index));
+                        }
+                    });
+                if ui.button("Add").clicked() {
+                    series.push(SeriesInput::nth(series.len()));
+                    *current_series = series.len() - 1;
+                    *saved = false;
+                }
+                let remove = egui::Button::new("Remove");
+                if ui.add_enabled(series.len() > 1, remove).clicked() {
+                    series.remove(*current_series);
+                    *current_series = (*current_series).min(series.len() - 1);
+                    *saved = false;
+                }
+            });
+            let current = &mut series[*current_series];
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                if ui.text_edit_singleline(&mut current.name).changed() {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Colour");
+                let colour = &mut current.style.colour;
+                let mut srgb = [colour.0, colour.1, colour.2];
+                if ui.color_edit_button_srgb(&mut srgb).changed() {
+                    *colour = Colour(srgb[0], srgb[1], srgb[2]);
+                    *saved = false;
+                }
+                ui.label("Marker");
+                egui::ComboBox::from_id_source("marker")
+                    .selected_text(current.style.marker.to_string())
+                    .show_ui(ui, |ui| {
+                        for marker in Marker::ALL {
+                            let option = ui.selectable_value(
+                                &mut current.style.marker,
+                                marker,
+                                marker.to_string(),
+                            );
+                            if option.changed() {
+                                *saved = false;
+                            }
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Default x uncertainty");
+                if ui
+                    .text_edit_singleline(&mut current.default_x_uncertainty)
+                    .changed()
+                {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Default y uncertainty");
+                if ui
+                    .text_edit_singleline(&mut current.default_y_uncertainty)
+                    .changed()
+                {
+                    *saved = false;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Decimal separator");
+                egui::ComboBox::from_id_source("decimal_separator")
+                    .selected_text(current.decimal_separator.to_string())
+                    .show_ui(ui, |ui| {
+                        for separator in DecimalSeparator::ALL {
+                            let option = ui.selectable_value(
+                                &mut current.decimal_separator,
+                                separator,
+                                separator.to_string(),
+                            );
+                            if option.changed() {
+                                *saved = false;
+                            }
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Repeated readings");
+                egui::ComboBox::from_id_source("repeat_uncertainty")
+                    .selected_text(current.repeats.uncertainty.to_string())
+                    .show_ui(ui, |ui| {
+                        for method in RepeatUncertainty::ALL {
+                            let option = ui.selectable_value(
+                                &mut current.repeats.uncertainty,
+                                method,
+                                method.to_string(),
+                            );
+                            if option.changed() {
+                                *saved = false;
+                            }
+                        }
+                    });
+                let resolution_input = egui::DragValue::new(&mut current.repeats.resolution)
+                    .clamp_range(0.0..=f64::INFINITY)
+                    .speed(0.001)
+                    .prefix("resolution ");
+                if ui
+                    .add(resolution_input)
+                    .on_hover_text("Smallest step of the readings, taken as an uncertainty of resolution/√12")
+                    .changed()
+                {
+                    *saved = false;
+                }
+            });
+            let separator = current.decimal_separator;
             egui::ScrollArea::vertical()
                 .max_height(300.0)
                 .auto_shrink([false; 2])
                 .show(ui, |ui| {
-                    let dsbox = egui::TextEdit::multiline(dataset)
+                    let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                        dataset_layouter(ui, text, wrap_width, separator)
+                    };
+                    let dsbox = egui::TextEdit::multiline(&mut current.dataset)
                         .desired_width(f32::INFINITY)
                         .desired_rows(10)
-                        .cursor_at_end(true);
+                        .cursor_at_end(true)
+                        .layouter(&mut layouter);
                     if ui.add(dsbox).changed() {
                         *saved = false;
                     }
+                    if let Err(error) = TwoVarDataSet::from_string(
+                        &current.dataset,
+                        Uncertainty::default(),
+                        Uncertainty::default(),
+                        Repeats::default(),
+                        separator,
+                    ) {
+                        ui.colored_label(Color32::RED, error.to_string());
+                    }
                 });
         });
     }
@@ -207,82 +790,335 @@ impl App {
                         ui.label("Zoom with ctrl + scroll.");
                     }
                     ui.label("Reset view with double-click.");
+                    ui.label("Click a point of the series being edited to exclude it from the fits or include it again.");
                 });
             });
-            let plot = Plot::new("preview").legend(Legend::default());
-            if let Ok(dataset) = self.parse_dataset() {
-                // Extra length before min and after max
-                let extra = (dataset.max_x(false) - dataset.min_x(false)) * 0.1;
+            let scale = self.spec.axis_scale;
+            let (log_x, log_y) = (scale.log_x(), scale.log_y());
+            // Log axes are shown by plotting the exponents
+            let corner = match self.spec.legend {
+                LegendPosition::UpperLeft => Corner::LeftTop,
+                LegendPosition::UpperRight => Corner::RightTop,
+                LegendPosition::LowerLeft => Corner::LeftBottom,
+                LegendPosition::LowerRight => Corner::RightBottom,
+            };
+            let mut plot = Plot::new("preview").legend(Legend::default().position(corner));
+            if log_x {
+                plot = plot.x_axis_formatter(log_axis_label);
+            }
+            if log_y {
+                plot = plot.y_axis_formatter(log_axis_label);
+            }
+            if let Ok(series) = self.fitted_series() {
+                let (axis_x, axis_y) = axis_ranges(&self.spec, &series);
                 // Two points for plotting the lines
-                let ln_plt_x = [dataset.min_x(true) - extra, dataset.max_x(true) + extra];
-                plot.show(ui, |plot_ui| {
-                    let best_fit = dataset.line_best_fit();
-                    plot_ui.line(
-                        Line::new(Values::from_values(vec![
-                            Value::new(ln_plt_x[0], best_fit.y(ln_plt_x[0])),
-                            Value::new(ln_plt_x[1], best_fit.y(ln_plt_x[1])),
-                        ]))
-                        .name(format!("Best Fit {}", best_fit)),
-                    );
-                    if let Some(line_min_grad) = dataset.line_min_grad() {
-                        plot_ui.line(
-                            Line::new(Values::from_values(vec![
-                                Value::new(ln_plt_x[0], line_min_grad.y(ln_plt_x[0])),
-                                Value::new(ln_plt_x[1], line_min_grad.y(ln_plt_x[1])),
-                            ]))
-                            .name(format!("Minimum Gradient {}", line_min_grad))
-                            .style(LineStyle::Dashed { length: 5.0 }),
-                        );
-                    }
-                    if let Some(line_max_grad) = dataset.line_max_grad() {
-                        plot_ui.line(
-                            Line::new(Values::from_values(vec![
-                                Value::new(ln_plt_x[0], line_max_grad.y(ln_plt_x[0])),
-                                Value::new(ln_plt_x[1], line_max_grad.y(ln_plt_x[1])),
-                            ]))
-                            .name(format!("Maximum Gradient {}", line_max_grad))
-                            .style(LineStyle::Dashed { length: 5.0 }),
-                        );
+                let ln_plt_x = if log_x || self.spec.x_range.is_some() {
+                    [axis_x.start, axis_x.end]
+                } else {
+                    let min_x = |with_uncertainty| {
+                        series
+                            .iter()
+                            .map(|one| one.data.min_x(with_uncertainty))
+                            .fold(f64::INFINITY, f64::min)
+                    };
+                    let max_x = |with_uncertainty| {
+                        series
+                            .iter()
+                            .map(|one| one.data.max_x(with_uncertainty))
+                            .fold(f64::NEG_INFINITY, f64::max)
+                    };
+                    // Extra length before min and after max
+                    let extra = (max_x(false) - min_x(false)) * 0.1;
+                    [min_x(true) - extra, max_x(true) + extra]
+                };
+                // Lines are curves on log scales, and drawn over the chosen
+                // range if extrapolated
+                let fit_x =
+                    fit_x_range(&self.spec).map_or(ln_plt_x, |range| [range.start, range.end]);
+                let plot_x = samples(fit_x[0], fit_x[1], CURVE_SAMPLES, log_x);
+                // Error bars crossing zero are clipped to the lower ends of log axes
+                let lower_x = if log_x {
+                    axis_x.start
+                } else {
+                    f64::NEG_INFINITY
+                };
+                let lower_y = if log_y {
+                    axis_y.start
+                } else {
+                    f64::NEG_INFINITY
+                };
+                let visible = |x: f64, y: f64| (!log_x || x > 0.0) && (!log_y || y > 0.0);
+                let plot_x_value = |x: f64| if log_x { x.log10() } else { x };
+                let value =
+                    |x: f64, y: f64| Value::new(plot_x_value(x), if log_y { y.log10() } else { y });
+                let curve = |f: &dyn Fn(f64) -> f64| {
+                    Values::from_values(
+                        plot_x
+                            .iter()
+                            .map(|x| (*x, f(*x)))
+                            .filter(|(x, y)| visible(*x, *y))
+                            .map(|(x, y)| value(x, y))
+                            .collect(),
+                    )
+                };
+                let method = self.spec.fit.gradient_line_method;
+                for one in series.iter() {
+                    match &one.fits {
+                        FitResults::Line {
+                            best_fit,
+                            gradient_lines,
+                        } => {
+                            if let Err(error) = best_fit {
+                                ui.colored_label(Color32::RED, one.label(&error.to_string()));
+                            }
+                            if let (GradientLineMethod::AllPoints, Err(error)) =
+                                (method, gradient_lines)
+                            {
+                                ui.colored_label(Color32::RED, one.label(&error.to_string()));
+                            }
+                        }
+                        FitResults::Expression(Ok(expression_fit)) => {
+                            for parameter in &expression_fit.parameters {
+                                ui.label(one.label(&format!(
+                                    "{} = {}",
+                                    parameter.name,
+                                    parameter.uncertain_value(self.spec.notation)
+                                )));
+                            }
+                        }
+                        FitResults::Expression(Err(error)) => {
+                            ui.colored_label(Color32::RED, one.label(&error.to_string()));
+                        }
+                        FitResults::Polynomial(_) => (),
                     }
-                    for point in dataset.deref() {
-                        let main = Points::new(Values::from_values(vec![Value::new(
-                            point.x_value,
-                            point.y_value,
-                        )]))
-                        .shape(MarkerShape::Cross)
-                        .color(Color32::DARK_GREEN)
-                        .radius(5.0)
-                        .filled(false);
-                        plot_ui.points(main);
-                        // Tips of error bars
-                        let around = Points::new(Values::from_values(vec![
-                            Value::new(point.x_value + point.x_uncertainty, point.y_value),
-                            Value::new(point.x_value - point.x_uncertainty, point.y_value),
-                            Value::new(point.x_value, point.y_value + point.y_uncertainty),
-                            Value::new(point.x_value, point.y_value - point.y_uncertainty),
-                        ]))
-                        .shape(MarkerShape::Plus)
-                        .color(Color32::DARK_GREEN)
-                        .radius(5.0)
-                        .filled(false);
-                        plot_ui.points(around);
-                        // Error bar
-                        plot_ui.line(
-                            Line::new(Values::from_values(vec![
-                                Value::new(point.x_value + point.x_uncertainty, point.y_value),
-                                Value::new(point.x_value - point.x_uncertainty, point.y_value),
-                            ]))
-                            .color(Color32::DARK_GREEN),
-                        );
-                        plot_ui.line(
-                            Line::new(Values::from_values(vec![
-                                Value::new(point.x_value, point.y_value + point.y_uncertainty),
-                                Value::new(point.x_value, point.y_value - point.y_uncertainty),
+                }
+                // The residuals take the bottom part if shown
+                if self.spec.fit.residuals {
+                    plot = plot.height(ui.available_height() * 0.7);
+                }
+                let clicked = plot.show(ui, |plot_ui| {
+                    for one in series.iter() {
+                        let dataset = &one.data;
+                        let colour = one.style.colour;
+                        let colour = Color32::from_rgb(colour.0, colour.1, colour.2);
+                        let faded = one.style.colour.faded();
+                        let faded = Color32::from_rgb(faded.0, faded.1, faded.2);
+                        match &one.fits {
+                            FitResults::Line {
+                                best_fit,
+                                gradient_lines,
+                            } => {
+                                // Written to the places of the uncertainties of the best fit
+                                let line_fit = best_fit.ok();
+                                let rounded = |line: &data::Line| match &line_fit {
+                                    Some(line_fit) => line_fit.round_line(line, self.spec.notation),
+                                    None => line.to_string(),
+                                };
+                                if let Some(best_fit) = line_fit.map(|line_fit| line_fit.line) {
+                                    plot_ui.line(
+                                        Line::new(curve(&|x| best_fit.y(x)))
+                                            .name(one.label(&format!(
+                                                "Best Fit {}",
+                                                rounded(&best_fit)
+                                            )))
+                                            .color(colour),
+                                    );
+                                }
+                                // Gradient lines only make sense for straight lines
+                                if let Ok((line_min_grad, line_max_grad)) = *gradient_lines {
+                                    plot_ui.line(
+                                        Line::new(curve(&|x| line_min_grad.y(x)))
+                                            .name(one.label(&format!(
+                                                "Minimum Gradient {}",
+                                                rounded(&line_min_grad)
+                                            )))
+                                            .color(colour)
+                                            .style(LineStyle::Dashed { length: 5.0 }),
+                                    );
+                                    plot_ui.line(
+                                        Line::new(curve(&|x| line_max_grad.y(x)))
+                                            .name(one.label(&format!(
+                                                "Maximum Gradient {}",
+                                                rounded(&line_max_grad)
+                                            )))
+                                            .color(colour)
+                                            .style(LineStyle::Dashed { length: 5.0 }),
+                                    );
+                                }
+                            }
+                            FitResults::Polynomial(polynomial) => {
+                                if let Some(polynomial) = polynomial {
+                                    plot_ui.line(
+                                        Line::new(curve(&|x| polynomial.y(x)))
+                                            .name(one.label(&format!("Best Fit {}", polynomial.equation(self.spec.notation))))
+                                            .color(colour),
+                                    );
+                                }
+                            }
+                            FitResults::Expression(expression_fit) => {
+                                if let Ok(expression_fit) = expression_fit {
+                                    plot_ui.line(
+                                        Line::new(curve(&|x| expression_fit.y(x)))
+                                            .name(one.label(&format!(
+                                                "Best Fit {}",
+                                                expression_fit.expression
+                                            )))
+                                            .color(colour),
+                                    );
+                                }
+                            }
+                        }
+                        if self.spec.intercepts_shown() {
+                            if let Some((x_intercept, y_intercept)) =
+                                line_intercepts(&self.spec, &one.fits)
+                            {
+                                let x_text = format!("x-intercept {}", x_intercept);
+                                let y_text = format!("y-intercept {}", y_intercept);
+                                // Below the x intercept and above the y
+                                // intercept so that both can be read near the origin
+                                let annotations = [
+                                    (x_intercept.value, 0.0, x_text, Align2::LEFT_TOP),
+                                    (0.0, y_intercept.value, y_text, Align2::LEFT_BOTTOM),
+                                ];
+                                for (x, y, text, anchor) in annotations {
+                                    plot_ui.points(
+                                        Points::new(Values::from_values(vec![value(x, y)]))
+                                            .shape(MarkerShape::Circle)
+                                            .color(colour)
+                                            .radius(4.0)
+                                            .filled(true),
+                                    );
+                                    // Spaced from the marker
+                                    plot_ui.text(
+                                        Text::new(value(x, y), format!("  {}", one.label(&text)))
+                                            .anchor(anchor)
+                                            .color(colour),
+                                    );
+                                }
+                            }
+                        }
+                        for point in dataset.deref() {
+                            // Points that cannot be shown on log scales are skipped
+                            if !visible(point.x_value, point.y_value) {
+                                continue;
+                            }
+                            let (x_low, x_high) =
+                                clipped_bar(point.x_value, point.x_uncertainty, lower_x);
+                            let (y_low, y_high) =
+                                clipped_bar(point.y_value, point.y_uncertainty, lower_y);
+                            // Points excluded from the fits are faded
+                            let colour = if point.excluded { faded } else { colour };
+                            let main = Points::new(Values::from_values(vec![value(
+                                point.x_value,
+                                point.y_value,
+                            )]))
+                            .shape(marker_shape(one.style.marker))
+                            .color(colour)
+                            .radius(5.0)
+                            .filled(false);
+                            plot_ui.points(main);
+                            // Tips of error bars
+                            let around = Points::new(Values::from_values(vec![
+                                value(x_high, point.y_value),
+                                value(x_low, point.y_value),
+                                value(point.x_value, y_high),
+                                value(point.x_value, y_low),
                             ]))
-                            .color(Color32::DARK_GREEN),
-                        );
+                            .shape(MarkerShape::Plus)
+                            .color(colour)
+                            .radius(5.0)
+                            .filled(false);
+                            plot_ui.points(around);
+                            // Error bar
+                            plot_ui.line(
+                                Line::new(Values::from_values(vec![
+                                    value(x_high, point.y_value),
+                                    value(x_low, point.y_value),
+                                ]))
+                                .color(colour),
+                            );
+                            plot_ui.line(
+                                Line::new(Values::from_values(vec![
+                                    value(point.x_value, y_high),
+                                    value(point.x_value, y_low),
+                                ]))
+                                .color(colour),
+                            );
+                        }
                     }
+                    // The point of the series being edited nearest to the click
+                    let pointer = plot_ui.pointer_coordinate().filter(|_| plot_ui.plot_clicked())?;
+                    let bounds = plot_ui.plot_bounds();
+                    let distance = |point: &TwoVarDataPoint| {
+                        let position = value(point.x_value, point.y_value);
+                        ((position.x - pointer.x) / bounds.width())
+                            .hypot((position.y - pointer.y) / bounds.height())
+                    };
+                    series[self.current_series]
+                        .data
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, point)| visible(point.x_value, point.y_value))
+                        .map(|(index, point)| (index, distance(point)))
+                        .filter(|(_, distance)| *distance < CLICK_DISTANCE)
+                        .min_by(|one, another| {
+                            one.1
+                                .partial_cmp(&another.1)
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        })
+                        .map(|(index, _)| index)
                 });
+                if let Some(index) = clicked.inner {
+                    self.toggle_excluded(index);
+                }
+                if self.spec.fit.residuals {
+                    let fit_method = self.spec.fit.fit_method;
+                    let mut residuals_plot = Plot::new("residuals")
+                        .include_x(plot_x_value(ln_plt_x[0]))
+                        .include_x(plot_x_value(ln_plt_x[1]));
+                    if log_x {
+                        residuals_plot = residuals_plot.x_axis_formatter(log_axis_label);
+                    }
+                    residuals_plot.show(ui, |plot_ui| {
+                        plot_ui.hline(HLine::new(0.0).color(Color32::GRAY));
+                        for one in series.iter() {
+                            let residuals = match one.data.residuals(&one.fits, fit_method) {
+                                Some(residuals) => residuals,
+                                None => continue,
+                            };
+                            for point in residuals.deref() {
+                                // Points that cannot be shown on log scales are skipped
+                                if log_x && point.x_value <= 0.0 {
+                                    continue;
+                                }
+                                let colour = if point.excluded {
+                                    one.style.colour.faded()
+                                } else {
+                                    one.style.colour
+                                };
+                                let colour = Color32::from_rgb(colour.0, colour.1, colour.2);
+                                let x = plot_x_value(point.x_value);
+                                let main = Points::new(Values::from_values(vec![Value::new(
+                                    x,
+                                    point.y_value,
+                                )]))
+                                .shape(marker_shape(one.style.marker))
+                                .color(colour)
+                                .radius(5.0)
+                                .filled(false);
+                                plot_ui.points(main);
+                                // Error bar
+                                plot_ui.line(
+                                    Line::new(Values::from_values(vec![
+                                        Value::new(x, point.y_value + point.y_uncertainty),
+                                        Value::new(x, point.y_value - point.y_uncertainty),
+                                    ]))
+                                    .color(colour),
+                                );
+                            }
+                        }
+                    });
+                }
             }
         });
     }
@@ -324,6 +1160,88 @@ impl App {
         }
     }
 
+    fn draw_import_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.import.is_some();
+        let mut import_clicked = false;
+        if let Some(import) = &mut self.import {
+            egui::Window::new("Import Table")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    let delimiter = import.delimiter;
+                    let separator = import.decimal_separator;
+                    egui::ComboBox::from_label("Delimiter")
+                        .selected_text(import.delimiter.to_string())
+                        .show_ui(ui, |ui| {
+                            for choice in Delimiter::ALL {
+                                ui.selectable_value(
+                                    &mut import.delimiter,
+                                    choice,
+                                    choice.to_string(),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_label("Decimal separator")
+                        .selected_text(import.decimal_separator.to_string())
+                        .show_ui(ui, |ui| {
+                            for choice in DecimalSeparator::ALL {
+                                ui.selectable_value(
+                                    &mut import.decimal_separator,
+                                    choice,
+                                    choice.to_string(),
+                                );
+                            }
+                        });
+                    if import.delimiter != delimiter || import.decimal_separator != separator {
+                        import.reparse();
+                    }
+                    let table = &import.table;
+                    column_choice(ui, "x", table, &mut import.x);
+                    optional_column_choice(ui, "x uncertainty", table, &mut import.x_uncertainty);
+                    column_choice(ui, "y", table, &mut import.y);
+                    optional_column_choice(ui, "y uncertainty", table, &mut import.y_uncertainty);
+                    ui.label(format!("{} rows", table.rows.len()));
+                    if ui.button("Import").clicked() {
+                        import_clicked = true;
+                    }
+                });
+        }
+        if import_clicked {
+            self.finish_import();
+        } else if !open {
+            self.import = None;
+        }
+    }
+
+    /// Put the mapped columns of the imported table into the current series
+    fn finish_import(&mut self) {
+        if let Some(import) = self.import.take() {
+            let mapping = import.mapping();
+            match import
+                .table
+                .to_dataset_text(&mapping, import.decimal_separator)
+            {
+                Ok(text) => {
+                    let series = &mut self.series[self.current_series];
+                    series.dataset = text;
+                    series.decimal_separator = DecimalSeparator::Point;
+                    // Headers are the natural axis labels
+                    if let Some(header) = import.table.header(import.x) {
+                        self.spec.x_label = header.to_owned();
+                    }
+                    if let Some(header) = import.table.header(import.y) {
+                        self.spec.y_label = header.to_owned();
+                    }
+                    self.saved = false;
+                }
+                Err(error) => {
+                    self.error = Some(format!("Cannot import table: {}", error));
+                    // Let the user choose other columns
+                    self.import = Some(import);
+                }
+            }
+        }
+    }
+
     fn draw_about_window(&mut self, ctx: &egui::Context) {
         if self.show_about {
             egui::Window::new("About").show(ctx, |ui| {
@@ -374,12 +1292,25 @@ impl App {
             Ok(result) => {
                 self.saved = true;
                 self.backend = result;
-                self.title = val.title;
-                self.dataset = val.dataset;
-                self.x_label = val.x_label;
-                self.y_label = val.y_label;
-                self.default_x_uncertainty = format!("{}", val.default_x_uncertainty);
-                self.default_y_uncertainty = format!("{}", val.default_y_uncertainty);
+                self.spec = val.spec;
+                self.transforms = val.transforms;
+                self.series = val
+                    .series
+                    .into_iter()
+                    .map(|one| SeriesInput {
+                        name: one.name,
+                        dataset: one.dataset,
+                        default_x_uncertainty: format!("{}", one.default_x_uncertainty),
+                        default_y_uncertainty: format!("{}", one.default_y_uncertainty),
+                        decimal_separator: one.decimal_separator,
+                        repeats: one.repeats,
+                        style: one.style,
+                    })
+                    .collect();
+                if self.series.is_empty() {
+                    self.series.push(SeriesInput::nth(0));
+                }
+                self.current_series = 0;
             }
             Err(error) => {
                 self.error = Some(format!(
@@ -403,7 +1334,8 @@ impl App {
             self.saved = false;
             match File::open(&path) {
                 Ok(mut file) => {
-                    let _ignore = file.read_to_string(&mut self.dataset);
+                    let dataset = &mut self.series[self.current_series].dataset;
+                    let _ignore = file.read_to_string(dataset);
                 }
                 Err(error) => {
                     self.error = Some(format!("Cannot open file: {}", error));
@@ -428,9 +1360,41 @@ impl App {
                 if let Ok(val) = PhysPlotterFile::from_reader(content.as_slice()) {
                     self.fill_app_from_saved(val);
                 } else {
-                    self.dataset = String::from_utf8_lossy(&content).to_string();
+                    self.series[self.current_series].dataset =
+                        String::from_utf8_lossy(&content).to_string();
+                }
+            }
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_table(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Tables", &["csv", "tsv", "txt"])
+            .pick_file()
+        {
+            match std::fs::read_to_string(&path) {
+                Ok(text) => self.import = Some(TableImport::new(text)),
+                Err(error) => {
+                    self.error = Some(format!("Cannot open file: {}", error));
                 }
             }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn import_table(&mut self) {
+        block_on(async move {
+            if let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter("Tables", &["csv", "tsv", "txt"])
+                .pick_file()
+                .await
+            {
+                let content = file.read().await;
+                self.import = Some(TableImport::new(
+                    String::from_utf8_lossy(&content).to_string(),
+                ));
+            }
         });
     }
 
@@ -513,88 +1477,250 @@ impl App {
         }
     }
 
-    fn parse_dataset(&self) -> Result<TwoVarDataSet, String> {
-        match (
-            self.default_x_uncertainty.parse(),
-            self.default_y_uncertainty.parse(),
-        ) {
-            (Ok(dux), Ok(duy)) => match TwoVarDataSet::from_string(&self.dataset, dux, duy) {
-                Ok(dataset) => {
-                    // Empty values can crash some backends
-                    if dataset.is_empty() {
-                        Err(String::from("Empty dataset"))
-                    } else {
-                        Ok(dataset)
-                    }
-                }
-                Err(error) => Err(format!("Invalid dataset: {}", error)),
-            },
-            (Err(_), _) => Err(format!(
-                "Invalid x uncertainty: {}",
-                self.default_x_uncertainty
-            )),
-            (_, Err(_)) => Err(format!(
-                "Invalid y uncertainty: {}",
-                self.default_y_uncertainty
-            )),
+    /// Save the graph as an image in the format given by the extension
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export(&mut self) {
+        let series = match self.fitted_series() {
+            Ok(series) => series,
+            Err(error) => {
+                self.error = Some(error);
+                return;
+            }
+        };
+        let mut dialog = rfd::FileDialog::new();
+        for format in plot::ImageFormat::ALL {
+            dialog = dialog.add_filter(format.description(), &[format.extension()]);
+        }
+        let path = match dialog.save_file() {
+            Some(path) => path,
+            None => return,
+        };
+        let format = match plot::ImageFormat::from_path(&path) {
+            Some(format) => format,
+            None => {
+                self.error = Some(format!("Unknown image format: {}", path.display()));
+                return;
+            }
+        };
+        let spec = self.spec.transformed(self.transforms);
+        let result = self
+            .backend
+            .save_checked(
+                &spec,
+                &series,
+                plot::SaveOptions {
+                    path: &path,
+                    format,
+                },
+            )
+            .map_err(|error| {
+                format!(
+                    "Error while running {}: {}",
+                    self.backend.display_name(),
+                    error
+                )
+            });
+        if let Err(error) = result {
+            self.error = Some(error);
         }
     }
 
-    fn plot(&mut self) {
-        let dataset = match self.parse_dataset() {
-            Ok(dataset) => dataset,
+    /// Save a gnuplot script reproducing the graph, with its data next to it
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_script(&mut self) {
+        let series = match self.fitted_series() {
+            Ok(series) => series,
             Err(error) => {
                 self.error = Some(error);
                 return;
             }
         };
+        let path = match rfd::FileDialog::new()
+            .add_filter("Gnuplot script", &["gp"])
+            .save_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        let spec = self.spec.transformed(self.transforms);
+        if let Err(error) = plot::gnuplot_script(&spec, &series, &path) {
+            self.error = Some(format!("Error while saving file: {}", error));
+        }
+    }
 
-        // Call plotting backend
-        match self.backend {
-            Backends::Gnuplot => {
-                if let Err(error) =
-                    plot::gnuplot(&self.title, &self.x_label, &self.y_label, &dataset, None)
-                {
-                    self.error = Some(format!("Error while opening GNU Plot: {}", error));
+    /// Parse and fit all series, reporting the first error
+    fn parse_series(&self) -> Result<Vec<FittedSeries>, String> {
+        self.series
+            .iter()
+            .enumerate()
+            .map(|(index, one)| {
+                let mut parsed = one
+                    .parse(self.transforms)
+                    .map_err(|error| format!("{}: {}", one.title(index), error))?;
+                if self.spec.fit.outlier_threshold > 0.0 {
+                    parsed.data.exclude_outliers(
+                        self.spec.fit.fit_method,
+                        self.spec.fit.outlier_threshold,
+                    );
                 }
+                Ok(FittedSeries::new(parsed, &self.spec.fit))
+            })
+            .collect()
+    }
+
+    /// Parse and fit all series like `parse_series`, reusing the last result
+    /// unless the series, the transforms or the fit options have changed
+    fn fitted_series(&mut self) -> Result<Rc<Vec<FittedSeries>>, String> {
+        let current = match &self.fitted {
+            Some(fitted) => {
+                fitted.series == self.series
+                    && fitted.transforms == self.transforms
+                    && fitted.fit == self.spec.fit
             }
-            Backends::Plotters => {
-                let mut svg_out = String::new();
-                if let Err(error) = plot::plotters(
-                    &self.title,
-                    &self.x_label,
-                    &self.y_label,
-                    &dataset,
-                    SVGBackend::with_string(&mut svg_out, (960, 540)),
-                ) {
-                    self.error = Some(format!("Error while generating: {}", error));
-                } else {
-                    self.save_svg_output(&svg_out);
-                }
+            None => false,
+        };
+        if !current {
+            self.fitted = Some(FittedInputs {
+                series: self.series.clone(),
+                transforms: self.transforms,
+                fit: self.spec.fit.clone(),
+                fitted: self.parse_series().map(Rc::new),
+            });
+        }
+        self.fitted.as_ref().unwrap().fitted.clone()
+    }
+
+    /// Toggle the exclusion mark of a point of the series being edited
+    fn toggle_excluded(&mut self, index: usize) {
+        let current = &mut self.series[self.current_series];
+        if let Some(dataset) =
+            TwoVarDataSet::toggle_excluded(&current.dataset, index, current.decimal_separator)
+        {
+            current.dataset = dataset;
+            self.saved = false;
+        }
+    }
+
+    fn plot(&mut self) {
+        let series = match self.fitted_series() {
+            Ok(series) => series,
+            Err(error) => {
+                self.error = Some(error);
+                return;
             }
         };
+        let spec = self.spec.transformed(self.transforms);
+
+        // Call plotting backend, saving an SVG document if it has no window
+        let backend = self.backend;
+        let result = if backend.can_show() {
+            backend.show(&spec, &series)
+        } else {
+            backend
+                .svg(&spec, &series)
+                .map(|svg| self.save_svg_output(&svg))
+        };
+        if let Err(error) = result {
+            self.error = Some(format!(
+                "Error while running {}: {}",
+                backend.display_name(),
+                error
+            ));
+        }
     }
 }
 
 /// Create save file from the state
 impl TryInto<PhysPlotterFile> for App {
-    type Error = <f64 as FromStr>::Err;
+    type Error = UncertaintyFromStrError;
 
     fn try_into(self) -> Result<PhysPlotterFile, Self::Error> {
         Ok(PhysPlotterFile {
             creator: defv::APP_ID.to_string(),
             version: crate_version!().to_string(),
-            title: self.title.clone(),
             backend_name: format!("{}", self.backend),
-            x_label: self.x_label.clone(),
-            y_label: self.y_label.clone(),
-            default_x_uncertainty: self.default_x_uncertainty.parse()?,
-            default_y_uncertainty: self.default_y_uncertainty.parse()?,
-            dataset: self.dataset.clone(),
+            series: self
+                .series
+                .iter()
+                .map(|one| {
+                    Ok(SavedSeries {
+                        name: one.name.clone(),
+                        default_x_uncertainty: one.default_x_uncertainty.parse()?,
+                        default_y_uncertainty: one.default_y_uncertainty.parse()?,
+                        dataset: one.dataset.clone(),
+                        decimal_separator: one.decimal_separator,
+                        repeats: one.repeats,
+                        style: one.style,
+                    })
+                })
+                .collect::<Result<_, Self::Error>>()?,
+            spec: self.spec.clone(),
+            transforms: self.transforms,
         })
     }
 }
 
+/// Lay out the dataset text, with the line that cannot be parsed highlighted
+fn dataset_layouter(
+    ui: &egui::Ui,
+    text: &str,
+    wrap_width: f32,
+    separator: DecimalSeparator,
+) -> Arc<egui::Galley> {
+    let normal = egui::TextFormat::simple(
+        egui::TextStyle::Body.resolve(ui.style()),
+        ui.visuals().widgets.inactive.text_color(),
+    );
+    let mut job = egui::text::LayoutJob::default();
+    match TwoVarDataSet::from_string(
+        text,
+        Uncertainty::default(),
+        Uncertainty::default(),
+        Repeats::default(),
+        separator,
+    ) {
+        Ok(_) => job.append(text, 0.0, normal),
+        Err(error) => {
+            let span = error.line_span(text);
+            let highlighted = egui::TextFormat {
+                background: Color32::RED.linear_multiply(0.25),
+                ..normal.clone()
+            };
+            job.append(&text[..span.start], 0.0, normal.clone());
+            job.append(&text[span.clone()], 0.0, highlighted);
+            job.append(&text[span.end..], 0.0, normal);
+        }
+    }
+    job.wrap_width = wrap_width;
+    ui.fonts().layout_job(job)
+}
+
+/// Shape of the marker in the preview
+fn marker_shape(marker: Marker) -> MarkerShape {
+    match marker {
+        Marker::Plus => MarkerShape::Plus,
+        Marker::Cross => MarkerShape::Cross,
+        Marker::Circle => MarkerShape::Circle,
+        Marker::Square => MarkerShape::Square,
+        Marker::Triangle => MarkerShape::Up,
+    }
+}
+
+/// Label of a log axis in the preview, which is plotted in exponents
+fn log_axis_label(exponent: f64, _range: &RangeInclusive<f64>) -> String {
+    // Three significant figures
+    let decimals = (2.0 - exponent.floor()).max(0.0) as usize;
+    let label = format!("{:.*}", decimals, 10.0_f64.powf(exponent));
+    if label.contains('.') {
+        label
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        label
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 fn redirect_to_data_uri(mime: &str, data: &str, filename: &str) -> Option<()> {
     let uri = format!("data:{},{}", mime, data);
@@ -0,0 +1,94 @@
+//
+//  Copyright (C) 2021 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Layout shared by the gnuplot backend and the gnuplot scripts, so that both draw the same graph
+
+use crate::plot::{FittedSeries, LegendPosition, Marker, PlotSpec};
+use std::ops::Range;
+
+/// Vertical distance between labels in graph coordinates
+pub(crate) const LABEL_SPACING: f64 = 0.06;
+
+/// Height of the first label in graph coordinates
+pub(crate) const LABEL_TOP: f64 = 0.97;
+
+/// Fraction of the height taken by the residuals panel
+pub(crate) const RESIDUALS_HEIGHT: f64 = 0.3;
+
+/// Left margin of the chart and the residuals panel in screen coordinates
+pub(crate) const LEFT_MARGIN: f64 = 0.1;
+
+/// Point symbol of the filled circles at the intercepts, in the gnuplot crate
+/// and as a gnuplot point type
+pub(crate) const INTERCEPT_POINT: (char, u8) = ('O', 7);
+
+/// Point symbol of the marker, in the gnuplot crate and as a gnuplot point type
+pub(crate) fn marker_point(marker: Marker) -> (char, u8) {
+    match marker {
+        Marker::Plus => ('+', 1),
+        Marker::Cross => ('x', 2),
+        Marker::Circle => ('o', 6),
+        Marker::Square => ('s', 4),
+        Marker::Triangle => ('t', 8),
+    }
+}
+
+/// Range of x covered by the lines: the x axis if fixed, else the error bars
+/// extended by a tenth of the span of the values on either side
+pub(crate) fn line_x_range(
+    spec: &PlotSpec,
+    series: &[FittedSeries],
+    axis_x: &Range<f64>,
+) -> Range<f64> {
+    if spec.fixed_x() {
+        return axis_x.clone();
+    }
+    let min_x = |with_uncertainty| {
+        series
+            .iter()
+            .map(|one| one.data.min_x(with_uncertainty))
+            .fold(f64::INFINITY, f64::min)
+    };
+    let max_x = |with_uncertainty| {
+        series
+            .iter()
+            .map(|one| one.data.max_x(with_uncertainty))
+            .fold(f64::NEG_INFINITY, f64::max)
+    };
+    let extra = (max_x(false) - min_x(false)) * 0.1;
+    (min_x(true) - extra)..(max_x(true) + extra)
+}
+
+/// Corner of the legend in graph coordinates
+pub(crate) fn legend_corner(legend: LegendPosition) -> (f64, f64) {
+    (
+        if legend.left() { 0.01 } else { 0.99 },
+        if legend.upper() { 0.95 } else { 0.05 },
+    )
+}
+
+/// Horizontal position of the labels in graph coordinates and whether they
+/// are aligned right. Labels are stacked at a top corner, away from the legend
+pub(crate) fn label_x(legend: LegendPosition) -> (f64, bool) {
+    if legend == LegendPosition::UpperLeft {
+        (0.99, true)
+    } else {
+        (0.01, false)
+    }
+}
@@ -0,0 +1,636 @@
+//
+//  Copyright (C) 2021 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+//! Standalone gnuplot scripts that reproduce the graph of the gnuplot backend
+
+use crate::data::{FitResults, GradientLineMethod};
+use crate::plot::gnuplot_common::{
+    label_x, legend_corner, line_x_range, marker_point, INTERCEPT_POINT, LABEL_SPACING, LABEL_TOP,
+    LEFT_MARGIN, RESIDUALS_HEIGHT,
+};
+use crate::plot::{
+    axis_ranges, clipped_bar, fit_x_range, line_intercepts, samples, FittedSeries, PlotSpec,
+    CURVE_SAMPLES,
+};
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Quote a string for gnuplot
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Gnuplot expression of a polynomial in x, given the coefficients from the
+/// constant term up
+fn polynomial(coefficients: &[f64]) -> String {
+    let mut terms = String::new();
+    for (power, coefficient) in coefficients.iter().enumerate().rev() {
+        if terms.is_empty() {
+            write!(terms, "{:?}", coefficient).unwrap();
+        } else if *coefficient < 0.0 {
+            write!(terms, " - {:?}", -coefficient).unwrap();
+        } else {
+            write!(terms, " + {:?}", coefficient).unwrap();
+        }
+        match power {
+            0 => (),
+            1 => terms.push_str(" * x"),
+            _ => write!(terms, " * x**{}", power).unwrap(),
+        }
+    }
+    if terms.is_empty() {
+        terms.push_str("0.0");
+    }
+    terms
+}
+
+/// Path of the data file written next to the script at `path`, which is the
+/// path of the script with `.dat` appended so that the two never coincide
+#[must_use]
+pub fn gnuplot_script_data_path(path: &Path) -> PathBuf {
+    let mut data_path = path.as_os_str().to_owned();
+    data_path.push(".dat");
+    PathBuf::from(data_path)
+}
+
+/// Blocks of the data file, addressed in the script by `index`
+#[derive(Default)]
+struct DataFile {
+    contents: String,
+    blocks: usize,
+}
+
+impl DataFile {
+    /// Append a block of rows, returning its index
+    fn block<I: IntoIterator<Item = Vec<f64>>>(&mut self, comment: &str, rows: I) -> usize {
+        if self.blocks != 0 {
+            // Blocks are separated by two blank lines
+            self.contents.push_str("\n\n");
+        }
+        writeln!(self.contents, "# {}", comment).unwrap();
+        for row in rows {
+            let row: Vec<String> = row.iter().map(|value| format!("{:?}", value)).collect();
+            writeln!(self.contents, "{}", row.join(" ")).unwrap();
+        }
+        self.blocks += 1;
+        self.blocks - 1
+    }
+}
+
+/// Write a gnuplot script to `path` that draws the same graph as the gnuplot
+/// backend, with the data in a file named by `gnuplot_script_data_path`.
+/// The straight lines and polynomials are written as gnuplot functions and the
+/// other curves as sampled points, so that the script can be edited freely
+pub fn gnuplot_script(
+    spec: &PlotSpec,
+    series: &[FittedSeries],
+    path: &Path,
+) -> std::io::Result<()> {
+    let scale = spec.axis_scale;
+    let fit = &spec.fit;
+    let data_path = gnuplot_script_data_path(path);
+    // The script refers to the data file by name so that both can be moved together
+    let data_name = data_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let data_name = quote(&data_name);
+    let title_font = quote(&format!("{},{}", spec.fonts.family, spec.fonts.title_size));
+    let label_font = quote(&format!("{},{}", spec.fonts.family, spec.fonts.label_size));
+    let (axis_x, axis_y) = axis_ranges(spec, series);
+    // Error bars are clipped at the lower ends of log axes
+    let lower_x = if scale.log_x() {
+        axis_x.start
+    } else {
+        f64::NEG_INFINITY
+    };
+    let lower_y = if scale.log_y() {
+        axis_y.start
+    } else {
+        f64::NEG_INFINITY
+    };
+    // Range of the lines and curves
+    let Range {
+        start: start_x,
+        end: end_x,
+    } = line_x_range(spec, series, &axis_x);
+    // The fits are extrapolated over the chosen range, outside which the
+    // functions are undefined
+    let fit_range = fit_x_range(spec);
+    let curve_x = match &fit_range {
+        Some(range) => samples(range.start, range.end, CURVE_SAMPLES, scale.log_x()),
+        None => samples(start_x, end_x, CURVE_SAMPLES, scale.log_x()),
+    };
+
+    let mut data = DataFile::default();
+    let mut script = String::new();
+    let mut plots = Vec::new();
+    let mut functions = String::new();
+    let mut labels = String::new();
+    let (label_x, label_right) = label_x(spec.legend);
+    let label_align = if label_right { "right" } else { "left" };
+    let mut label_y = LABEL_TOP;
+    for (number, one) in series.iter().enumerate() {
+        let number = number + 1;
+        let colour = one.style.colour.to_string();
+        for excluded in [false, true] {
+            // Points that cannot be shown on log scales are skipped
+            let rows: Vec<Vec<f64>> = one
+                .data
+                .iter()
+                .filter(|point| {
+                    point.excluded == excluded
+                        && (!scale.log_x() || point.x_value > 0.0)
+                        && (!scale.log_y() || point.y_value > 0.0)
+                })
+                .map(|point| {
+                    let (x_low, x_high) = clipped_bar(point.x_value, point.x_uncertainty, lower_x);
+                    let (y_low, y_high) = clipped_bar(point.y_value, point.y_uncertainty, lower_y);
+                    vec![point.x_value, point.y_value, x_low, x_high, y_low, y_high]
+                })
+                .collect();
+            if rows.is_empty() {
+                continue;
+            }
+            let (comment, point_colour) = if excluded {
+                (
+                    "excluded from the fits",
+                    one.style.colour.faded().to_string(),
+                )
+            } else {
+                ("points", colour.clone())
+            };
+            let index = data.block(
+                &format!(
+                    "Series {} {}: x y x_low x_high y_low y_high",
+                    number, comment
+                ),
+                rows,
+            );
+            plots.push(format!(
+                "{} index {} using 1:2:3:4:5:6 with xyerrorbars pt {} lw 1.5 lc rgb \"{}\" notitle",
+                data_name,
+                index,
+                marker_point(one.style.marker).1,
+                point_colour
+            ));
+        }
+        let mut line = |name: &str, function: String, caption: String, dashed: bool| {
+            let function = match &fit_range {
+                Some(range) => format!(
+                    "x < {:?} || x > {:?} ? 1/0 : {}",
+                    range.start, range.end, function
+                ),
+                None => function,
+            };
+            writeln!(functions, "{}_{}(x) = {}", name, number, function).unwrap();
+            plots.push(format!(
+                "{}_{}(x) with lines {}lw {} lc rgb \"{}\" title {}",
+                name,
+                number,
+                if dashed { "dt 2 " } else { "" },
+                if dashed { 1.5 } else { 2.0 },
+                colour,
+                quote(&one.label(&caption))
+            ));
+        };
+        match &one.fits {
+            FitResults::Line {
+                best_fit,
+                gradient_lines,
+            } => {
+                // Three lines, written to the places of the uncertainties of the best fit
+                let line_fit = best_fit.ok();
+                if let Some(line_fit) = &line_fit {
+                    let line_best_fit = line_fit.line;
+                    line(
+                        "best",
+                        polynomial(&[line_best_fit.y_intercept, line_best_fit.gradient]),
+                        line_best_fit_name!(line_best_fit, Some(line_fit), spec.notation),
+                        false,
+                    );
+                    writeln!(
+                        labels,
+                        "set label {} at graph {:?}, {:?} {} font {}",
+                        quote(&one.label(&line_fit_stats_name!(line_fit, spec.notation))),
+                        label_x,
+                        label_y,
+                        label_align,
+                        label_font
+                    )
+                    .unwrap();
+                    label_y -= LABEL_SPACING;
+                }
+                match *gradient_lines {
+                    Ok((line_min_grad, line_max_grad)) => {
+                        line(
+                            "min_grad",
+                            polynomial(&[line_min_grad.y_intercept, line_min_grad.gradient]),
+                            line_min_grad_name!(line_min_grad, line_fit.as_ref(), spec.notation),
+                            true,
+                        );
+                        line(
+                            "max_grad",
+                            polynomial(&[line_max_grad.y_intercept, line_max_grad.gradient]),
+                            line_max_grad_name!(line_max_grad, line_fit.as_ref(), spec.notation),
+                            true,
+                        );
+                    }
+                    Err(error) if fit.gradient_line_method == GradientLineMethod::AllPoints => {
+                        writeln!(
+                            labels,
+                            "set label {} at graph {:?}, {:?} {} font {}",
+                            quote(&one.label(&no_grad_lines_name!(error))),
+                            label_x,
+                            label_y,
+                            label_align,
+                            label_font
+                        )
+                        .unwrap();
+                        label_y -= LABEL_SPACING;
+                    }
+                    Err(_) => {}
+                }
+            }
+            FitResults::Polynomial(fitted) => {
+                if let Some(fitted) = fitted {
+                    line(
+                        "best",
+                        polynomial(&fitted.coefficients),
+                        polynomial_best_fit_name!(fitted, spec.notation),
+                        false,
+                    );
+                }
+            }
+            FitResults::Expression(expression_fit) => {
+                if let Ok(expression_fit) = expression_fit {
+                    let index = data.block(
+                        &format!("Series {} best fit: x y", number),
+                        curve_x.iter().map(|x| vec![*x, expression_fit.y(*x)]),
+                    );
+                    let expression = &expression_fit.expression;
+                    plots.push(format!(
+                        "{} index {} using 1:2 with lines lw 2.0 lc rgb \"{}\" title {}",
+                        data_name,
+                        index,
+                        colour,
+                        quote(&one.label(&line_best_fit_name!(expression)))
+                    ));
+                    writeln!(
+                        labels,
+                        "set label {} at graph {:?}, {:?} {} font {}",
+                        quote(
+                            &one.label(&expression_fit_stats_name!(expression_fit, spec.notation))
+                        ),
+                        label_x,
+                        label_y,
+                        label_align,
+                        label_font
+                    )
+                    .unwrap();
+                    label_y -= LABEL_SPACING;
+                }
+            }
+        }
+        if spec.intercepts_shown() {
+            if let Some((x_intercept, y_intercept)) = line_intercepts(spec, &one.fits) {
+                // Below the x intercept and above the y intercept, as in the
+                // gnuplot backend
+                let annotations = [
+                    (x_intercept.value, 0.0, x_intercept_name!(x_intercept), -1),
+                    (0.0, y_intercept.value, y_intercept_name!(y_intercept), 1),
+                ];
+                // Labels off the chart are not clipped
+                for (x, y, text, offset_y) in annotations {
+                    if axis_x.contains(&x) && axis_y.contains(&y) {
+                        writeln!(
+                            labels,
+                            "set label {} at first {:?}, first {:?} font {} textcolor rgb \"{}\" point pt {} lc rgb \"{}\" offset 1, {}",
+                            quote(&one.label(&text)),
+                            x,
+                            y,
+                            label_font,
+                            colour,
+                            INTERCEPT_POINT.1,
+                            colour,
+                            offset_y
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    writeln!(script, "# Generated by physics plotter").unwrap();
+    writeln!(
+        script,
+        "# Run in the directory of {} with `gnuplot -p {}`",
+        data_name,
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    )
+    .unwrap();
+    writeln!(
+        script,
+        "# To save the graph instead, uncomment the lines below"
+    )
+    .unwrap();
+    writeln!(
+        script,
+        "# set terminal pngcairo size {},{}",
+        spec.size.width, spec.size.height
+    )
+    .unwrap();
+    writeln!(script, "# set output \"graph.png\"").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "set termoption noenhanced").unwrap();
+    writeln!(
+        script,
+        "set title {} font {}",
+        quote(&spec.title),
+        title_font
+    )
+    .unwrap();
+    writeln!(
+        script,
+        "set xlabel {} font {}",
+        quote(&spec.x_label),
+        label_font
+    )
+    .unwrap();
+    writeln!(
+        script,
+        "set ylabel {} font {}",
+        quote(&spec.y_label),
+        label_font
+    )
+    .unwrap();
+    writeln!(script, "set tics font {}", label_font).unwrap();
+    let (legend_x, legend_y) = legend_corner(spec.legend);
+    writeln!(
+        script,
+        "set key at graph {:?}, graph {:?} {} {} font {}",
+        legend_x,
+        legend_y,
+        if spec.legend.left() { "left" } else { "right" },
+        if spec.legend.upper() { "top" } else { "bottom" },
+        label_font
+    )
+    .unwrap();
+    writeln!(script, "set xrange [{:?}:{:?}]", start_x, end_x).unwrap();
+    if spec.fixed_y() {
+        writeln!(script, "set yrange [{:?}:{:?}]", axis_y.start, axis_y.end).unwrap();
+    }
+    // Ticks are spaced by gnuplot unless set for a linear axis
+    let x_major = spec
+        .x_ticks
+        .major_spacing(&axis_x)
+        .filter(|_| !scale.log_x());
+    let y_major = spec
+        .y_ticks
+        .major_spacing(&axis_y)
+        .filter(|_| !scale.log_y());
+    if let Some(major) = x_major {
+        writeln!(script, "set xtics {:?}", major).unwrap();
+        writeln!(
+            script,
+            "set mxtics {}",
+            spec.x_ticks.minor_intervals(&axis_x)
+        )
+        .unwrap();
+    }
+    if let Some(major) = y_major {
+        writeln!(script, "set ytics {:?}", major).unwrap();
+        writeln!(
+            script,
+            "set mytics {}",
+            spec.y_ticks.minor_intervals(&axis_y)
+        )
+        .unwrap();
+    }
+    if scale.log_x() {
+        writeln!(script, "set logscale x 10").unwrap();
+    }
+    if scale.log_y() {
+        writeln!(script, "set logscale y 10").unwrap();
+    }
+    // The functions are sampled like the curves of the backends
+    writeln!(script, "set samples {}", CURVE_SAMPLES).unwrap();
+    writeln!(script).unwrap();
+    if !functions.is_empty() {
+        writeln!(script, "# Fitted lines").unwrap();
+        script.push_str(&functions);
+        writeln!(script).unwrap();
+    }
+    script.push_str(&labels);
+    if fit.residuals {
+        // The chart takes the top part. The left margins and the x ranges are
+        // the same as those of the residuals panel, so that the x axes are aligned
+        writeln!(script, "set multiplot").unwrap();
+        writeln!(script, "set lmargin at screen {:?}", LEFT_MARGIN).unwrap();
+        writeln!(script, "set origin 0, {:?}", RESIDUALS_HEIGHT).unwrap();
+        writeln!(script, "set size 1, {:?}", 1.0 - RESIDUALS_HEIGHT).unwrap();
+    }
+    writeln!(script, "plot {}", plots.join(", \\\n     ")).unwrap();
+    if fit.residuals {
+        let mut residual_plots = vec![String::from("0 with lines lc rgb \"black\" notitle")];
+        for (number, one) in series.iter().enumerate() {
+            let residuals = match one.data.residuals(&one.fits, fit.fit_method) {
+                Some(residuals) => residuals,
+                None => continue,
+            };
+            for excluded in [false, true] {
+                // Points that cannot be shown on log scales are skipped
+                let rows: Vec<Vec<f64>> = residuals
+                    .iter()
+                    .filter(|point| {
+                        point.excluded == excluded && (!scale.log_x() || point.x_value > 0.0)
+                    })
+                    .map(|point| vec![point.x_value, point.y_value, point.y_uncertainty])
+                    .collect();
+                if rows.is_empty() {
+                    continue;
+                }
+                let colour = if excluded {
+                    one.style.colour.faded()
+                } else {
+                    one.style.colour
+                };
+                let index = data.block(
+                    &format!(
+                        "Series {} residuals{}: x residual uncertainty",
+                        number + 1,
+                        if excluded {
+                            " excluded from the fits"
+                        } else {
+                            ""
+                        }
+                    ),
+                    rows,
+                );
+                residual_plots.push(format!(
+                    "{} index {} using 1:2:3 with yerrorbars pt {} lw 1.5 lc rgb \"{}\" notitle",
+                    data_name,
+                    index,
+                    marker_point(one.style.marker).1,
+                    colour
+                ));
+            }
+        }
+        writeln!(script).unwrap();
+        writeln!(script, "# Residuals panel").unwrap();
+        writeln!(script, "set origin 0, 0").unwrap();
+        writeln!(script, "set size 1, {:?}", RESIDUALS_HEIGHT).unwrap();
+        writeln!(script, "unset title").unwrap();
+        writeln!(script, "unset label").unwrap();
+        writeln!(script, "unset logscale y").unwrap();
+        writeln!(script, "set autoscale y").unwrap();
+        if y_major.is_some() {
+            writeln!(script, "set ytics autofreq").unwrap();
+            writeln!(script, "set mytics 1").unwrap();
+        }
+        writeln!(script, "set ylabel \"Residual\" font {}", label_font).unwrap();
+        writeln!(script, "plot {}", residual_plots.join(", \\\n     ")).unwrap();
+        writeln!(script, "unset multiplot").unwrap();
+    }
+    std::fs::write(&data_path, data.contents)?;
+    std::fs::write(path, script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{DecimalSeparator, FitOptions, Repeats, TwoVarDataSet, Uncertainty};
+    use crate::plot::Series;
+
+    #[test]
+    fn data_path_is_apart_from_script() {
+        for (script, data) in [
+            ("plot.gp", "plot.gp.dat"),
+            ("plot.dat", "plot.dat.dat"),
+            ("dir/plot", "dir/plot.dat"),
+        ] {
+            assert_eq!(
+                gnuplot_script_data_path(Path::new(script)),
+                Path::new(data),
+                "data file of {}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn quotes_escape_backslashes_and_quotes() {
+        assert_eq!(quote(r#"Say "hi" \ bye"#), r#""Say \"hi\" \\ bye""#);
+        assert_eq!(quote("two\nlines"), r#""two\nlines""#);
+    }
+
+    #[test]
+    fn polynomials_as_functions() {
+        assert_eq!(polynomial(&[1.0, 2.0]), "2.0 * x + 1.0");
+        assert_eq!(polynomial(&[-1.5, 0.0, 3.0]), "3.0 * x**2 + 0.0 * x - 1.5");
+        assert_eq!(polynomial(&[]), "0.0");
+    }
+
+    #[test]
+    fn writes_script_and_data() {
+        let data = TwoVarDataSet::from_string(
+            "1 3\n2 5\n* 3 9\n4 9",
+            Uncertainty::default(),
+            Uncertainty::default(),
+            Repeats::default(),
+            DecimalSeparator::Point,
+        )
+        .unwrap();
+        let spec = PlotSpec {
+            title: String::from(r#"Say "hi" \ bye"#),
+            fit_x_range: Some(0.0..5.0),
+            fit: FitOptions {
+                residuals: true,
+                ..PlotSpec::default().fit
+            },
+            ..PlotSpec::default()
+        };
+        let series = FittedSeries::fit_all(
+            vec![Series {
+                data,
+                ..Series::default()
+            }],
+            &spec.fit,
+        );
+        let directory =
+            std::env::temp_dir().join(format!("phys_plotter_script_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("plot.gp");
+        gnuplot_script(&spec, &series, &path).unwrap();
+        let script = std::fs::read_to_string(&path).unwrap();
+        let data = std::fs::read_to_string(gnuplot_script_data_path(&path)).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // The included and the excluded points are in separate blocks, as
+        // are their residuals after them
+        let blocks: Vec<&str> = data.split("\n\n\n").collect();
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(
+            blocks[0],
+            "# Series 1 points: x y x_low x_high y_low y_high\n\
+             1.0 3.0 1.0 1.0 3.0 3.0\n\
+             2.0 5.0 2.0 2.0 5.0 5.0\n\
+             4.0 9.0 4.0 4.0 9.0 9.0"
+        );
+        assert_eq!(
+            blocks[1],
+            "# Series 1 excluded from the fits: x y x_low x_high y_low y_high\n\
+             3.0 9.0 3.0 3.0 9.0 9.0"
+        );
+        assert!(blocks[2].starts_with("# Series 1 residuals: x residual uncertainty\n"));
+        assert!(blocks[3].starts_with("# Series 1 residuals excluded from the fits:"));
+        assert!(script.contains("\"plot.gp.dat\" index 0 using 1:2:3:4:5:6 with xyerrorbars"));
+        assert!(script.contains("\"plot.gp.dat\" index 1 using 1:2:3:4:5:6 with xyerrorbars"));
+        for index in [2, 3] {
+            assert!(script.contains(&format!(
+                "\"plot.gp.dat\" index {} using 1:2:3 with yerrorbars",
+                index
+            )));
+        }
+
+        // The lines are functions, undefined outside the fit range
+        for name in ["best_1", "min_grad_1", "max_grad_1"] {
+            let function = script
+                .lines()
+                .find(|line| line.starts_with(&format!("{}(x) = ", name)))
+                .unwrap_or_else(|| panic!("{} is not defined", name));
+            assert!(
+                function.contains("(x) = x < 0.0 || x > 5.0 ? 1/0 : "),
+                "{}",
+                function
+            );
+            assert!(script.contains(&format!("{}(x) with lines", name)));
+        }
+        assert!(script.contains(r#"set title "Say \"hi\" \\ bye" font"#));
+        assert!(script.contains("set multiplot"));
+    }
+}
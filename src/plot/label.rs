@@ -17,20 +17,110 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
+/// A straight line written to the places of the uncertainties of the fit,
+/// given as an `Option<&LineFit>`
+macro_rules! rounded_line {
+    ($line_var: ident, $fit_var: expr, $notation: expr) => {
+        match $fit_var {
+            Some(fit) => fit.round_line(&$line_var, $notation),
+            None => $line_var.to_string(),
+        }
+    };
+}
+
+/// A statistic such as R² to six significant figures
+macro_rules! statistic {
+    ($value: expr, $notation: expr) => {
+        $crate::data::UncertainValue::without_uncertainty($value, $notation).value_string()
+    };
+}
+
+macro_rules! polynomial_best_fit_name {
+    ($polynomial_var: ident, $notation: expr) => {
+        format!("Best fit {}", $polynomial_var.equation($notation))
+    };
+}
+
 macro_rules! line_best_fit_name {
     ($line_var: ident) => {
         format!("Best fit {}", $line_var)
     };
+    ($line_var: ident, $fit_var: expr, $notation: expr) => {
+        format!("Best fit {}", rounded_line!($line_var, $fit_var, $notation))
+    };
+}
+
+macro_rules! line_fit_stats_name {
+    ($fit_var: ident, $notation: expr) => {
+        format!(
+            "Gradient {}, y-intercept {}, R² = {}{} ({} d.o.f.)",
+            $fit_var.gradient($notation),
+            $fit_var.y_intercept($notation),
+            statistic!($fit_var.r_squared, $notation),
+            match $fit_var.residual_sd {
+                Some(residual_sd) => format!(", s = {}", statistic!(residual_sd, $notation)),
+                None => String::new(),
+            },
+            $fit_var.degrees_of_freedom
+        )
+    };
+}
+
+macro_rules! expression_fit_stats_name {
+    ($fit_var: ident, $notation: expr) => {
+        format!(
+            "{}, R² = {}, s = {} ({} d.o.f.)",
+            $fit_var
+                .parameters
+                .iter()
+                .map(|parameter| format!(
+                    "{} = {}",
+                    parameter.name,
+                    parameter.uncertain_value($notation)
+                ))
+                .collect::<Vec<String>>()
+                .join(", "),
+            statistic!($fit_var.r_squared, $notation),
+            statistic!($fit_var.residual_sd, $notation),
+            $fit_var.degrees_of_freedom
+        )
+    };
 }
 
 macro_rules! line_min_grad_name {
-    ($line_var: ident) => {
-        format!("Minimum gradient {}", $line_var)
+    ($line_var: ident, $fit_var: expr, $notation: expr) => {
+        format!(
+            "Minimum gradient {}",
+            rounded_line!($line_var, $fit_var, $notation)
+        )
     };
 }
 
 macro_rules! line_max_grad_name {
-    ($line_var: ident) => {
-        format!("Maximum gradient {}", $line_var)
+    ($line_var: ident, $fit_var: expr, $notation: expr) => {
+        format!(
+            "Maximum gradient {}",
+            rounded_line!($line_var, $fit_var, $notation)
+        )
+    };
+}
+
+/// Why there are no gradient lines through all error bars, given the
+/// `ThroughAllError`
+macro_rules! no_grad_lines_name {
+    ($error_var: ident) => {
+        $error_var.to_string()
+    };
+}
+
+macro_rules! x_intercept_name {
+    ($intercept_var: ident) => {
+        format!("x-intercept {}", $intercept_var)
+    };
+}
+
+macro_rules! y_intercept_name {
+    ($intercept_var: ident) => {
+        format!("y-intercept {}", $intercept_var)
     };
 }
@@ -1,61 +1,545 @@
 #[macro_use]
 mod label;
+mod gnuplot_common;
+mod gnuplot_script;
 mod plot_gnuplot;
 mod plot_plotters;
+mod renderer;
 mod save_options;
+mod series;
+mod spec;
 
-pub use plot_gnuplot::gnuplot;
-pub use plot_plotters::plotters;
-pub use save_options::SaveOptions;
+use crate::data::{FitResults, Line, UncertainValue};
+pub use gnuplot_script::{gnuplot_script, gnuplot_script_data_path};
+pub use plot_gnuplot::{gnuplot, GnuplotRenderer};
+pub use plot_plotters::{plotters, PlottersRenderer};
+pub use renderer::{register, Backends, BackendsFromStrError, Renderer, RendererError};
+#[cfg(feature = "ui_gtk")]
+pub use renderer::{register_cairo, CairoRenderer};
+pub use save_options::{ImageFormat, ImageFormatFromStrError, SaveFormatError, SaveOptions};
+pub use series::{
+    Colour, ColourFromStrError, FittedSeries, Marker, MarkerFromStrError, Series, SeriesStyle,
+};
+pub use spec::{
+    format_axis_range, parse_axis_range, AxisOptionFromStrError, Fonts, LegendPosition,
+    LegendPositionFromStrError, PlotSize, PlotSpec, TickSpacing,
+};
+use std::ops::Range;
 use std::str::FromStr;
 
-/// Available backends
-#[derive(PartialEq, Eq, Debug, Copy, Clone, serde::Deserialize, serde::Serialize)]
-pub enum Backends {
-    Gnuplot,
-    Plotters,
+/// Number of points sampled when plotting curves
+pub(crate) const CURVE_SAMPLES: usize = 200;
+
+/// Get `count` evenly spaced values from `start` to `end`, both inclusive
+pub(crate) fn linspace(start: f64, end: f64, count: usize) -> Vec<f64> {
+    let intervals = count.saturating_sub(1).max(1) as f64;
+    (0..count)
+        .map(|idx| start + (end - start) * idx as f64 / intervals)
+        .collect()
 }
 
-impl std::fmt::Display for Backends {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Backends::Gnuplot => write!(f, "gnuplot"),
-            Backends::Plotters => write!(f, "plotters"),
+/// Get `count` values from `start` to `end`, both inclusive, evenly spaced
+/// on a log scale if `log`, so that curves look smooth on that scale
+pub(crate) fn samples(start: f64, end: f64, count: usize, log: bool) -> Vec<f64> {
+    if log {
+        linspace(start.log10(), end.log10(), count)
+            .into_iter()
+            .map(|exponent| 10.0_f64.powf(exponent))
+            .collect()
+    } else {
+        linspace(start, end, count)
+    }
+}
+
+/// Range of an axis covering the error bars of `(value, uncertainty)` pairs,
+/// extended by 10% of the span of the values on either side.
+/// On a log scale, the span is measured in decades, non-positive values are
+/// skipped, and error bars crossing zero are left to be clipped
+pub(crate) fn axis_range<I: Iterator<Item = (f64, f64)>>(points: I, log: bool) -> Range<f64> {
+    let mut min = f64::INFINITY;
+    let mut max = f64::NEG_INFINITY;
+    let mut min_with_uncertainty = f64::INFINITY;
+    let mut max_with_uncertainty = f64::NEG_INFINITY;
+    for (value, uncertainty) in points {
+        if log && value <= 0.0 {
+            continue;
         }
+        let low = value - uncertainty;
+        let (value, low, high) = if log {
+            let low = if low > 0.0 { low } else { value };
+            (value.log10(), low.log10(), (value + uncertainty).log10())
+        } else {
+            (value, low, value + uncertainty)
+        };
+        min = min.min(value);
+        max = max.max(value);
+        min_with_uncertainty = min_with_uncertainty.min(low);
+        max_with_uncertainty = max_with_uncertainty.max(high);
+    }
+    if min > max {
+        // Nothing can be shown
+        return if log { 1.0..10.0 } else { 0.0..1.0 };
+    }
+    let extra = (max - min) * 0.1;
+    let range = (min_with_uncertainty - extra)..(max_with_uncertainty + extra);
+    if log {
+        10.0_f64.powf(range.start)..10.0_f64.powf(range.end)
+    } else {
+        range
     }
 }
 
-/// Error when converting from str to Backends
-#[derive(Clone)]
-pub enum BackendsFromStrError {
-    UnknownBackend(String),
+/// The range if it can be shown on the scale
+fn usable_range(range: &Option<Range<f64>>, log: bool) -> Option<Range<f64>> {
+    range
+        .clone()
+        .filter(|range| range.start < range.end && (!log || range.start > 0.0))
+}
+
+/// Range of x over which the fits are extrapolated, if chosen by the spec and
+/// it can be shown on the scale
+pub(crate) fn fit_x_range(spec: &PlotSpec) -> Option<Range<f64>> {
+    usable_range(&spec.fit_x_range, spec.axis_scale.log_x())
+}
+
+/// Ranges of the axes, fixed by the spec unless they cannot be shown on the
+/// scale, or else covering the error bars of all series, the extrapolated
+/// fits, and the origin and the intercepts of the fits if asked to
+pub(crate) fn axis_ranges(spec: &PlotSpec, series: &[FittedSeries]) -> (Range<f64>, Range<f64>) {
+    let points = || series.iter().flat_map(|one| one.data.iter());
+    let scale = spec.axis_scale;
+    let fit_x = fit_x_range(spec);
+    let axis_x = usable_range(&spec.x_range, scale.log_x()).unwrap_or_else(|| {
+        let range = axis_range(
+            points().map(|point| (point.x_value, point.x_uncertainty)),
+            scale.log_x(),
+        );
+        let mut values = Vec::new();
+        if let Some(fit_x) = &fit_x {
+            values.extend([fit_x.start, fit_x.end]);
+        }
+        if spec.reaches_origin() && !scale.log_x() {
+            values.push(0.0);
+            if spec.intercepts_shown() {
+                values.extend(
+                    series
+                        .iter()
+                        .filter_map(|one| line_intercepts(spec, &one.fits))
+                        .map(|(x_intercept, _)| x_intercept.value),
+                );
+            }
+        }
+        including(range, &values, scale.log_x())
+    });
+    let axis_y = usable_range(&spec.y_range, scale.log_y()).unwrap_or_else(|| {
+        let range = axis_range(
+            points().map(|point| (point.y_value, point.y_uncertainty)),
+            scale.log_y(),
+        );
+        let mut values = Vec::new();
+        // The part of the extrapolated fits on the chart
+        if let Some(fit_x) = &fit_x {
+            let start = fit_x.start.max(axis_x.start);
+            let end = fit_x.end.min(axis_x.end);
+            if start < end {
+                let fit_x = samples(start, end, CURVE_SAMPLES, scale.log_x());
+                values.extend(fit_values(series, &fit_x));
+            }
+        }
+        if spec.reaches_origin() && !scale.log_y() {
+            values.push(0.0);
+            // The y intercepts are only on the chart if x = 0 is
+            if axis_x.contains(&0.0) {
+                values.extend(fit_values(series, &[0.0]));
+            }
+        }
+        including(range, &values, scale.log_y())
+    });
+    (axis_x, axis_y)
+}
+
+/// Extend a range to the values, leaving a margin beyond those other than
+/// zero so that the axes can meet at the origin. On a log scale, the margin
+/// is measured in decades and values that cannot be shown are skipped
+fn including(range: Range<f64>, values: &[f64], log: bool) -> Range<f64> {
+    let values = values
+        .iter()
+        .copied()
+        .filter(|value| value.is_finite() && (!log || *value > 0.0));
+    let low = values.clone().fold(range.start, f64::min);
+    let high = values.fold(range.end, f64::max);
+    let to_scale = |value: f64| if log { value.log10() } else { value };
+    let from_scale = |value: f64| if log { 10.0_f64.powf(value) } else { value };
+    let margin = (to_scale(high) - to_scale(low)) * 0.05;
+    let start = if low < range.start && low != 0.0 {
+        from_scale(to_scale(low) - margin)
+    } else {
+        low
+    };
+    let end = if high > range.end && high != 0.0 {
+        from_scale(to_scale(high) + margin)
+    } else {
+        high
+    };
+    start..end
+}
+
+/// Values of the fits of all series at the x values, and of the gradient
+/// lines of straight-line fits
+fn fit_values(series: &[FittedSeries], x_values: &[f64]) -> Vec<f64> {
+    let mut values = Vec::new();
+    for one in series {
+        values.extend(x_values.iter().filter_map(|x| one.fits.y(*x)));
+        if let Some((line_min_grad, line_max_grad)) = one.fits.gradient_lines() {
+            for line in [line_min_grad, line_max_grad] {
+                values.extend(x_values.iter().map(|x| line.y(*x)));
+            }
+        }
+    }
+    values.retain(|value| value.is_finite());
+    values
+}
+
+/// The x and y intercepts of the line of best fit of the data, if the fit is
+/// a straight line that crosses both axes. Their uncertainties are the largest
+/// distances from where the gradient lines cross the axes, or zero without
+/// gradient lines
+pub(crate) fn line_intercepts(
+    spec: &PlotSpec,
+    fits: &FitResults,
+) -> Option<(UncertainValue, UncertainValue)> {
+    let line_best_fit = fits.line_fit()?.line;
+    let gradient_lines: Vec<Line> = fits
+        .gradient_lines()
+        .map_or_else(Vec::new, |(min, max)| vec![min, max]);
+    let intercept = |at: fn(&Line) -> f64| {
+        let value = at(&line_best_fit);
+        let uncertainty = gradient_lines
+            .iter()
+            .map(|line| (at(line) - value).abs())
+            .filter(|distance| distance.is_finite())
+            .fold(0.0, f64::max);
+        UncertainValue::new(value, uncertainty, spec.notation)
+    };
+    let x_intercept = intercept(Line::x_intercept);
+    let y_intercept = intercept(|line| line.y_intercept);
+    (x_intercept.value.is_finite() && y_intercept.value.is_finite())
+        .then_some((x_intercept, y_intercept))
+}
+
+/// Ends of an error bar, with the lower end clipped to `lower`
+pub(crate) fn clipped_bar(value: f64, uncertainty: f64, lower: f64) -> (f64, f64) {
+    ((value - uncertainty).max(lower), value + uncertainty)
+}
+
+/// Split a sampled curve into the pieces whose y values lie within the range,
+/// so that out-of-range points are neither clamped to the edges nor joined
+pub(crate) fn visible_segments<I: Iterator<Item = (f64, f64)>>(
+    points: I,
+    y_range: &Range<f64>,
+) -> Vec<Vec<(f64, f64)>> {
+    let mut segments = vec![Vec::new()];
+    for (x, y) in points {
+        if y_range.contains(&y) {
+            segments.last_mut().unwrap().push((x, y));
+        } else if !segments.last().unwrap().is_empty() {
+            segments.push(Vec::new());
+        }
+    }
+    segments.retain(|segment| segment.len() > 1);
+    segments
+}
+
+/// Scales of the axes
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum AxisScale {
+    #[default]
+    Linear,
+    LogX,
+    LogY,
+    LogLog,
+}
+
+impl AxisScale {
+    /// Whether the x axis is logarithmic
+    #[must_use]
+    pub fn log_x(self) -> bool {
+        matches!(self, AxisScale::LogX | AxisScale::LogLog)
+    }
+
+    /// Whether the y axis is logarithmic
+    #[must_use]
+    pub fn log_y(self) -> bool {
+        matches!(self, AxisScale::LogY | AxisScale::LogLog)
+    }
 }
 
-impl std::fmt::Display for BackendsFromStrError {
+impl std::fmt::Display for AxisScale {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            BackendsFromStrError::UnknownBackend(bstr) => write!(f, "Unknown backend: {}", bstr),
+            AxisScale::Linear => write!(f, "linear"),
+            AxisScale::LogX => write!(f, "log-x"),
+            AxisScale::LogY => write!(f, "log-y"),
+            AxisScale::LogLog => write!(f, "log-log"),
         }
     }
 }
 
-impl std::fmt::Debug for BackendsFromStrError {
+/// Error when converting from str to `AxisScale`
+#[derive(Clone, Debug)]
+pub enum AxisScaleFromStrError {
+    UnknownScale(String),
+}
+
+impl std::fmt::Display for AxisScaleFromStrError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            BackendsFromStrError::UnknownBackend(bstr) => write!(f, "Unknown backend: {:?}", bstr),
+            AxisScaleFromStrError::UnknownScale(sstr) => write!(f, "Unknown axis scale: {}", sstr),
+        }
+    }
+}
+
+impl FromStr for AxisScale {
+    type Err = AxisScaleFromStrError;
+
+    /// Parse axis scale description
+    fn from_str(sstr: &str) -> Result<Self, Self::Err> {
+        match sstr.to_lowercase().as_str() {
+            "linear" => Ok(AxisScale::Linear),
+            "log-x" => Ok(AxisScale::LogX),
+            "log-y" => Ok(AxisScale::LogY),
+            "log-log" => Ok(AxisScale::LogLog),
+            other => Err(Self::Err::UnknownScale(other.to_string())),
         }
     }
 }
 
-impl FromStr for Backends {
-    type Err = BackendsFromStrError;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{
+        DecimalSeparator, LineFit, Repeats, ThroughAllError, TwoVarDataSet, Uncertainty,
+    };
 
-    /// Parse backend description
-    fn from_str(bstr: &str) -> Result<Self, Self::Err> {
-        match bstr.to_lowercase().as_str() {
-            "plotters" => Ok(Backends::Plotters),
-            "gnuplot" => Ok(Backends::Gnuplot),
-            other => Err(Self::Err::UnknownBackend(other.to_string())),
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Straight-line fits of an exact line
+    fn line_fits(
+        gradient: f64,
+        y_intercept: f64,
+        gradient_lines: Result<(Line, Line), ThroughAllError>,
+    ) -> FitResults {
+        FitResults::Line {
+            best_fit: Ok(LineFit {
+                line: Line {
+                    gradient,
+                    y_intercept,
+                },
+                gradient_uncertainty: None,
+                y_intercept_uncertainty: None,
+                correlation: 0.0,
+                r_squared: 1.0,
+                residual_sd: None,
+                degrees_of_freedom: 0,
+            }),
+            gradient_lines,
         }
     }
+
+    #[test]
+    fn intercepts_with_gradient_lines() {
+        let gradient_lines = (
+            Line {
+                gradient: 1.0,
+                y_intercept: -1.0,
+            },
+            Line {
+                gradient: 3.0,
+                y_intercept: -7.0,
+            },
+        );
+        let fits = line_fits(2.0, -4.0, Ok(gradient_lines));
+        let (x_intercept, y_intercept) = line_intercepts(&PlotSpec::default(), &fits).unwrap();
+        // The gradient lines cross the x axis at 1 and 7/3
+        assert_close(x_intercept.value, 2.0);
+        assert_close(x_intercept.uncertainty, 1.0);
+        assert_close(y_intercept.value, -4.0);
+        assert_close(y_intercept.uncertainty, 3.0);
+        assert_eq!(x_intercept.to_string(), "2.0 ± 1.0");
+        assert_eq!(y_intercept.to_string(), "-4 ± 3");
+    }
+
+    #[test]
+    fn intercepts_without_gradient_lines() {
+        let fits = line_fits(0.5, 1.0, Err(ThroughAllError::Infeasible));
+        let (x_intercept, y_intercept) = line_intercepts(&PlotSpec::default(), &fits).unwrap();
+        assert_close(x_intercept.value, -2.0);
+        assert_eq!(x_intercept.uncertainty, 0.0);
+        assert_close(y_intercept.value, 1.0);
+        assert_eq!(y_intercept.uncertainty, 0.0);
+    }
+
+    #[test]
+    fn no_intercepts_of_level_lines_or_curves() {
+        let fits = line_fits(0.0, 3.0, Err(ThroughAllError::TooFewPoints));
+        assert!(line_intercepts(&PlotSpec::default(), &fits).is_none());
+        let fits = FitResults::Polynomial(None);
+        assert!(line_intercepts(&PlotSpec::default(), &fits).is_none());
+    }
+
+    #[test]
+    fn fit_range_must_be_shown() {
+        let spec = |fit_x_range: Option<Range<f64>>, axis_scale: AxisScale| PlotSpec {
+            fit_x_range,
+            axis_scale,
+            ..PlotSpec::default()
+        };
+        assert_eq!(fit_x_range(&spec(None, AxisScale::Linear)), None);
+        assert_eq!(
+            fit_x_range(&spec(Some(-1.0..5.0), AxisScale::Linear)),
+            Some(-1.0..5.0)
+        );
+        // Reversed and empty ranges are rejected
+        assert_eq!(fit_x_range(&spec(Some(5.0..1.0), AxisScale::Linear)), None);
+        assert_eq!(fit_x_range(&spec(Some(2.0..2.0), AxisScale::Linear)), None);
+        // Log scales need positive ranges
+        assert_eq!(fit_x_range(&spec(Some(-1.0..5.0), AxisScale::LogX)), None);
+        assert_eq!(
+            fit_x_range(&spec(Some(-1.0..5.0), AxisScale::LogY)),
+            Some(-1.0..5.0)
+        );
+        assert_eq!(
+            fit_x_range(&spec(Some(0.5..5.0), AxisScale::LogLog)),
+            Some(0.5..5.0)
+        );
+    }
+
+    /// A series of the points in the data string, fitted with the options
+    /// of the spec
+    fn fitted(buf: &str, spec: &PlotSpec) -> Vec<FittedSeries> {
+        let data = TwoVarDataSet::from_string(
+            buf,
+            Uncertainty::default(),
+            Uncertainty::default(),
+            Repeats::default(),
+            DecimalSeparator::Point,
+        )
+        .unwrap();
+        let series = Series {
+            data,
+            ..Series::default()
+        };
+        FittedSeries::fit_all(vec![series], &spec.fit)
+    }
+
+    /// Points on y = 2x - 1, which crosses the axes at (0.5, 0) and (0, -1)
+    const POINTS: &str = "2 3\n3 5\n4 7";
+
+    fn assert_range(actual: Range<f64>, expected: Range<f64>) {
+        assert_close(actual.start, expected.start);
+        assert_close(actual.end, expected.end);
+    }
+
+    #[test]
+    fn ranges_cover_the_error_bars() {
+        let spec = PlotSpec::default();
+        let (axis_x, axis_y) = axis_ranges(&spec, &fitted(POINTS, &spec));
+        assert_range(axis_x, 1.8..4.2);
+        assert_range(axis_y, 2.6..7.4);
+    }
+
+    #[test]
+    fn ranges_include_the_origin() {
+        let spec = PlotSpec {
+            include_origin: true,
+            ..PlotSpec::default()
+        };
+        let (axis_x, axis_y) = axis_ranges(&spec, &fitted(POINTS, &spec));
+        // No margin is left beyond zero, so that the axes meet there
+        assert_range(axis_x, 0.0..4.2);
+        // The y-intercept is below the origin, with a margin of 5%
+        assert_range(axis_y, -1.42..7.4);
+    }
+
+    #[test]
+    fn set_ranges_override_the_origin() {
+        let spec = PlotSpec {
+            include_origin: true,
+            x_range: Some(1.0..5.0),
+            ..PlotSpec::default()
+        };
+        let (axis_x, axis_y) = axis_ranges(&spec, &fitted(POINTS, &spec));
+        assert_eq!(axis_x, 1.0..5.0);
+        // The y-intercept is not on the chart, but the origin of y still is
+        assert_range(axis_y, 0.0..7.4);
+        let spec = PlotSpec {
+            y_range: Some(-3.0..3.0),
+            ..spec
+        };
+        let (_, axis_y) = axis_ranges(&spec, &fitted(POINTS, &spec));
+        assert_eq!(axis_y, -3.0..3.0);
+        // Ranges that cannot be shown are ignored
+        let spec = PlotSpec {
+            x_range: Some(5.0..1.0),
+            ..spec
+        };
+        let (axis_x, _) = axis_ranges(&spec, &fitted(POINTS, &spec));
+        assert_range(axis_x, 0.0..4.2);
+    }
+
+    #[test]
+    fn samples_on_log_scales() {
+        let samples_log = samples(1.0, 1000.0, 4, true);
+        for (actual, expected) in samples_log.into_iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert_close(actual, expected);
+        }
+        assert_eq!(samples(1.0, 1000.0, 4, false), [1.0, 334.0, 667.0, 1000.0]);
+        assert_eq!(samples(2.0, 5.0, 1, true), [2.0]);
+    }
+
+    #[test]
+    fn bars_clipped_to_the_axis() {
+        assert_eq!(clipped_bar(5.0, 2.0, 0.1), (3.0, 7.0));
+        // The bar crosses zero, which cannot be shown on a log scale
+        assert_eq!(clipped_bar(1.0, 2.0, 0.1), (0.1, 3.0));
+    }
+
+    #[test]
+    fn log_ranges_skip_what_cannot_be_shown() {
+        let range = axis_range(
+            [(10.0, 20.0), (100.0, 10.0), (-5.0, 1.0), (0.0, 1.0)].into_iter(),
+            true,
+        );
+        // The bar crossing zero is left to be clipped, and the margins are a
+        // tenth of the decade spanned by the values
+        assert_range(range, 10.0_f64.powf(0.9)..110.0 * 10.0_f64.powf(0.1));
+        assert_eq!(
+            axis_range([(-1.0, 0.5), (0.0, 0.0)].into_iter(), true),
+            1.0..10.0
+        );
+        assert_eq!(axis_range(std::iter::empty(), false), 0.0..1.0);
+    }
+
+    #[test]
+    fn log_axis_ranges() {
+        let spec = PlotSpec {
+            axis_scale: AxisScale::LogLog,
+            // The origin cannot be shown on log scales
+            include_origin: true,
+            ..PlotSpec::default()
+        };
+        let points = "1 10 20\n10 100 10\n-1 5 0\n100 1000 0";
+        let (axis_x, axis_y) = axis_ranges(&spec, &fitted(points, &spec));
+        // The point at x = -1 is only left out of the x range
+        assert_range(axis_x, 10.0_f64.powf(-0.2)..10.0_f64.powf(2.2));
+        let extra = 10.0_f64.powf((3.0 - 5.0_f64.log10()) * 0.1);
+        assert_range(axis_y, 5.0 / extra..1000.0 * extra);
+    }
 }
@@ -17,95 +17,403 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
-use crate::data::TwoVarDataSet;
-use crate::plot::SaveOptions;
+use crate::data::{FitResults, GradientLineMethod};
+use crate::plot::gnuplot_common::{
+    label_x, legend_corner, line_x_range, marker_point, INTERCEPT_POINT, LABEL_SPACING, LABEL_TOP,
+    LEFT_MARGIN, RESIDUALS_HEIGHT,
+};
+use crate::plot::{
+    axis_ranges, clipped_bar, fit_x_range, line_intercepts, samples, FittedSeries, PlotSpec,
+    TickSpacing, CURVE_SAMPLES,
+};
+use crate::plot::{ImageFormat, Renderer, SaveOptions};
 use gnuplot::{
-    Auto, AxesCommon, Caption, Color, Dash, Figure, Font, GnuplotInitError, Graph, LineStyle,
-    LineWidth,
+    AlignBottom, AlignLeft, AlignRight, AlignTop, Auto, AxesCommon, Axis, Caption, Color, Dash,
+    Figure, Fix, Font, GnuplotInitError, Graph, LineStyle, LineWidth, MarginLeft, MarkerColor,
+    MarkerSymbol, Placement, PointSize, PointSymbol, TextAlign, TextColor, TextOffset,
 };
+use std::ops::Range;
 
-/// Plot dataset with the gnuplot backend. If save is None, the graph is only shown, else the graph is saved
+/// Plot series with the gnuplot backend. If save is None, the graph is only shown, else the graph is saved in the chosen format
 pub fn gnuplot(
-    title: &str,
-    x_label: &str,
-    y_label: &str,
-    data: &TwoVarDataSet,
+    spec: &PlotSpec,
+    series: &[FittedSeries],
     save: Option<SaveOptions<'_>>,
 ) -> Result<(), GnuplotInitError> {
-    // Extra length before min and after max
-    let extra = (data.max_x(false) - data.min_x(false)) * 0.1;
+    let scale = spec.axis_scale;
+    let fit = &spec.fit;
+    let (axis_x, axis_y) = axis_ranges(spec, series);
+    // Linear axes are scaled automatically unless fixed by the spec
+    let fixed_x = spec.fixed_x();
+    let fixed_y = spec.fixed_y();
+    // Ticks are spaced by gnuplot unless set for a linear axis
+    let ticks =
+        |log: bool, spacing: &TickSpacing, range: &Range<f64>| match spacing.major_spacing(range) {
+            Some(major) if !log => Some((Fix(major), spacing.minor_intervals(range))),
+            _ => Some((Auto, 1)),
+        };
+    let lower_x = if scale.log_x() {
+        axis_x.start
+    } else {
+        f64::NEG_INFINITY
+    };
+    let lower_y = if scale.log_y() {
+        axis_y.start
+    } else {
+        f64::NEG_INFINITY
+    };
     // Two points for plotting the lines
-    let ln_plt_x = Vec::from([data.min_x(true) - extra, data.max_x(true) + extra]);
-    // Three lines
-    let x_values = data.get_x_value();
-    let y_values = data.get_y_value();
+    let ln_plt_x = line_x_range(spec, series, &axis_x);
+    let ln_plt_x = Vec::from([ln_plt_x.start, ln_plt_x.end]);
+    // Ends of the lines, over the chosen range if extrapolated
+    let fit_x = fit_x_range(spec).map_or_else(
+        || ln_plt_x.clone(),
+        |range| Vec::from([range.start, range.end]),
+    );
+    // Points for plotting the curves, and the lines on log scales
+    let curve_x = samples(fit_x[0], fit_x[1], CURVE_SAMPLES, scale.log_x());
+    let line_x = if scale.log_x() { &curve_x } else { &fit_x };
+    let centre = |bars: &[(f64, f64)]| -> Vec<f64> {
+        bars.iter().map(|(low, high)| (low + high) / 2.0).collect()
+    };
+    let half_width = |bars: &[(f64, f64)]| -> Vec<f64> {
+        bars.iter().map(|(low, high)| (high - low) / 2.0).collect()
+    };
+    let title_font = || Font(spec.fonts.family.as_str(), spec.fonts.title_size);
+    let label_font = || Font(spec.fonts.family.as_str(), spec.fonts.label_size);
+    let (legend_x, legend_y) = legend_corner(spec.legend);
+    let legend_align_x = if spec.legend.left() {
+        AlignLeft
+    } else {
+        AlignRight
+    };
+    let legend_align_y = if spec.legend.upper() {
+        AlignTop
+    } else {
+        AlignBottom
+    };
     let mut fg = Figure::new();
     let figure = fg
         .axes2d()
-        .set_title(title, &[Font("Times", 22.0)])
-        .set_x_label(x_label, &[Font("Times", 13.0)])
-        .set_y_label(y_label, &[Font("Times", 13.0)])
-        // Automatically generate ticks
-        .set_x_ticks(Some((Auto, 1)), &[], &[Font("Times", 13.0)])
-        .set_y_ticks(Some((Auto, 1)), &[], &[Font("Times", 13.0)]);
-    figure
-        // Scatter points "Real data"
-        //.points(&x_values, &y_values, &[Color("#4477AA"), PointSymbol('.')])
-        // Plot error bars
-        .x_error_bars(
-            &x_values,
-            &y_values,
-            &data.get_x_uncertainty(),
-            &[LineWidth(1.5), Color("#4477AA")],
+        .set_title(&spec.title, &[title_font()])
+        .set_x_label(&spec.x_label, &[label_font()])
+        .set_y_label(&spec.y_label, &[label_font()])
+        .set_x_ticks(
+            ticks(scale.log_x(), &spec.x_ticks, &axis_x),
+            &[],
+            &[label_font()],
         )
-        .y_error_bars(
-            &x_values,
-            &y_values,
-            &data.get_y_uncertainty(),
-            &[LineWidth(1.5), Color("#4477AA")],
+        .set_y_ticks(
+            ticks(scale.log_y(), &spec.y_ticks, &axis_y),
+            &[],
+            &[label_font()],
         )
-        .set_legend(Graph(0.99), Graph(0.95), &[], &[Font("Times", 13.0)]);
-    // Three required lines
-    let line_best_fit = data.line_best_fit();
-    let y_best: Vec<f64> = ln_plt_x.iter().map(|x| line_best_fit.y(*x)).collect();
-    figure.lines(
-        &ln_plt_x,
-        &y_best,
-        &[
-            Caption(line_best_fit_name!(line_best_fit).as_str()),
-            LineWidth(2.0),
-            Color("#EE7733"),
-        ],
-    );
-    if let Some(line_min_grad) = data.line_min_grad() {
-        let y_min: Vec<f64> = ln_plt_x.iter().map(|x| line_min_grad.y(*x)).collect();
-        figure.lines(
-            &ln_plt_x,
-            &y_min,
-            &[
-                Caption(line_min_grad_name!(line_min_grad).as_str()),
-                LineStyle(Dash),
-                LineWidth(1.5),
-                Color("#009988"),
-            ],
+        .set_legend(
+            Graph(legend_x),
+            Graph(legend_y),
+            &[Placement(legend_align_x, legend_align_y)],
+            &[label_font()],
         );
+    if scale.log_x() {
+        figure.set_x_log(Some(10.0));
     }
-    if let Some(line_max_grad) = data.line_max_grad() {
-        let y_max: Vec<f64> = ln_plt_x.iter().map(|x| line_max_grad.y(*x)).collect();
-        figure.lines(
-            &ln_plt_x,
-            &y_max,
-            &[
-                Caption(line_max_grad_name!(line_max_grad).as_str()),
-                LineStyle(Dash),
-                LineWidth(1.5),
-                Color("#0077BB"),
-            ],
-        );
+    if scale.log_y() {
+        figure.set_y_log(Some(10.0));
+    }
+    // The x ranges of the chart and the residuals panel are the same
+    if fixed_x || fit.residuals {
+        figure.set_x_range(Fix(ln_plt_x[0]), Fix(ln_plt_x[1]));
+    }
+    if fixed_y {
+        figure.set_y_range(Fix(axis_y.start), Fix(axis_y.end));
+    }
+    if fit.residuals {
+        // The chart takes the top part. The left margins in screen
+        // coordinates are the same as those of the residuals panel, so that
+        // the x axes are aligned
+        figure
+            .set_pos(0.0, RESIDUALS_HEIGHT)
+            .set_size(1.0, 1.0 - RESIDUALS_HEIGHT)
+            .set_margins(&[MarginLeft(LEFT_MARGIN)]);
+    }
+    let (label_x, label_right) = label_x(spec.legend);
+    let label_align = if label_right { AlignRight } else { AlignLeft };
+    let mut label_y = LABEL_TOP;
+    for one in series {
+        let data = &one.data;
+        let colour = one.style.colour.to_string();
+        let colour = colour.as_str();
+        let (symbol, _) = marker_point(one.style.marker);
+        // Points excluded from the fits are faded
+        for excluded in [false, true] {
+            let point_colour = if excluded {
+                one.style.colour.faded().to_string()
+            } else {
+                one.style.colour.to_string()
+            };
+            let point_colour = point_colour.as_str();
+            // Points that cannot be shown on log scales are skipped
+            let points: Vec<_> = data
+                .iter()
+                .filter(|point| {
+                    point.excluded == excluded
+                        && (!scale.log_x() || point.x_value > 0.0)
+                        && (!scale.log_y() || point.y_value > 0.0)
+                })
+                .collect();
+            if points.is_empty() {
+                continue;
+            }
+            let x_values: Vec<f64> = points.iter().map(|point| point.x_value).collect();
+            let y_values: Vec<f64> = points.iter().map(|point| point.y_value).collect();
+            // Error bars are drawn around their centres so that they can be clipped
+            let x_bars: Vec<(f64, f64)> = points
+                .iter()
+                .map(|point| clipped_bar(point.x_value, point.x_uncertainty, lower_x))
+                .collect();
+            let y_bars: Vec<(f64, f64)> = points
+                .iter()
+                .map(|point| clipped_bar(point.y_value, point.y_uncertainty, lower_y))
+                .collect();
+            figure
+                // Plot error bars
+                .x_error_bars(
+                    &centre(&x_bars),
+                    &y_values,
+                    &half_width(&x_bars),
+                    &[LineWidth(1.5), Color(point_colour), PointSize(0.0)],
+                )
+                .y_error_bars(
+                    &x_values,
+                    &centre(&y_bars),
+                    &half_width(&y_bars),
+                    &[LineWidth(1.5), Color(point_colour), PointSize(0.0)],
+                )
+                // Scatter points "Real data"
+                .points(
+                    &x_values,
+                    &y_values,
+                    &[Color(point_colour), PointSymbol(symbol)],
+                );
+        }
+        match &one.fits {
+            FitResults::Line {
+                best_fit,
+                gradient_lines,
+            } => {
+                // Three lines, written to the places of the uncertainties of the best fit
+                let line_fit = best_fit.ok();
+                if let Some(line_fit) = &line_fit {
+                    let line_best_fit = line_fit.line;
+                    let y_best: Vec<f64> = line_x.iter().map(|x| line_best_fit.y(*x)).collect();
+                    figure.lines(
+                        line_x,
+                        &y_best,
+                        &[
+                            Caption(
+                                one.label(&line_best_fit_name!(
+                                    line_best_fit,
+                                    Some(line_fit),
+                                    spec.notation
+                                ))
+                                .as_str(),
+                            ),
+                            LineWidth(2.0),
+                            Color(colour),
+                        ],
+                    );
+                    figure.label(
+                        one.label(&line_fit_stats_name!(line_fit, spec.notation))
+                            .as_str(),
+                        Graph(label_x),
+                        Graph(label_y),
+                        &[TextAlign(label_align), label_font()],
+                    );
+                    label_y -= LABEL_SPACING;
+                }
+                match *gradient_lines {
+                    Ok((line_min_grad, line_max_grad)) => {
+                        let y_min: Vec<f64> = line_x.iter().map(|x| line_min_grad.y(*x)).collect();
+                        figure.lines(
+                            line_x,
+                            &y_min,
+                            &[
+                                Caption(
+                                    one.label(&line_min_grad_name!(
+                                        line_min_grad,
+                                        line_fit.as_ref(),
+                                        spec.notation
+                                    ))
+                                    .as_str(),
+                                ),
+                                LineStyle(Dash),
+                                LineWidth(1.5),
+                                Color(colour),
+                            ],
+                        );
+                        let y_max: Vec<f64> = line_x.iter().map(|x| line_max_grad.y(*x)).collect();
+                        figure.lines(
+                            line_x,
+                            &y_max,
+                            &[
+                                Caption(
+                                    one.label(&line_max_grad_name!(
+                                        line_max_grad,
+                                        line_fit.as_ref(),
+                                        spec.notation
+                                    ))
+                                    .as_str(),
+                                ),
+                                LineStyle(Dash),
+                                LineWidth(1.5),
+                                Color(colour),
+                            ],
+                        );
+                    }
+                    Err(error) if fit.gradient_line_method == GradientLineMethod::AllPoints => {
+                        figure.label(
+                            one.label(&no_grad_lines_name!(error)).as_str(),
+                            Graph(label_x),
+                            Graph(label_y),
+                            &[TextAlign(label_align), label_font()],
+                        );
+                        label_y -= LABEL_SPACING;
+                    }
+                    Err(_) => {}
+                }
+            }
+            FitResults::Polynomial(polynomial) => {
+                if let Some(polynomial) = polynomial {
+                    let curve_y: Vec<f64> = curve_x.iter().map(|x| polynomial.y(*x)).collect();
+                    figure.lines(
+                        &curve_x,
+                        &curve_y,
+                        &[
+                            Caption(
+                                one.label(&polynomial_best_fit_name!(polynomial, spec.notation))
+                                    .as_str(),
+                            ),
+                            LineWidth(2.0),
+                            Color(colour),
+                        ],
+                    );
+                }
+            }
+            FitResults::Expression(expression_fit) => {
+                if let Ok(expression_fit) = expression_fit {
+                    let curve_y: Vec<f64> = curve_x.iter().map(|x| expression_fit.y(*x)).collect();
+                    let expression = &expression_fit.expression;
+                    figure.lines(
+                        &curve_x,
+                        &curve_y,
+                        &[
+                            Caption(one.label(&line_best_fit_name!(expression)).as_str()),
+                            LineWidth(2.0),
+                            Color(colour),
+                        ],
+                    );
+                    figure.label(
+                        one.label(&expression_fit_stats_name!(expression_fit, spec.notation))
+                            .as_str(),
+                        Graph(label_x),
+                        Graph(label_y),
+                        &[TextAlign(label_align), label_font()],
+                    );
+                    label_y -= LABEL_SPACING;
+                }
+            }
+        }
+        if spec.intercepts_shown() {
+            if let Some((x_intercept, y_intercept)) = line_intercepts(spec, &one.fits) {
+                // Below the x intercept and above the y intercept so that
+                // both can be read near the origin
+                let annotations = [
+                    (x_intercept.value, 0.0, x_intercept_name!(x_intercept), -1.0),
+                    (0.0, y_intercept.value, y_intercept_name!(y_intercept), 1.0),
+                ];
+                // Labels off the chart are not clipped
+                for (x, y, text, offset_y) in annotations {
+                    if axis_x.contains(&x) && axis_y.contains(&y) {
+                        figure.label(
+                            one.label(&text).as_str(),
+                            Axis(x),
+                            Axis(y),
+                            &[
+                                MarkerSymbol(INTERCEPT_POINT.0),
+                                MarkerColor(colour),
+                                TextColor(colour),
+                                TextOffset(1.0, offset_y),
+                                label_font(),
+                            ],
+                        );
+                    }
+                }
+            }
+        }
+    }
+    if fit.residuals {
+        let residuals_panel = fg
+            .axes2d()
+            .set_pos(0.0, 0.0)
+            .set_size(1.0, RESIDUALS_HEIGHT)
+            .set_margins(&[MarginLeft(LEFT_MARGIN)])
+            .set_x_label(&spec.x_label, &[label_font()])
+            .set_y_label("Residual", &[label_font()])
+            .set_x_ticks(
+                ticks(scale.log_x(), &spec.x_ticks, &axis_x),
+                &[],
+                &[label_font()],
+            )
+            .set_y_ticks(Some((Auto, 1)), &[], &[label_font()])
+            .set_x_range(Fix(ln_plt_x[0]), Fix(ln_plt_x[1]));
+        if scale.log_x() {
+            residuals_panel.set_x_log(Some(10.0));
+        }
+        residuals_panel.lines(&ln_plt_x, &[0.0, 0.0], &[Color("black")]);
+        for one in series {
+            let residuals = match one.data.residuals(&one.fits, fit.fit_method) {
+                Some(residuals) => residuals,
+                None => continue,
+            };
+            for excluded in [false, true] {
+                // Points that cannot be shown on log scales are skipped
+                let points: Vec<_> = residuals
+                    .iter()
+                    .filter(|point| {
+                        point.excluded == excluded && (!scale.log_x() || point.x_value > 0.0)
+                    })
+                    .collect();
+                if points.is_empty() {
+                    continue;
+                }
+                let colour = if excluded {
+                    one.style.colour.faded()
+                } else {
+                    one.style.colour
+                };
+                residuals_panel.y_error_bars(
+                    points.iter().map(|point| point.x_value),
+                    points.iter().map(|point| point.y_value),
+                    points.iter().map(|point| point.y_uncertainty),
+                    &[
+                        LineWidth(1.5),
+                        Color(colour.to_string().as_str()),
+                        PointSymbol(marker_point(one.style.marker).0),
+                    ],
+                );
+            }
+        }
     }
     match save {
         Some(save) => {
-            fg.save_to_png(save.path, save.width, save.height)?;
+            let (width, height) = spec.size.into();
+            let (width_in, height_in) = spec.size.in_inches();
+            match save.format {
+                ImageFormat::Png => fg.save_to_png(save.path, width, height)?,
+                ImageFormat::Svg => fg.save_to_svg(save.path, width, height)?,
+                ImageFormat::Pdf => fg.save_to_pdf(save.path, width_in, height_in)?,
+                ImageFormat::Eps => fg.save_to_eps(save.path, width_in, height_in)?,
+            };
         }
         None => {
             fg.show()?;
@@ -113,3 +421,42 @@ pub fn gnuplot(
     };
     Ok(())
 }
+
+/// The gnuplot backend, which shows the graph in its own window and saves
+/// all formats
+pub struct GnuplotRenderer;
+
+impl Renderer for GnuplotRenderer {
+    fn name(&self) -> &'static str {
+        "gnuplot"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "GNU Plot"
+    }
+
+    fn can_save(&self, _format: ImageFormat) -> bool {
+        true
+    }
+
+    fn save(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+        save: SaveOptions<'_>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(gnuplot(spec, series, Some(save))?)
+    }
+
+    fn can_show(&self) -> bool {
+        true
+    }
+
+    fn show(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(gnuplot(spec, series, None)?)
+    }
+}
@@ -17,126 +17,572 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
-use crate::data::TwoVarDataSet;
+use crate::data::{FitResults, GradientLineMethod, TwoVarDataPoint, TwoVarDataSet};
+use crate::plot::{
+    axis_range, axis_ranges, clipped_bar, fit_x_range, line_intercepts, samples, visible_segments,
+    Colour, FittedSeries, ImageFormat, LegendPosition, Marker, PlotSpec, Renderer, RendererError,
+    SaveOptions, TickSpacing, CURVE_SAMPLES,
+};
+use plotters::coord::ranged1d::{KeyPointHint, NoDefaultFormatting, ValueFormatter};
+use plotters::coord::types::RangedCoordf64;
 use plotters::prelude::*;
-use plotters::style::RGBColor;
-
-macro_rules! line_best_fit_style {
-    () => {
-        ShapeStyle {
-            color: RGBColor(238, 119, 51).to_rgba(),
-            filled: true,
-            stroke_width: 2,
-        }
-    };
+use plotters::style::{RGBColor, RelativeSize};
+use std::ops::Range;
+
+/// Style of the line of best fit
+fn line_best_fit_style(colour: Colour) -> ShapeStyle {
+    ShapeStyle {
+        color: RGBColor(colour.0, colour.1, colour.2).to_rgba(),
+        filled: true,
+        stroke_width: 2,
+    }
+}
+
+/// Style of the max/min-gradient lines
+fn line_grad_style(colour: Colour) -> ShapeStyle {
+    ShapeStyle {
+        color: RGBColor(colour.0, colour.1, colour.2).mix(0.6),
+        filled: true,
+        stroke_width: 1,
+    }
+}
+
+/// Colour of the markers and the error bars, faded if excluded from the fits
+fn point_colour(colour: Colour, excluded: bool) -> RGBColor {
+    let colour = if excluded { colour.faded() } else { colour };
+    RGBColor(colour.0, colour.1, colour.2)
+}
+
+/// Linear coordinate with the labelled ticks at the positions of the spec
+struct Ticked {
+    inner: RangedCoordf64,
+    /// Positions of the ticks, chosen by plotters if None
+    major: Option<Vec<f64>>,
 }
 
-macro_rules! line_min_grad_style {
-    () => {
-        ShapeStyle {
-            color: RGBColor(0, 153, 136).to_rgba(),
-            filled: true,
-            stroke_width: 1,
+impl Ticked {
+    fn new(range: &Range<f64>, ticks: &TickSpacing) -> Self {
+        Self {
+            inner: range.clone().into(),
+            major: ticks.major_ticks(range),
         }
-    };
+    }
 }
 
-macro_rules! line_max_grad_style {
-    () => {
-        ShapeStyle {
-            color: RGBColor(0, 119, 187).to_rgba(),
-            filled: true,
-            stroke_width: 1,
+impl Ranged for Ticked {
+    type FormatOption = NoDefaultFormatting;
+    type ValueType = f64;
+
+    fn range(&self) -> Range<f64> {
+        self.inner.range()
+    }
+
+    fn map(&self, value: &f64, limit: (i32, i32)) -> i32 {
+        self.inner.map(value, limit)
+    }
+
+    fn key_points<Hint: KeyPointHint>(&self, hint: Hint) -> Vec<f64> {
+        match &self.major {
+            Some(major) => major.clone(),
+            None => self.inner.key_points(hint),
         }
-    };
+    }
+}
+
+impl ValueFormatter<f64> for Ticked {
+    fn format(value: &f64) -> String {
+        RangedCoordf64::format(value)
+    }
+}
+
+/// Draw the unlabelled ticks inwards from the bottom and left edges, since
+/// plotters only draws the labelled ones
+fn draw_minor_ticks<'a, DB: DrawingBackend + 'a, X, Y>(
+    ctx: &mut ChartContext<'a, DB, Cartesian2d<X, Y>>,
+    x_ticks: Vec<f64>,
+    y_ticks: Vec<f64>,
+) -> Result<(), plotters::drawing::DrawingAreaErrorKind<DB::ErrorType>>
+where
+    X: Ranged<ValueType = f64>,
+    Y: Ranged<ValueType = f64>,
+{
+    let axis_x = ctx.x_range();
+    let axis_y = ctx.y_range();
+    ctx.draw_series(x_ticks.into_iter().map(|x| {
+        EmptyElement::at((x, axis_y.start)) + PathElement::new(vec![(0, 0), (0, -4)], BLACK)
+    }))?;
+    ctx.draw_series(y_ticks.into_iter().map(|y| {
+        EmptyElement::at((axis_x.start, y)) + PathElement::new(vec![(0, 0), (4, 0)], BLACK)
+    }))?;
+    Ok(())
 }
 
 /// Generic plotter for all kinds of backends.
 /// WARNING: Cannot proceed with empty values
 pub fn plotters<ET: std::error::Error + Send + Sync, T: DrawingBackend<ErrorType = ET>>(
-    title: &str,
-    x_label: &str,
-    y_label: &str,
-    data: &TwoVarDataSet,
+    spec: &PlotSpec,
+    series: &[FittedSeries],
     backend: T,
 ) -> Result<(), plotters::drawing::DrawingAreaErrorKind<ET>> {
     // Those generic type parameters are so dreadful
-    // Extra length before min and after max
-    let extrax = (data.max_x(false) - data.min_x(false)) * 0.1;
-    let extray = (data.max_y(false) - data.min_y(false)) * 0.1;
-    // Axis ranges
-    let axis_x = (data.min_x(true) - extrax)..(data.max_x(true) + extrax);
-    let axis_y = (data.min_y(true) - extray)..(data.max_y(true) + extray);
-    // Points for plotting the lines
-    let plot_x = Vec::from([data.min_x(true) - extrax, data.max_x(true) + extrax]);
+    let scale = spec.axis_scale;
+    let fit = &spec.fit;
+    let (axis_x, axis_y) = axis_ranges(spec, series);
+    let max_y = series
+        .iter()
+        .map(|one| one.data.max_y(false))
+        .fold(f64::NEG_INFINITY, f64::max);
     // Create drawing area
     let root_drawing_area = backend.into_drawing_area();
     root_drawing_area.fill(&WHITE)?;
-    let mut ctx = ChartBuilder::on(&root_drawing_area)
+    // The residuals take the bottom part if shown
+    let (main_drawing_area, residuals_drawing_area) = if fit.residuals {
+        let (upper, lower) = root_drawing_area.split_vertically(RelativeSize::Height(0.7));
+        (upper, Some(lower))
+    } else {
+        (root_drawing_area, None)
+    };
+    let left_label_area_size = (16.0 * max_y.log10()) as u32;
+    let mut chart = ChartBuilder::on(&main_drawing_area);
+    chart
         .margin(5)
-        .caption(title, ("Times", 22))
-        .set_label_area_size(
-            LabelAreaPosition::Left,
-            (16.0 * data.max_y(false).log10()) as u32,
-        )
-        .set_label_area_size(LabelAreaPosition::Bottom, 40)
-        .build_cartesian_2d(axis_x, axis_y)?;
+        .caption(&spec.title, spec.title_font())
+        .set_label_area_size(LabelAreaPosition::Left, left_label_area_size)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40);
+    // Each combination of scales is a different type of chart, and the
+    // spacing of the ticks is only kept on linear axes
+    let ticked_x = Ticked::new(&axis_x, &spec.x_ticks);
+    let ticked_y = Ticked::new(&axis_y, &spec.y_ticks);
+    match (scale.log_x(), scale.log_y()) {
+        (false, false) => draw_chart(chart.build_cartesian_2d(ticked_x, ticked_y)?, spec, series),
+        (true, false) => draw_chart(
+            chart.build_cartesian_2d(axis_x.clone().log_scale(), ticked_y)?,
+            spec,
+            series,
+        ),
+        (false, true) => draw_chart(
+            chart.build_cartesian_2d(ticked_x, axis_y.log_scale())?,
+            spec,
+            series,
+        ),
+        (true, true) => draw_chart(
+            chart.build_cartesian_2d(axis_x.clone().log_scale(), axis_y.log_scale())?,
+            spec,
+            series,
+        ),
+    }?;
+    if let Some(residuals_drawing_area) = residuals_drawing_area {
+        let residuals: Vec<_> = series
+            .iter()
+            .map(|one| one.data.residuals(&one.fits, fit.fit_method))
+            .collect();
+        // Residuals are always on a linear scale
+        let axis_residual = axis_range(
+            residuals
+                .iter()
+                .flatten()
+                .flat_map(|dataset| dataset.iter())
+                .map(|point| (point.y_value, point.y_uncertainty)),
+            false,
+        );
+        let mut chart = ChartBuilder::on(&residuals_drawing_area);
+        // The same label area keeps the x axes aligned
+        chart
+            .margin(5)
+            .set_label_area_size(LabelAreaPosition::Left, left_label_area_size)
+            .set_label_area_size(LabelAreaPosition::Bottom, 40);
+        if scale.log_x() {
+            draw_residuals(
+                chart.build_cartesian_2d(axis_x.log_scale(), axis_residual)?,
+                spec,
+                series,
+                &residuals,
+            )?;
+        } else {
+            draw_residuals(
+                chart.build_cartesian_2d(Ticked::new(&axis_x, &spec.x_ticks), axis_residual)?,
+                spec,
+                series,
+                &residuals,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Draw markers of the shape at the centres
+fn draw_markers<'a, DB: DrawingBackend + 'a, X, Y, I>(
+    ctx: &mut ChartContext<'a, DB, Cartesian2d<X, Y>>,
+    centres: I,
+    marker: Marker,
+    colour: RGBColor,
+) -> Result<(), plotters::drawing::DrawingAreaErrorKind<DB::ErrorType>>
+where
+    X: Ranged<ValueType = f64>,
+    Y: Ranged<ValueType = f64>,
+    I: Iterator<Item = (f64, f64)>,
+{
+    match marker {
+        Marker::Plus => ctx.draw_series(centres.map(|centre| {
+            EmptyElement::at(centre)
+                + PathElement::new(vec![(-4, 0), (4, 0)], colour)
+                + PathElement::new(vec![(0, -4), (0, 4)], colour)
+        }))?,
+        Marker::Cross => ctx.draw_series(centres.map(|centre| Cross::new(centre, 4, colour)))?,
+        Marker::Circle => ctx.draw_series(centres.map(|centre| Circle::new(centre, 4, colour)))?,
+        Marker::Square => {
+            ctx.draw_series(centres.map(|centre| {
+                EmptyElement::at(centre) + Rectangle::new([(-4, -4), (4, 4)], colour)
+            }))?
+        }
+        Marker::Triangle => {
+            ctx.draw_series(centres.map(|centre| TriangleMarker::new(centre, 5, colour)))?
+        }
+    };
+    Ok(())
+}
+
+/// Draw the residuals of every series with their y error bars around zero
+fn draw_residuals<'a, DB: DrawingBackend + 'a, X>(
+    mut ctx: ChartContext<'a, DB, Cartesian2d<X, RangedCoordf64>>,
+    spec: &PlotSpec,
+    series: &[FittedSeries],
+    residuals: &[Option<TwoVarDataSet>],
+) -> Result<(), plotters::drawing::DrawingAreaErrorKind<DB::ErrorType>>
+where
+    X: Ranged<ValueType = f64> + ValueFormatter<f64>,
+{
+    let scale = spec.axis_scale;
+    let axis_x = ctx.x_range();
     ctx.configure_mesh()
         .disable_mesh()
-        .x_desc(x_label)
-        .y_desc(y_label)
-        .axis_desc_style(("Times", 13))
+        .x_desc(&spec.x_label)
+        .y_desc("Residual")
+        // The panel is short
+        .y_labels(5)
+        .label_style(spec.label_font())
+        .axis_desc_style(spec.label_font())
         .draw()?;
-    // Three lines
-    let line_best_fit = data.line_best_fit();
+    if !scale.log_x() {
+        draw_minor_ticks(&mut ctx, spec.x_ticks.minor_ticks(&axis_x), Vec::new())?;
+    }
     ctx.draw_series(LineSeries::new(
-        plot_x.iter().map(|x| (*x, line_best_fit.y(*x))),
-        line_best_fit_style!(),
-    ))?
-    .label(line_best_fit_name!(line_best_fit).as_str())
-    .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], line_best_fit_style!()));
-    if let Some(line_min_grad) = data.line_min_grad() {
-        ctx.draw_series(LineSeries::new(
-            plot_x.iter().map(|x| (*x, line_min_grad.y(*x))),
-            line_min_grad_style!(),
-        ))?
-        .label(line_min_grad_name!(line_min_grad).as_str())
-        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], line_min_grad_style!()));
-    }
-    if let Some(line_max_grad) = data.line_max_grad() {
-        ctx.draw_series(LineSeries::new(
-            plot_x.iter().map(|x| (*x, line_max_grad.y(*x))),
-            line_max_grad_style!(),
-        ))?
-        .label(line_max_grad_name!(line_max_grad).as_str())
-        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], line_max_grad_style!()));
-    }
-    // Scatter series and uncertainties
-    /*ctx.draw_series(
-        data.iter().map(|point|
-            Circle::new((point.x_value, point.y_value), 5, &BLUE)
-        ),
-    )?;*/
-    ctx.draw_series(data.iter().map(|point| {
-        ErrorBar::new_vertical(
-            point.x_value,
-            point.y_value - point.y_uncertainty,
-            point.y_value,
-            point.y_value + point.y_uncertainty,
-            &BLUE,
-            10,
-        )
-    }))?;
-    ctx.draw_series(data.iter().map(|point| {
-        ErrorBar::new_horizontal(
-            point.y_value,
-            point.x_value - point.x_uncertainty,
-            point.x_value,
-            point.x_value + point.x_uncertainty,
-            &BLUE,
-            10,
-        )
-    }))?;
-    ctx.configure_series_labels().border_style(&BLACK).draw()?;
+        [(axis_x.start, 0.0), (axis_x.end, 0.0)],
+        BLACK.mix(0.6),
+    ))?;
+    for (one, residuals) in series.iter().zip(residuals) {
+        let residuals = match residuals {
+            Some(residuals) => residuals,
+            None => continue,
+        };
+        for excluded in [false, true] {
+            let colour = point_colour(one.style.colour, excluded);
+            // Points that cannot be shown on log scales are skipped
+            let visible = |point: &&TwoVarDataPoint| {
+                point.excluded == excluded && (!scale.log_x() || point.x_value > 0.0)
+            };
+            draw_markers(
+                &mut ctx,
+                residuals
+                    .iter()
+                    .filter(visible)
+                    .map(|point| (point.x_value, point.y_value)),
+                one.style.marker,
+                colour,
+            )?;
+            ctx.draw_series(residuals.iter().filter(visible).map(|point| {
+                ErrorBar::new_vertical(
+                    point.x_value,
+                    point.y_value - point.y_uncertainty,
+                    point.y_value,
+                    point.y_value + point.y_uncertainty,
+                    colour,
+                    10,
+                )
+            }))?;
+        }
+    }
     Ok(())
 }
+
+/// Draw the axes, the fitted lines and the data on a chart of any scale
+fn draw_chart<'a, DB: DrawingBackend + 'a, X, Y>(
+    mut ctx: ChartContext<'a, DB, Cartesian2d<X, Y>>,
+    spec: &PlotSpec,
+    series: &[FittedSeries],
+) -> Result<(), plotters::drawing::DrawingAreaErrorKind<DB::ErrorType>>
+where
+    X: Ranged<ValueType = f64> + ValueFormatter<f64>,
+    Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+{
+    let scale = spec.axis_scale;
+    let fit = &spec.fit;
+    let axis_x = ctx.x_range();
+    let axis_y = ctx.y_range();
+    // Points for plotting the lines, which are curves on log scales, across
+    // the chart or the part of the extrapolated range on it
+    let fit_x = fit_x_range(spec).map_or_else(
+        || axis_x.clone(),
+        |range| range.start.max(axis_x.start)..range.end.min(axis_x.end),
+    );
+    let plot_x = if fit_x.start < fit_x.end {
+        samples(fit_x.start, fit_x.end, CURVE_SAMPLES, scale.log_x())
+    } else {
+        Vec::new()
+    };
+    // Out-of-range points would otherwise be clamped to the edges
+    let curve =
+        |f: &dyn Fn(f64) -> f64| visible_segments(plot_x.iter().map(|x| (*x, f(*x))), &axis_y);
+    ctx.configure_mesh()
+        .disable_mesh()
+        .x_desc(&spec.x_label)
+        .y_desc(&spec.y_label)
+        .label_style(spec.label_font())
+        .axis_desc_style(spec.label_font())
+        .draw()?;
+    let minor_ticks = |log: bool, ticks: &TickSpacing, range: &Range<f64>| {
+        if log {
+            Vec::new()
+        } else {
+            ticks.minor_ticks(range)
+        }
+    };
+    draw_minor_ticks(
+        &mut ctx,
+        minor_ticks(scale.log_x(), &spec.x_ticks, &axis_x),
+        minor_ticks(scale.log_y(), &spec.y_ticks, &axis_y),
+    )?;
+    for one in series {
+        let data = &one.data;
+        let best_fit_style = line_best_fit_style(one.style.colour);
+        let grad_style = line_grad_style(one.style.colour);
+        match &one.fits {
+            FitResults::Line {
+                best_fit,
+                gradient_lines,
+            } => {
+                // Three lines, written to the places of the uncertainties of the best fit
+                let line_fit = best_fit.ok();
+                if let Some(line_fit) = &line_fit {
+                    let line_best_fit = line_fit.line;
+                    ctx.draw_series(
+                        curve(&|x| line_best_fit.y(x))
+                            .into_iter()
+                            .map(|segment| PathElement::new(segment, best_fit_style)),
+                    )?
+                    .label(one.label(&line_best_fit_name!(
+                        line_best_fit,
+                        Some(line_fit),
+                        spec.notation
+                    )))
+                    .legend(move |(x, y)| {
+                        PathElement::new(vec![(x, y), (x + 20, y)], best_fit_style)
+                    });
+                    // Legend-only entry for the statistics
+                    ctx.draw_series(LineSeries::new(std::iter::empty(), best_fit_style))?
+                        .label(one.label(&line_fit_stats_name!(line_fit, spec.notation)))
+                        .legend(|(x, y)| EmptyElement::at((x, y)));
+                }
+                match *gradient_lines {
+                    Ok((line_min_grad, line_max_grad)) => {
+                        ctx.draw_series(
+                            curve(&|x| line_min_grad.y(x))
+                                .into_iter()
+                                .map(|segment| PathElement::new(segment, grad_style)),
+                        )?
+                        .label(one.label(&line_min_grad_name!(
+                            line_min_grad,
+                            line_fit.as_ref(),
+                            spec.notation
+                        )))
+                        .legend(move |(x, y)| {
+                            PathElement::new(vec![(x, y), (x + 20, y)], grad_style)
+                        });
+                        ctx.draw_series(
+                            curve(&|x| line_max_grad.y(x))
+                                .into_iter()
+                                .map(|segment| PathElement::new(segment, grad_style)),
+                        )?
+                        .label(one.label(&line_max_grad_name!(
+                            line_max_grad,
+                            line_fit.as_ref(),
+                            spec.notation
+                        )))
+                        .legend(move |(x, y)| {
+                            PathElement::new(vec![(x, y), (x + 20, y)], grad_style)
+                        });
+                    }
+                    Err(error) if fit.gradient_line_method == GradientLineMethod::AllPoints => {
+                        ctx.draw_series(LineSeries::new(std::iter::empty(), grad_style))?
+                            .label(one.label(&no_grad_lines_name!(error)))
+                            .legend(|(x, y)| EmptyElement::at((x, y)));
+                    }
+                    Err(_) => {}
+                }
+            }
+            FitResults::Polynomial(polynomial) => {
+                if let Some(polynomial) = polynomial {
+                    ctx.draw_series(
+                        curve(&|x| polynomial.y(x))
+                            .into_iter()
+                            .map(|segment| PathElement::new(segment, best_fit_style)),
+                    )?
+                    .label(one.label(&polynomial_best_fit_name!(polynomial, spec.notation)))
+                    .legend(move |(x, y)| {
+                        PathElement::new(vec![(x, y), (x + 20, y)], best_fit_style)
+                    });
+                }
+            }
+            FitResults::Expression(expression_fit) => {
+                if let Ok(expression_fit) = expression_fit {
+                    let expression = &expression_fit.expression;
+                    ctx.draw_series(
+                        curve(&|x| expression_fit.y(x))
+                            .into_iter()
+                            .map(|segment| PathElement::new(segment, best_fit_style)),
+                    )?
+                    .label(one.label(&line_best_fit_name!(expression)))
+                    .legend(move |(x, y)| {
+                        PathElement::new(vec![(x, y), (x + 20, y)], best_fit_style)
+                    });
+                    ctx.draw_series(LineSeries::new(std::iter::empty(), best_fit_style))?
+                        .label(
+                            one.label(&expression_fit_stats_name!(expression_fit, spec.notation)),
+                        )
+                        .legend(|(x, y)| EmptyElement::at((x, y)));
+                }
+            }
+        }
+        if spec.intercepts_shown() {
+            if let Some((x_intercept, y_intercept)) = line_intercepts(spec, &one.fits) {
+                let colour = point_colour(one.style.colour, false);
+                let font = TextStyle::from(spec.label_font()).color(&colour);
+                // To the right of the markers, below the x intercept and above
+                // the y intercept so that both can be read near the origin
+                let annotations = [
+                    ((x_intercept.value, 0.0), x_intercept_name!(x_intercept), 6),
+                    (
+                        (0.0, y_intercept.value),
+                        y_intercept_name!(y_intercept),
+                        -6 - spec.fonts.label_size as i32,
+                    ),
+                ];
+                // Intercepts off the chart would be clamped to the edges
+                ctx.draw_series(
+                    annotations
+                        .into_iter()
+                        .filter(|((x, y), _, _)| axis_x.contains(x) && axis_y.contains(y))
+                        .map(|(position, text, text_y)| {
+                            EmptyElement::at(position)
+                                + Circle::new((0, 0), 4, colour.filled())
+                                + Text::new(one.label(&text), (6, text_y), font.clone())
+                        }),
+                )?;
+            }
+        }
+        // Scatter series and uncertainties
+        for excluded in [false, true] {
+            let colour = point_colour(one.style.colour, excluded);
+            // Points that cannot be shown on log scales are skipped
+            let visible = |point: &&TwoVarDataPoint| {
+                point.excluded == excluded
+                    && (!scale.log_x() || point.x_value > 0.0)
+                    && (!scale.log_y() || point.y_value > 0.0)
+            };
+            draw_markers(
+                &mut ctx,
+                data.iter()
+                    .filter(visible)
+                    .map(|point| (point.x_value, point.y_value)),
+                one.style.marker,
+                colour,
+            )?;
+            ctx.draw_series(data.iter().filter(visible).map(|point| {
+                let (low, high) = clipped_bar(point.y_value, point.y_uncertainty, axis_y.start);
+                ErrorBar::new_vertical(point.x_value, low, point.y_value, high, colour, 10)
+            }))?;
+            ctx.draw_series(data.iter().filter(visible).map(|point| {
+                let (low, high) = clipped_bar(point.x_value, point.x_uncertainty, axis_x.start);
+                ErrorBar::new_horizontal(point.y_value, low, point.x_value, high, colour, 10)
+            }))?;
+        }
+    }
+    ctx.configure_series_labels()
+        .position(match spec.legend {
+            LegendPosition::UpperLeft => SeriesLabelPosition::UpperLeft,
+            LegendPosition::UpperRight => SeriesLabelPosition::UpperRight,
+            LegendPosition::LowerLeft => SeriesLabelPosition::LowerLeft,
+            LegendPosition::LowerRight => SeriesLabelPosition::LowerRight,
+        })
+        .label_font(spec.label_font())
+        .border_style(&BLACK)
+        .draw()?;
+    Ok(())
+}
+
+/// The plotters backend, which draws on bitmaps, SVG documents and cairo
+/// contexts but has no window of its own
+pub struct PlottersRenderer;
+
+impl Renderer for PlottersRenderer {
+    fn name(&self) -> &'static str {
+        "plotters"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Plotters"
+    }
+
+    fn can_save(&self, format: ImageFormat) -> bool {
+        // PDF and EPS need a cairo surface, which only the GTK interface has
+        matches!(format, ImageFormat::Png | ImageFormat::Svg)
+    }
+
+    fn save(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+        save: SaveOptions<'_>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match save.format {
+            ImageFormat::Png => Ok(plotters(
+                spec,
+                series,
+                BitMapBackend::new(save.path, spec.size.into()),
+            )?),
+            ImageFormat::Svg => Ok(plotters(
+                spec,
+                series,
+                SVGBackend::new(save.path, spec.size.into()),
+            )?),
+            format => Err(Box::new(RendererError::CannotSave(self.name(), format))),
+        }
+    }
+
+    fn svg(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut svg = String::new();
+        plotters(
+            spec,
+            series,
+            SVGBackend::with_string(&mut svg, spec.size.into()),
+        )?;
+        Ok(svg)
+    }
+}
+
+#[cfg(feature = "ui_gtk")]
+impl crate::plot::CairoRenderer for PlottersRenderer {
+    fn draw_cairo(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+        context: &cairo::Context,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let backend = plotters_cairo::CairoBackend::new(context, spec.size.into())?;
+        Ok(plotters(spec, series, backend)?)
+    }
+}
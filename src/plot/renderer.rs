@@ -0,0 +1,391 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::plot::{
+    FittedSeries, GnuplotRenderer, ImageFormat, PlotSpec, PlottersRenderer, SaveOptions,
+};
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// Something that draws graphs, such as gnuplot or plotters.
+///
+/// Front ends only know renderers through this trait, so a renderer
+/// registered with `register` is offered next to the built-in ones.
+/// Only `name`, `can_save` and `save` are required, the other ways of
+/// showing a graph are refused unless implemented. The series come with
+/// their fits for the fit options of the spec. Renderers which can also
+/// draw for the GTK interface implement `CairoRenderer`
+pub trait Renderer: Sync {
+    /// Name in save files and on the command line, in lower case
+    fn name(&self) -> &'static str;
+
+    /// Name shown in the graphical interfaces
+    fn display_name(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Whether the renderer can save the graph in the format
+    fn can_save(&self, format: ImageFormat) -> bool;
+
+    /// Save the graph to the file, in a format accepted by `can_save`
+    fn save(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+        save: SaveOptions<'_>,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Whether the renderer can show the graph in a window of its own
+    fn can_show(&self) -> bool {
+        false
+    }
+
+    /// Show the graph in a window of its own
+    fn show(&self, _spec: &PlotSpec, _series: &[FittedSeries]) -> Result<(), Box<dyn Error>> {
+        Err(Box::new(RendererError::CannotShow(self.name())))
+    }
+
+    /// Draw the graph as an SVG document, for front ends which can neither
+    /// open windows nor write files, such as the web one
+    fn svg(&self, _spec: &PlotSpec, _series: &[FittedSeries]) -> Result<String, Box<dyn Error>> {
+        Err(Box::new(RendererError::CannotDraw(self.name())))
+    }
+}
+
+/// A renderer which can draw on cairo contexts, for the preview and the PDF
+/// and EPS surfaces of the GTK interface. Registered with `register_cairo`
+#[cfg(feature = "ui_gtk")]
+pub trait CairoRenderer: Renderer {
+    /// Draw the graph on a cairo context of the size of the spec
+    fn draw_cairo(
+        &self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+        context: &cairo::Context,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+/// Error when a renderer is asked for something it cannot do
+#[derive(Clone, Debug)]
+pub enum RendererError {
+    CannotShow(&'static str),
+    CannotDraw(&'static str),
+    CannotSave(&'static str, ImageFormat),
+    /// A renderer of the same name is already registered
+    DuplicateName(&'static str),
+}
+
+impl std::fmt::Display for RendererError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RendererError::CannotShow(name) => {
+                write!(f, "The {} backend cannot show the graph", name)
+            }
+            RendererError::CannotDraw(name) => {
+                write!(f, "The {} backend cannot draw the graph here", name)
+            }
+            RendererError::CannotSave(name, format) => write!(
+                f,
+                "The {} backend cannot save {}",
+                name,
+                format.extension().to_uppercase()
+            ),
+            RendererError::DuplicateName(name) => {
+                write!(f, "A backend named {} is already registered", name)
+            }
+        }
+    }
+}
+
+impl Error for RendererError {}
+
+/// Renderers added by `register`, after the built-in ones
+static REGISTERED: RwLock<Vec<&'static dyn Renderer>> = RwLock::new(Vec::new());
+
+/// Offer a renderer in all front ends, unless its name is taken
+pub fn register(renderer: &'static dyn Renderer) -> Result<(), RendererError> {
+    let name = renderer.name();
+    // Checked under the same lock as the push, so that only one of two
+    // renderers of the same name registered at once is taken
+    let mut registered = REGISTERED.write().unwrap();
+    let taken = [Backends::GNUPLOT, Backends::PLOTTERS]
+        .iter()
+        .map(|backend| backend.name())
+        .chain(registered.iter().map(|other| other.name()))
+        .any(|other| other == name);
+    if taken {
+        return Err(RendererError::DuplicateName(name));
+    }
+    registered.push(renderer);
+    Ok(())
+}
+
+/// Renderers added by `register_cairo`, which are also in `REGISTERED`
+#[cfg(feature = "ui_gtk")]
+static REGISTERED_CAIRO: RwLock<Vec<&'static dyn CairoRenderer>> = RwLock::new(Vec::new());
+
+/// Offer a renderer in all front ends, drawing in the window of the GTK
+/// interface, unless its name is taken
+#[cfg(feature = "ui_gtk")]
+pub fn register_cairo<R: CairoRenderer>(renderer: &'static R) -> Result<(), RendererError> {
+    // Held while registering, so that the renderer draws in the window as
+    // soon as it is offered
+    let mut registered_cairo = REGISTERED_CAIRO.write().unwrap();
+    register(renderer)?;
+    registered_cairo.push(renderer);
+    Ok(())
+}
+
+/// A renderer of the registry, saved by its name
+#[derive(Copy, Clone)]
+pub struct Backends(&'static dyn Renderer);
+
+impl Backends {
+    pub const GNUPLOT: Backends = Backends(&GnuplotRenderer);
+    pub const PLOTTERS: Backends = Backends(&PlottersRenderer);
+
+    /// All renderers, the built-in ones first, in the order shown to the user
+    #[must_use]
+    pub fn all() -> Vec<Backends> {
+        let mut all = vec![Backends::GNUPLOT, Backends::PLOTTERS];
+        all.extend(REGISTERED.read().unwrap().iter().copied().map(Backends));
+        all
+    }
+
+    /// The renderer as a `CairoRenderer`, if it can draw on cairo contexts
+    #[cfg(feature = "ui_gtk")]
+    #[must_use]
+    pub fn cairo(self) -> Option<&'static dyn CairoRenderer> {
+        let built_in: [&'static dyn CairoRenderer; 1] = [&PlottersRenderer];
+        let registered = REGISTERED_CAIRO.read().unwrap();
+        built_in
+            .into_iter()
+            .chain(registered.iter().copied())
+            .find(|renderer| renderer.name() == self.name())
+    }
+
+    /// Check that the renderer can save in the format before saving
+    pub fn save_checked(
+        self,
+        spec: &PlotSpec,
+        series: &[FittedSeries],
+        save: SaveOptions<'_>,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.can_save(save.format) {
+            self.save(spec, series, save)
+        } else {
+            Err(Box::new(RendererError::CannotSave(
+                self.name(),
+                save.format,
+            )))
+        }
+    }
+}
+
+impl std::ops::Deref for Backends {
+    type Target = dyn Renderer;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl PartialEq for Backends {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for Backends {}
+
+impl std::fmt::Debug for Backends {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Backends").field(&self.name()).finish()
+    }
+}
+
+impl std::fmt::Display for Backends {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Error when converting from str to Backends
+#[derive(Clone)]
+pub enum BackendsFromStrError {
+    UnknownBackend(String),
+}
+
+impl std::fmt::Display for BackendsFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendsFromStrError::UnknownBackend(bstr) => write!(f, "Unknown backend: {}", bstr),
+        }
+    }
+}
+
+impl std::fmt::Debug for BackendsFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendsFromStrError::UnknownBackend(bstr) => write!(f, "Unknown backend: {:?}", bstr),
+        }
+    }
+}
+
+impl FromStr for Backends {
+    type Err = BackendsFromStrError;
+
+    /// Look up the renderer by its name
+    fn from_str(bstr: &str) -> Result<Self, Self::Err> {
+        let bstr = bstr.to_lowercase();
+        Backends::all()
+            .into_iter()
+            .find(|backend| backend.name() == bstr)
+            .ok_or(Self::Err::UnknownBackend(bstr))
+    }
+}
+
+/// Saved by name, so that the variants of the former enum still load
+impl serde::Serialize for Backends {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Backends {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Backends::from_str(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A renderer which cannot do anything, registered by the tests.
+    /// The registry is shared by all tests, so each uses its own names
+    struct NamedRenderer(&'static str);
+
+    impl Renderer for NamedRenderer {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn can_save(&self, _format: ImageFormat) -> bool {
+            false
+        }
+
+        fn save(
+            &self,
+            _spec: &PlotSpec,
+            _series: &[FittedSeries],
+            _save: SaveOptions<'_>,
+        ) -> Result<(), Box<dyn Error>> {
+            unreachable!("save_checked calls can_save first")
+        }
+    }
+
+    static OFFERED: NamedRenderer = NamedRenderer("offered");
+    static TWICE: NamedRenderer = NamedRenderer("twice");
+    static BUILT_IN_NAME: NamedRenderer = NamedRenderer("plotters");
+    static SAVED: NamedRenderer = NamedRenderer("saved");
+    static RACED: NamedRenderer = NamedRenderer("raced");
+
+    #[test]
+    fn registered_renderers_are_offered() {
+        register(&OFFERED).unwrap();
+        let offered = Backends::from_str("Offered").unwrap();
+        let all = Backends::all();
+        assert_eq!(&all[..2], &[Backends::GNUPLOT, Backends::PLOTTERS]);
+        assert!(all.contains(&offered));
+        assert_eq!(offered.display_name(), "offered");
+        assert!(!offered.can_show());
+        assert!(offered.show(&PlotSpec::default(), &[]).is_err());
+        assert!(offered.svg(&PlotSpec::default(), &[]).is_err());
+        let save = SaveOptions {
+            path: Path::new("graph.png"),
+            format: ImageFormat::Png,
+        };
+        let error = offered
+            .save_checked(&PlotSpec::default(), &[], save)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "The offered backend cannot save PNG");
+    }
+
+    #[test]
+    fn duplicate_names_are_refused() {
+        assert!(matches!(
+            register(&BUILT_IN_NAME),
+            Err(RendererError::DuplicateName("plotters"))
+        ));
+        register(&TWICE).unwrap();
+        assert!(matches!(
+            register(&TWICE),
+            Err(RendererError::DuplicateName("twice"))
+        ));
+        let named = |name| {
+            Backends::all()
+                .into_iter()
+                .filter(|backend| backend.name() == name)
+                .count()
+        };
+        assert_eq!(named("plotters"), 1);
+        assert_eq!(named("twice"), 1);
+    }
+
+    #[test]
+    fn saved_by_name() {
+        assert_eq!(
+            serde_json::to_string(&Backends::GNUPLOT).unwrap(),
+            "\"gnuplot\""
+        );
+        assert_eq!(
+            serde_json::from_str::<Backends>("\"Plotters\"").unwrap(),
+            Backends::PLOTTERS
+        );
+        assert!(serde_json::from_str::<Backends>("\"saved\"").is_err());
+        register(&SAVED).unwrap();
+        let saved: Backends = serde_json::from_str("\"saved\"").unwrap();
+        assert_eq!(saved.name(), "saved");
+        assert_eq!(serde_json::to_string(&saved).unwrap(), "\"saved\"");
+    }
+
+    #[test]
+    fn one_of_simultaneous_duplicates_is_taken() {
+        let threads: Vec<_> = (0..8)
+            .map(|_| std::thread::spawn(|| register(&RACED).is_ok()))
+            .collect();
+        let taken = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .filter(|taken| *taken)
+            .count();
+        assert_eq!(taken, 1);
+        assert_eq!(
+            Backends::all()
+                .into_iter()
+                .filter(|backend| backend.name() == "raced")
+                .count(),
+            1
+        );
+    }
+}
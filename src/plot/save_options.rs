@@ -17,9 +17,233 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
-/// Options to save as png
+use crate::plot::Backends;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Formats of saved images
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Svg,
+    Pdf,
+    /// Encapsulated PostScript
+    Eps,
+}
+
+impl ImageFormat {
+    /// All formats, in the order shown to the user
+    pub const ALL: [ImageFormat; 4] = [
+        ImageFormat::Png,
+        ImageFormat::Svg,
+        ImageFormat::Pdf,
+        ImageFormat::Eps,
+    ];
+
+    /// Guess the format from the extension of the path
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_str(path.extension()?.to_str()?).ok()
+    }
+
+    /// Format to save the graph to the path in, the chosen one or else that
+    /// of the extension, checking that the backend can write it
+    pub fn for_saving(
+        path: &Path,
+        chosen: Option<Self>,
+        backend: Backends,
+    ) -> Result<Self, SaveFormatError> {
+        let format = chosen
+            .or_else(|| Self::from_path(path))
+            .ok_or_else(|| SaveFormatError::UnknownExtension(path.to_path_buf()))?;
+        if backend.can_save(format) {
+            Ok(format)
+        } else {
+            Err(SaveFormatError::CannotSave(backend.name(), format))
+        }
+    }
+
+    /// File name extension of the format
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+            ImageFormat::Pdf => "pdf",
+            ImageFormat::Eps => "eps",
+        }
+    }
+
+    /// Name of the format shown in file dialogs
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "Portable Network Graphics",
+            ImageFormat::Svg => "Scalable Vector Graphics",
+            ImageFormat::Pdf => "Portable Document Format",
+            ImageFormat::Eps => "Encapsulated PostScript",
+        }
+    }
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Error when converting from str to `ImageFormat`
+#[derive(Clone, Debug)]
+pub enum ImageFormatFromStrError {
+    UnknownFormat(String),
+}
+
+impl std::fmt::Display for ImageFormatFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFormatFromStrError::UnknownFormat(fstr) => {
+                write!(f, "Unknown image format: {}", fstr)
+            }
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = ImageFormatFromStrError;
+
+    /// Parse image format description
+    fn from_str(fstr: &str) -> Result<Self, Self::Err> {
+        match fstr.to_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "svg" => Ok(ImageFormat::Svg),
+            "pdf" => Ok(ImageFormat::Pdf),
+            "eps" | "ps" => Ok(ImageFormat::Eps),
+            other => Err(Self::Err::UnknownFormat(other.to_string())),
+        }
+    }
+}
+
+/// Error when choosing the format of a saved graph
+#[derive(Clone, Debug)]
+pub enum SaveFormatError {
+    /// No format was chosen and the extension of the path is not known
+    UnknownExtension(PathBuf),
+    /// Name of the backend which cannot save the format
+    CannotSave(&'static str, ImageFormat),
+}
+
+impl std::fmt::Display for SaveFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveFormatError::UnknownExtension(path) => write!(
+                f,
+                "Cannot tell the image format of {}, use an extension of png, svg, pdf or eps or choose the format",
+                path.display()
+            ),
+            SaveFormatError::CannotSave(name, format) => write!(
+                f,
+                "The {} backend cannot save {}, try another backend",
+                name,
+                format.extension().to_uppercase()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveFormatError {}
+
+/// Options to save as an image, whose size is given by the `PlotSpec`
+#[derive(Debug, Copy, Clone)]
 pub struct SaveOptions<'path_lt> {
-    pub path: &'path_lt std::path::Path,
-    pub width: u32,
-    pub height: u32,
+    pub path: &'path_lt Path,
+    pub format: ImageFormat,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_extensions() {
+        for format in ImageFormat::ALL {
+            assert_eq!(format.extension().parse::<ImageFormat>().unwrap(), format);
+            assert_eq!(format.to_string(), format.extension());
+        }
+        assert_eq!(ImageFormat::from_str("PDF").unwrap(), ImageFormat::Pdf);
+        assert_eq!(ImageFormat::from_str("ps").unwrap(), ImageFormat::Eps);
+        assert!(ImageFormat::from_str("jpeg").is_err());
+    }
+
+    #[test]
+    fn formats_from_paths() {
+        for (path, format) in [
+            ("graph.png", Some(ImageFormat::Png)),
+            ("out/graph.svg", Some(ImageFormat::Svg)),
+            ("graph.v2.pdf", Some(ImageFormat::Pdf)),
+            ("graph.eps", Some(ImageFormat::Eps)),
+            ("GRAPH.PNG", Some(ImageFormat::Png)),
+            ("graph.Eps", Some(ImageFormat::Eps)),
+            ("graph", None),
+            ("graph.pdf.txt", None),
+            (".png", None),
+        ] {
+            assert_eq!(ImageFormat::from_path(Path::new(path)), format, "{}", path);
+        }
+    }
+
+    #[test]
+    fn vector_formats_by_backend() {
+        // Plotters needs the cairo surfaces of the GTK interface for PDF and EPS
+        for format in ImageFormat::ALL {
+            assert!(
+                Backends::GNUPLOT.can_save(format),
+                "gnuplot saves {}",
+                format
+            );
+            assert_eq!(
+                Backends::PLOTTERS.can_save(format),
+                matches!(format, ImageFormat::Png | ImageFormat::Svg),
+                "plotters saving {}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn format_for_saving() {
+        let choose = |path: &str, chosen, backend| {
+            ImageFormat::for_saving(Path::new(path), chosen, backend)
+                .map_err(|error| error.to_string())
+        };
+        for format in ImageFormat::ALL {
+            let path = format!("graph.{}", format.extension().to_uppercase());
+            assert_eq!(choose(&path, None, Backends::GNUPLOT), Ok(format));
+        }
+        // A chosen format wins over the extension
+        assert_eq!(
+            choose("graph.png", Some(ImageFormat::Svg), Backends::PLOTTERS),
+            Ok(ImageFormat::Svg)
+        );
+        assert_eq!(
+            choose("graph", Some(ImageFormat::Eps), Backends::GNUPLOT),
+            Ok(ImageFormat::Eps)
+        );
+        assert_eq!(
+            choose("graph", None, Backends::GNUPLOT),
+            Err(String::from("Cannot tell the image format of graph, use an extension of png, svg, pdf or eps or choose the format"))
+        );
+        assert_eq!(
+            choose("graph.pdf", None, Backends::PLOTTERS),
+            Err(String::from(
+                "The plotters backend cannot save PDF, try another backend"
+            ))
+        );
+        assert_eq!(
+            choose("graph.png", Some(ImageFormat::Eps), Backends::PLOTTERS),
+            Err(String::from(
+                "The plotters backend cannot save EPS, try another backend"
+            ))
+        );
+    }
 }
@@ -0,0 +1,255 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::{FitOptions, FitResults, TwoVarDataSet};
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// Colours given to series in turn, from Paul Tol's bright scheme
+const PALETTE: [Colour; 7] = [
+    Colour(0x44, 0x77, 0xAA),
+    Colour(0xEE, 0x66, 0x77),
+    Colour(0x22, 0x88, 0x33),
+    Colour(0xCC, 0xBB, 0x44),
+    Colour(0x66, 0xCC, 0xEE),
+    Colour(0xAA, 0x33, 0x77),
+    Colour(0xBB, 0xBB, 0xBB),
+];
+
+/// RGB colour, written as "#rrggbb"
+#[derive(PartialEq, Eq, Debug, Copy, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Colour(pub u8, pub u8, pub u8);
+
+impl Default for Colour {
+    fn default() -> Self {
+        PALETTE[0]
+    }
+}
+
+impl Colour {
+    /// Default colour of the nth series
+    #[must_use]
+    pub fn nth(index: usize) -> Self {
+        PALETTE[index % PALETTE.len()]
+    }
+
+    /// The colour mixed with white, for points excluded from the fits
+    #[must_use]
+    pub fn faded(self) -> Self {
+        let fade = |component: u8| component + (255 - component) / 3 * 2;
+        Colour(fade(self.0), fade(self.1), fade(self.2))
+    }
+}
+
+impl std::fmt::Display for Colour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Error when converting from str to `Colour`
+#[derive(Clone, Debug)]
+pub enum ColourFromStrError {
+    InvalidColour(String),
+}
+
+impl std::fmt::Display for ColourFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColourFromStrError::InvalidColour(cstr) => {
+                write!(f, "Invalid colour: {}, expected #rrggbb", cstr)
+            }
+        }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = ColourFromStrError;
+
+    /// Parse "#rrggbb" or "rrggbb"
+    fn from_str(cstr: &str) -> Result<Self, Self::Err> {
+        let hex = cstr.trim().trim_start_matches('#');
+        let component = |idx: usize| {
+            hex.get(idx..idx + 2)
+                .and_then(|part| u8::from_str_radix(part, 16).ok())
+                .ok_or_else(|| Self::Err::InvalidColour(cstr.to_string()))
+        };
+        if hex.len() != 6 {
+            return Err(Self::Err::InvalidColour(cstr.to_string()));
+        }
+        Ok(Colour(component(0)?, component(2)?, component(4)?))
+    }
+}
+
+impl TryFrom<String> for Colour {
+    type Error = ColourFromStrError;
+
+    fn try_from(cstr: String) -> Result<Self, Self::Error> {
+        Self::from_str(&cstr)
+    }
+}
+
+impl From<Colour> for String {
+    fn from(colour: Colour) -> Self {
+        colour.to_string()
+    }
+}
+
+/// Shapes of the data point markers
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Marker {
+    #[default]
+    Plus,
+    Cross,
+    Circle,
+    Square,
+    Triangle,
+}
+
+impl Marker {
+    /// All markers, in the order given to series
+    pub const ALL: [Marker; 5] = [
+        Marker::Plus,
+        Marker::Cross,
+        Marker::Circle,
+        Marker::Square,
+        Marker::Triangle,
+    ];
+
+    /// Default marker of the nth series
+    #[must_use]
+    pub fn nth(index: usize) -> Self {
+        Self::ALL[index % Self::ALL.len()]
+    }
+}
+
+impl std::fmt::Display for Marker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Marker::Plus => write!(f, "plus"),
+            Marker::Cross => write!(f, "cross"),
+            Marker::Circle => write!(f, "circle"),
+            Marker::Square => write!(f, "square"),
+            Marker::Triangle => write!(f, "triangle"),
+        }
+    }
+}
+
+/// Error when converting from str to `Marker`
+#[derive(Clone, Debug)]
+pub enum MarkerFromStrError {
+    UnknownMarker(String),
+}
+
+impl std::fmt::Display for MarkerFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarkerFromStrError::UnknownMarker(mstr) => write!(f, "Unknown marker: {}", mstr),
+        }
+    }
+}
+
+impl FromStr for Marker {
+    type Err = MarkerFromStrError;
+
+    /// Parse marker description
+    fn from_str(mstr: &str) -> Result<Self, Self::Err> {
+        match mstr.to_lowercase().as_str() {
+            "plus" => Ok(Marker::Plus),
+            "cross" => Ok(Marker::Cross),
+            "circle" => Ok(Marker::Circle),
+            "square" => Ok(Marker::Square),
+            "triangle" => Ok(Marker::Triangle),
+            other => Err(Self::Err::UnknownMarker(other.to_string())),
+        }
+    }
+}
+
+/// Appearance of a series. Its points, error bars and fitted lines share the colour
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SeriesStyle {
+    #[serde(default)]
+    pub colour: Colour,
+    #[serde(default)]
+    pub marker: Marker,
+}
+
+impl SeriesStyle {
+    /// Default style of the nth series, so that series can be told apart
+    #[must_use]
+    pub fn nth(index: usize) -> Self {
+        Self {
+            colour: Colour::nth(index),
+            marker: Marker::nth(index),
+        }
+    }
+}
+
+/// A named dataset to be plotted
+#[derive(Debug, Default, Clone)]
+pub struct Series {
+    /// Prefixed to the legend entries if not empty
+    pub name: String,
+    pub data: TwoVarDataSet,
+    pub style: SeriesStyle,
+}
+
+impl Series {
+    /// Legend entry of something belonging to this series
+    #[must_use]
+    pub fn label(&self, text: &str) -> String {
+        if self.name.is_empty() {
+            text.to_string()
+        } else {
+            format!("{}: {}", self.name, text)
+        }
+    }
+}
+
+/// A series with the fits of its data for the fit options of a spec, which
+/// are shared by the axis ranges, the intercepts and the renderers
+#[derive(Debug, Clone)]
+pub struct FittedSeries {
+    pub series: Series,
+    pub fits: FitResults,
+}
+
+impl FittedSeries {
+    /// Fit the data of the series with the fit options
+    #[must_use]
+    pub fn new(series: Series, fit: &FitOptions) -> Self {
+        let fits = FitResults::new(&series.data, fit);
+        Self { series, fits }
+    }
+
+    /// Fit every series with the fit options
+    #[must_use]
+    pub fn fit_all(series: Vec<Series>, fit: &FitOptions) -> Vec<Self> {
+        series.into_iter().map(|one| Self::new(one, fit)).collect()
+    }
+}
+
+impl Deref for FittedSeries {
+    type Target = Series;
+
+    fn deref(&self) -> &Self::Target {
+        &self.series
+    }
+}
@@ -0,0 +1,489 @@
+//
+//  Copyright (C) 2022 Zhang Maiyun <me@myzhangll.xyz>
+//
+//  This file is part of physics plotter.
+//
+//  Physics plotter is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  Physics plotter is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+//
+//  You should have received a copy of the GNU General Public License
+//  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
+//
+
+use crate::data::{FitMethod, FitModel, FitOptions, GradientLineMethod, Notation, Transforms};
+use crate::default_values as defv;
+use crate::plot::AxisScale;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Pixels per inch of the vector formats, whose sizes are in inches
+const PIXELS_PER_INCH: f32 = 96.0;
+
+/// Most ticks of each kind on an axis, above which the spacing is ignored
+const MAX_TICKS: f64 = 200.0;
+
+/// Corner of the chart holding the legend
+#[derive(PartialEq, Eq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub enum LegendPosition {
+    UpperLeft,
+    #[default]
+    UpperRight,
+    LowerLeft,
+    LowerRight,
+}
+
+impl LegendPosition {
+    /// All positions, in the order shown to the user
+    pub const ALL: [LegendPosition; 4] = [
+        LegendPosition::UpperLeft,
+        LegendPosition::UpperRight,
+        LegendPosition::LowerLeft,
+        LegendPosition::LowerRight,
+    ];
+
+    /// Whether the legend is on the left of the chart
+    #[must_use]
+    pub fn left(self) -> bool {
+        matches!(self, LegendPosition::UpperLeft | LegendPosition::LowerLeft)
+    }
+
+    /// Whether the legend is at the top of the chart
+    #[must_use]
+    pub fn upper(self) -> bool {
+        matches!(self, LegendPosition::UpperLeft | LegendPosition::UpperRight)
+    }
+}
+
+impl std::fmt::Display for LegendPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LegendPosition::UpperLeft => write!(f, "upper-left"),
+            LegendPosition::UpperRight => write!(f, "upper-right"),
+            LegendPosition::LowerLeft => write!(f, "lower-left"),
+            LegendPosition::LowerRight => write!(f, "lower-right"),
+        }
+    }
+}
+
+/// Error when converting from str to `LegendPosition`
+#[derive(Clone, Debug)]
+pub enum LegendPositionFromStrError {
+    UnknownPosition(String),
+}
+
+impl std::fmt::Display for LegendPositionFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LegendPositionFromStrError::UnknownPosition(pstr) => {
+                write!(f, "Unknown legend position: {}", pstr)
+            }
+        }
+    }
+}
+
+impl FromStr for LegendPosition {
+    type Err = LegendPositionFromStrError;
+
+    /// Parse legend position description
+    fn from_str(pstr: &str) -> Result<Self, Self::Err> {
+        match pstr.to_lowercase().as_str() {
+            "upper-left" => Ok(LegendPosition::UpperLeft),
+            "upper-right" => Ok(LegendPosition::UpperRight),
+            "lower-left" => Ok(LegendPosition::LowerLeft),
+            "lower-right" => Ok(LegendPosition::LowerRight),
+            other => Err(Self::Err::UnknownPosition(other.to_string())),
+        }
+    }
+}
+
+/// Size of the graph in pixels
+#[derive(PartialEq, Eq, Debug, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PlotSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for PlotSize {
+    fn default() -> Self {
+        Self {
+            width: defv::WIDTH.parse().unwrap(),
+            height: defv::HEIGHT.parse().unwrap(),
+        }
+    }
+}
+
+impl PlotSize {
+    /// Size in inches, for the vector formats
+    #[must_use]
+    pub fn in_inches(self) -> (f32, f32) {
+        (
+            self.width as f32 / PIXELS_PER_INCH,
+            self.height as f32 / PIXELS_PER_INCH,
+        )
+    }
+}
+
+impl From<PlotSize> for (u32, u32) {
+    fn from(size: PlotSize) -> Self {
+        (size.width, size.height)
+    }
+}
+
+/// Spacing of the ticks of a linear axis, ignored on log axes
+#[derive(PartialEq, Debug, Default, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TickSpacing {
+    /// Distance between the labelled ticks, chosen by the backend if None
+    pub major: Option<f64>,
+    /// Distance between the unlabelled ticks, rounded to divide the major
+    /// spacing. Only used with a major spacing
+    pub minor: Option<f64>,
+}
+
+impl TickSpacing {
+    /// Major spacing if it gives a sensible number of ticks on the range
+    pub(crate) fn major_spacing(&self, range: &Range<f64>) -> Option<f64> {
+        self.major
+            .filter(|&major| major > 0.0 && (range.end - range.start) / major <= MAX_TICKS)
+    }
+
+    /// Number of intervals the unlabelled ticks divide each major interval
+    /// into, 1 if there are none
+    pub(crate) fn minor_intervals(&self, range: &Range<f64>) -> u32 {
+        match (self.major_spacing(range), self.minor) {
+            (Some(major), Some(minor)) if minor > 0.0 => {
+                (major / minor).round().clamp(1.0, MAX_TICKS) as u32
+            }
+            _ => 1,
+        }
+    }
+
+    /// Positions of the labelled ticks in the range, None if automatic
+    pub(crate) fn major_ticks(&self, range: &Range<f64>) -> Option<Vec<f64>> {
+        self.major_spacing(range)
+            .map(|major| multiples(range, major))
+    }
+
+    /// Positions of the unlabelled ticks in the range, including those at
+    /// the labelled ticks
+    pub(crate) fn minor_ticks(&self, range: &Range<f64>) -> Vec<f64> {
+        match self.major_spacing(range) {
+            Some(major) => {
+                let intervals = self.minor_intervals(range);
+                let minor = major / f64::from(intervals);
+                if intervals > 1 && (range.end - range.start) / minor <= MAX_TICKS {
+                    multiples(range, minor)
+                } else {
+                    Vec::new()
+                }
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for TickSpacing {
+    /// Written as parsed by `from_str`, empty if automatic
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.major, self.minor) {
+            (Some(major), Some(minor)) => write!(f, "{}:{}", major, minor),
+            (Some(major), None) => write!(f, "{}", major),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+impl FromStr for TickSpacing {
+    type Err = AxisOptionFromStrError;
+
+    /// Parse a spacing such as 5, or 5:1 with unlabelled ticks, or an empty
+    /// string for automatic ticks
+    fn from_str(tstr: &str) -> Result<Self, Self::Err> {
+        let tstr = tstr.trim();
+        if tstr.is_empty() {
+            return Ok(Self::default());
+        }
+        let (major, minor) = match tstr.split_once(':') {
+            Some((major, minor)) => (major, Some(minor)),
+            None => (tstr, None),
+        };
+        let spacing = |value: &str| match parse_number(value)? {
+            value if value > 0.0 => Ok(value),
+            _ => Err(AxisOptionFromStrError::NonPositiveSpacing),
+        };
+        Ok(Self {
+            major: Some(spacing(major)?),
+            minor: minor.map(spacing).transpose()?,
+        })
+    }
+}
+
+/// Error when parsing an axis range or a tick spacing
+#[derive(Clone, Debug)]
+pub enum AxisOptionFromStrError {
+    InvalidNumber(String),
+    /// Not in the form START:END
+    NotARange(String),
+    EmptyRange,
+    NonPositiveSpacing,
+}
+
+impl std::fmt::Display for AxisOptionFromStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AxisOptionFromStrError::InvalidNumber(nstr) => write!(f, "Invalid number: {}", nstr),
+            AxisOptionFromStrError::NotARange(rstr) => {
+                write!(f, "Expected a range such as 0:10, got {}", rstr)
+            }
+            AxisOptionFromStrError::EmptyRange => {
+                write!(f, "The start of a range must be less than the end")
+            }
+            AxisOptionFromStrError::NonPositiveSpacing => {
+                write!(f, "The spacing of ticks must be positive")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AxisOptionFromStrError {}
+
+fn parse_number(nstr: &str) -> Result<f64, AxisOptionFromStrError> {
+    let nstr = nstr.trim();
+    nstr.parse()
+        .map_err(|_| AxisOptionFromStrError::InvalidNumber(nstr.to_string()))
+}
+
+/// Parse an axis range such as 0:10
+pub fn parse_axis_range(rstr: &str) -> Result<Range<f64>, AxisOptionFromStrError> {
+    let (start, end) = rstr
+        .split_once(':')
+        .ok_or_else(|| AxisOptionFromStrError::NotARange(rstr.to_string()))?;
+    let (start, end) = (parse_number(start)?, parse_number(end)?);
+    if start < end {
+        Ok(start..end)
+    } else {
+        Err(AxisOptionFromStrError::EmptyRange)
+    }
+}
+
+/// Write an axis range as parsed by `parse_axis_range`
+#[must_use]
+pub fn format_axis_range(range: &Range<f64>) -> String {
+    format!("{}:{}", range.start, range.end)
+}
+
+/// Multiples of `step` in the range
+fn multiples(range: &Range<f64>, step: f64) -> Vec<f64> {
+    let first = (range.start / step).ceil() as i64;
+    let last = (range.end / step).floor() as i64;
+    (first..=last).map(|index| index as f64 * step).collect()
+}
+
+/// Fonts of the texts on the graph
+#[derive(PartialEq, Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Fonts {
+    pub family: String,
+    /// Sizes in points
+    pub title_size: f64,
+    /// Size of the axis labels, the ticks, the legend and the statistics
+    pub label_size: f64,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Self {
+            family: String::from(defv::FONT_FAMILY),
+            title_size: defv::TITLE_FONT_SIZE.parse().unwrap(),
+            label_size: defv::LABEL_FONT_SIZE.parse().unwrap(),
+        }
+    }
+}
+
+/// Everything about how a graph looks, shared by the backends and the front
+/// ends. The data and the style of each series are kept in `Series`
+#[derive(PartialEq, Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PlotSpec {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    #[serde(flatten)]
+    pub fit: FitOptions,
+    #[serde(default)]
+    pub axis_scale: AxisScale,
+    /// Fixed range of the x axis, covering the data if None
+    #[serde(default)]
+    pub x_range: Option<Range<f64>>,
+    /// Fixed range of the y axis, covering the data if None
+    #[serde(default)]
+    pub y_range: Option<Range<f64>>,
+    /// Whether the axes are extended to the origin and the y intercepts of
+    /// the fits, on linear axes without fixed ranges
+    #[serde(default)]
+    pub include_origin: bool,
+    /// Range of x over which the fits are drawn, extrapolating them beyond
+    /// the data, or that of the data if None
+    #[serde(default)]
+    pub fit_x_range: Option<Range<f64>>,
+    /// Whether the intercepts of the straight-line fits on the axes are
+    /// marked on linear charts, with the uncertainties from the gradient
+    /// lines. The axes without fixed ranges are extended to show them
+    #[serde(default)]
+    pub annotate_intercepts: bool,
+    /// How the powers of ten of the fitted parameters are written
+    #[serde(default)]
+    pub notation: Notation,
+    #[serde(default)]
+    pub x_ticks: TickSpacing,
+    #[serde(default)]
+    pub y_ticks: TickSpacing,
+    #[serde(default)]
+    pub legend: LegendPosition,
+    #[serde(default)]
+    pub size: PlotSize,
+    #[serde(default)]
+    pub fonts: Fonts,
+}
+
+impl Default for PlotSpec {
+    fn default() -> Self {
+        Self {
+            title: String::from(defv::TITLE),
+            x_label: String::from(defv::X_LABEL),
+            y_label: String::from(defv::Y_LABEL),
+            fit: FitOptions {
+                model: FitModel::Line,
+                fit_method: FitMethod::from_str(defv::FIT_METHOD).unwrap(),
+                gradient_line_method: GradientLineMethod::from_str(defv::GRADIENT_LINE_METHOD)
+                    .unwrap(),
+                residuals: false,
+                outlier_threshold: defv::OUTLIER_THRESHOLD.parse().unwrap(),
+            },
+            axis_scale: AxisScale::from_str(defv::AXIS_SCALE).unwrap(),
+            x_range: None,
+            y_range: None,
+            include_origin: false,
+            fit_x_range: None,
+            annotate_intercepts: false,
+            notation: Notation::from_str(defv::NOTATION).unwrap(),
+            x_ticks: TickSpacing::default(),
+            y_ticks: TickSpacing::default(),
+            legend: LegendPosition::from_str(defv::LEGEND_POSITION).unwrap(),
+            size: PlotSize::default(),
+            fonts: Fonts::default(),
+        }
+    }
+}
+
+impl PlotSpec {
+    /// The same graph with the axis labels showing the transforms of the data
+    #[must_use]
+    pub fn transformed(&self, transforms: Transforms) -> Self {
+        Self {
+            x_label: transforms.x_transform.label(&self.x_label),
+            y_label: transforms.y_transform.label(&self.y_label),
+            ..self.clone()
+        }
+    }
+
+    /// Whether the intercepts are marked, which is only done on linear charts
+    pub(crate) fn intercepts_shown(&self) -> bool {
+        self.annotate_intercepts && self.axis_scale == AxisScale::Linear
+    }
+
+    /// Whether the linear axes without fixed ranges reach the origin
+    pub(crate) fn reaches_origin(&self) -> bool {
+        self.include_origin || self.intercepts_shown()
+    }
+
+    /// Whether the range of the x axis is set rather than left to gnuplot.
+    /// Log axes are always set so that error bars crossing zero can be clipped,
+    /// and extrapolated fits so that the data stays on the chart
+    pub(crate) fn fixed_x(&self) -> bool {
+        self.axis_scale.log_x()
+            || self.x_range.is_some()
+            || self.reaches_origin()
+            || self.fit_x_range.is_some()
+    }
+
+    /// Whether the range of the y axis is set rather than left to gnuplot
+    pub(crate) fn fixed_y(&self) -> bool {
+        self.axis_scale.log_y() || self.y_range.is_some() || self.reaches_origin()
+    }
+
+    /// Font of the title for plotters
+    pub(crate) fn title_font(&self) -> (&str, f64) {
+        (&self.fonts.family, self.fonts.title_size)
+    }
+
+    /// Font of the other texts for plotters
+    pub(crate) fn label_font(&self) -> (&str, f64) {
+        (&self.fonts.family, self.fonts.label_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_spacing_from_str() {
+        assert_eq!(TickSpacing::from_str("").unwrap(), TickSpacing::default());
+        assert_eq!(
+            TickSpacing::from_str("5").unwrap(),
+            TickSpacing {
+                major: Some(5.0),
+                minor: None,
+            }
+        );
+        let spacing = TickSpacing::from_str(" 2 : 0.5 ").unwrap();
+        assert_eq!(spacing.major, Some(2.0));
+        assert_eq!(spacing.minor, Some(0.5));
+        assert_eq!(spacing.to_string(), "2:0.5");
+        assert_eq!(
+            TickSpacing::from_str(&spacing.to_string()).unwrap(),
+            spacing
+        );
+        for tstr in ["0", "-1", "5:0", "5:-1", "-2:1"] {
+            assert!(
+                matches!(
+                    TickSpacing::from_str(tstr),
+                    Err(AxisOptionFromStrError::NonPositiveSpacing)
+                ),
+                "{}",
+                tstr
+            );
+        }
+        assert!(matches!(
+            TickSpacing::from_str("a:1"),
+            Err(AxisOptionFromStrError::InvalidNumber(nstr)) if nstr == "a"
+        ));
+    }
+
+    #[test]
+    fn axis_range_from_str() {
+        assert_eq!(parse_axis_range("0:10").unwrap(), 0.0..10.0);
+        assert_eq!(parse_axis_range(" -1.5 : 2e3 ").unwrap(), -1.5..2000.0);
+        let range = parse_axis_range(&format_axis_range(&(-0.25..4.0))).unwrap();
+        assert_eq!(range, -0.25..4.0);
+        for rstr in ["10:0", "3:3"] {
+            assert!(matches!(
+                parse_axis_range(rstr),
+                Err(AxisOptionFromStrError::EmptyRange)
+            ));
+        }
+        assert!(matches!(
+            parse_axis_range("10"),
+            Err(AxisOptionFromStrError::NotARange(rstr)) if rstr == "10"
+        ));
+        assert!(matches!(
+            parse_axis_range("0:x"),
+            Err(AxisOptionFromStrError::InvalidNumber(nstr)) if nstr == "x"
+        ));
+    }
+}
@@ -17,8 +17,17 @@
 //  along with physics plotter.  If not, see <https://www.gnu.org/licenses/>.
 //
 
+//! The `.psp` files saved by the graphical interfaces.
+//!
+//! Files created by earlier versions still load. Fields marked
+//! `#[serde(default)]` here and in the structures flattened into
+//! `PhysPlotterFile` are missing from files created before they were
+//! introduced, and older layouts are moved into place by `upgrade`
+
+use crate::data::{DecimalSeparator, ParseError, Repeats, Transforms, TwoVarDataSet, Uncertainty};
+use crate::plot::{PlotSpec, Series, SeriesStyle};
 use serde::{Deserialize, Serialize};
-use serde_json::{from_reader, to_writer};
+use serde_json::{from_reader, from_value, to_writer, Value};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::Path;
@@ -27,13 +36,68 @@ use std::path::Path;
 pub struct PhysPlotterFile {
     pub version: String,
     pub creator: String,
-    pub title: String,
     pub backend_name: String,
-    pub x_label: String,
-    pub y_label: String,
-    pub default_x_uncertainty: f64,
-    pub default_y_uncertainty: f64,
+    pub series: Vec<SavedSeries>,
+    /// Kept at the top level, where the title, the labels and the fit
+    /// options were before the specification was introduced
+    #[serde(flatten)]
+    pub spec: PlotSpec,
+    #[serde(flatten)]
+    pub transforms: Transforms,
+}
+
+/// A series as entered by the user
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SavedSeries {
+    #[serde(default)]
+    pub name: String,
+    /// Numbers in files created before percentages were introduced
+    pub default_x_uncertainty: Uncertainty,
+    pub default_y_uncertainty: Uncertainty,
     pub dataset: String,
+    #[serde(default)]
+    pub decimal_separator: DecimalSeparator,
+    #[serde(default)]
+    pub repeats: Repeats,
+    #[serde(flatten)]
+    pub style: SeriesStyle,
+}
+
+impl SavedSeries {
+    /// Parse the dataset into a series to be plotted
+    pub fn to_series(&self) -> Result<Series, ParseError> {
+        Ok(Series {
+            name: self.name.clone(),
+            data: TwoVarDataSet::from_string(
+                &self.dataset,
+                self.default_x_uncertainty,
+                self.default_y_uncertainty,
+                self.repeats,
+                self.decimal_separator,
+            )?,
+            style: self.style,
+        })
+    }
+}
+
+/// Move the only dataset of files created before multiple series were
+/// introduced into `series`
+fn upgrade(mut value: Value) -> Value {
+    if let Value::Object(map) = &mut value {
+        if !map.contains_key("series") {
+            let mut series = serde_json::Map::new();
+            for key in ["default_x_uncertainty", "default_y_uncertainty", "dataset"] {
+                if let Some(field) = map.remove(key) {
+                    series.insert(key.to_string(), field);
+                }
+            }
+            map.insert(
+                String::from("series"),
+                Value::Array(vec![Value::Object(series)]),
+            );
+        }
+    }
+    value
 }
 
 impl TryInto<String> for PhysPlotterFile {
@@ -54,14 +118,14 @@ impl PhysPlotterFile {
 
     /// Try to parse a reader as `PhysPlotterFile`
     pub fn from_reader<R: std::io::Read>(reader: R) -> serde_json::Result<Self> {
-        from_reader::<_, PhysPlotterFile>(reader)
+        from_value(upgrade(from_reader(reader)?))
     }
 
     /// Open filename and try to parse it as `PhysPlotterFile`
     pub fn from_file<P: AsRef<Path>>(filename: P) -> std::io::Result<Self> {
         let file = File::open(&filename)?;
         let reader = BufReader::new(file);
-        if let Ok(result) = from_reader::<_, PhysPlotterFile>(reader) {
+        if let Ok(result) = Self::from_reader(reader) {
             Ok(result)
         } else {
             Err(std::io::Error::new(
@@ -71,3 +135,123 @@ impl PhysPlotterFile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{
+        FitMethod, FitModel, FitOptions, GradientLineMethod, Notation, RepeatUncertainty, Transform,
+    };
+    use crate::plot::{AxisScale, LegendPosition, PlotSize};
+
+    /// A file as saved before the fit options, the series and the
+    /// specification were introduced
+    const BASELINE: &str = r#"{"version":"0.3.0","creator":"physics plotter","title":"Falling ball","backend_name":"plotters","x_label":"t","y_label":"v","default_x_uncertainty":0.1,"default_y_uncertainty":0.2,"dataset":"1 2\n2 4.1\n3 5.9"}"#;
+
+    #[test]
+    fn loads_baseline_file() {
+        let file = PhysPlotterFile::from_reader(BASELINE.as_bytes()).unwrap();
+        assert_eq!(file.backend_name, "plotters");
+        assert_eq!(file.spec.title, "Falling ball");
+        assert_eq!(file.spec.x_label, "t");
+        assert_eq!(file.spec.y_label, "v");
+        assert_eq!(file.spec.fit, FitOptions::default());
+        assert_eq!(file.spec.axis_scale, AxisScale::Linear);
+        assert_eq!(file.spec.x_range, None);
+        assert_eq!(file.transforms, Transforms::default());
+        // The dataset moves into the only series
+        assert_eq!(file.series.len(), 1);
+        let saved = &file.series[0];
+        assert_eq!(saved.name, "");
+        assert_eq!(saved.default_x_uncertainty, Uncertainty::from(0.1));
+        assert_eq!(saved.default_y_uncertainty, Uncertainty::from(0.2));
+        assert_eq!(saved.decimal_separator, DecimalSeparator::Point);
+        assert_eq!(saved.repeats, Repeats::default());
+        let series = saved.to_series().unwrap();
+        let ys: Vec<f64> = series.data.iter().map(|point| point.y_value).collect();
+        assert_eq!(ys, [2.0, 4.1, 5.9]);
+        assert!(series
+            .data
+            .iter()
+            .all(|point| point.x_uncertainty == 0.1 && point.y_uncertainty == 0.2));
+    }
+
+    #[test]
+    fn round_trips_current_file() {
+        let file = PhysPlotterFile {
+            version: String::from("1.0.0"),
+            creator: String::from("physics plotter"),
+            backend_name: String::from("gnuplot"),
+            series: vec![SavedSeries {
+                name: String::from("Run 1"),
+                default_x_uncertainty: Uncertainty {
+                    percentage: 0.5,
+                    absolute: 0.01,
+                },
+                default_y_uncertainty: Uncertainty::from(0.2),
+                dataset: String::from("1,5 [2,0 2,2]\n2,5 [4,1 4,3]"),
+                decimal_separator: DecimalSeparator::Comma,
+                repeats: Repeats {
+                    uncertainty: RepeatUncertainty::StandardError,
+                    resolution: 0.05,
+                },
+                style: SeriesStyle::nth(2),
+            }],
+            spec: PlotSpec {
+                title: String::from("Decay"),
+                fit: FitOptions {
+                    model: FitModel::Expression {
+                        expression: String::from("a*exp(-x/tau)"),
+                        initial_guesses: String::from("a=1; tau=2"),
+                    },
+                    fit_method: FitMethod::York,
+                    gradient_line_method: GradientLineMethod::AllPoints,
+                    residuals: true,
+                    outlier_threshold: 3.0,
+                },
+                axis_scale: AxisScale::LogY,
+                x_range: Some(0.0..10.0),
+                fit_x_range: Some(-1.0..12.0),
+                include_origin: true,
+                annotate_intercepts: true,
+                notation: Notation::Scientific,
+                legend: LegendPosition::UpperLeft,
+                size: PlotSize {
+                    width: 800,
+                    height: 600,
+                },
+                ..PlotSpec::default()
+            },
+            transforms: Transforms {
+                x_transform: Transform::Reciprocal,
+                y_transform: Transform::Ln,
+            },
+        };
+        let text: String = serde_json::to_string(&file).unwrap();
+        // The specification and the transforms are saved at the top level
+        let value: Value = serde_json::from_str(&text).unwrap();
+        for key in ["title", "model", "fit_method", "x_range", "x_transform"] {
+            assert!(value.get(key).is_some(), "{} at the top level", key);
+        }
+        let loaded = PhysPlotterFile::from_reader(text.as_bytes()).unwrap();
+        assert_eq!(loaded.version, file.version);
+        assert_eq!(loaded.backend_name, file.backend_name);
+        assert_eq!(loaded.spec, file.spec);
+        assert_eq!(loaded.transforms, file.transforms);
+        assert_eq!(loaded.series.len(), 1);
+        let (saved, loaded_series) = (&file.series[0], &loaded.series[0]);
+        assert_eq!(loaded_series.name, saved.name);
+        assert_eq!(
+            loaded_series.default_x_uncertainty,
+            saved.default_x_uncertainty
+        );
+        assert_eq!(
+            loaded_series.default_y_uncertainty,
+            saved.default_y_uncertainty
+        );
+        assert_eq!(loaded_series.dataset, saved.dataset);
+        assert_eq!(loaded_series.decimal_separator, saved.decimal_separator);
+        assert_eq!(loaded_series.repeats, saved.repeats);
+        assert_eq!(loaded_series.style, saved.style);
+    }
+}